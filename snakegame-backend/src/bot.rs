@@ -0,0 +1,60 @@
+pub mod bot {
+    // Steering for snakes nobody's at the controls of, e.g. while their player
+    // reconnects. A bot heads for the nearest food it can see, and carries
+    // straight on when there's none.
+    use crate::game::common::Coordinates;
+    use crate::game::game::Game;
+    use std::f64::consts::PI;
+
+    // how far from its head a bot sees food
+    pub const SIGHT: f64 = 25.0;
+
+    // How far to turn the snake towards its nearest food, no further than
+    // max_turn, if it needs turning at all
+    pub fn steer(game: &Game, snake_id: usize, max_turn: f64) -> Option<f64> {
+        let snake = game.get_snake(snake_id).ok()?;
+        let loc = snake.get_location();
+        let vel = snake.get_velocity();
+        let distance = |food: &Coordinates| (food.x - loc.x).hypot(food.y - loc.y);
+        let nearest = game.get_food_near(loc, SIGHT).into_iter()
+            .filter_map(|food_id| game._get_food(food_id).ok().map(|this_food| this_food.get_location()))
+            .min_by(|food1, food2| distance(food1).partial_cmp(&distance(food2)).unwrap())?;
+        let heading = vel.delta_y.atan2(vel.delta_x);
+        let bearing = (nearest.y - loc.y).atan2(nearest.x - loc.x);
+        // whichever way round is shorter
+        let mut turn = bearing - heading;
+        if turn > PI {
+            turn -= 2.0 * PI;
+        } else if turn < -PI {
+            turn += 2.0 * PI;
+        }
+        let turn = turn.max(-max_turn).min(max_turn);
+        if turn.abs() < 1e-9 {
+            None
+        } else {
+            Some(turn)
+        }
+    }
+
+    #[test]
+    fn t_steer() {
+        use std::f64::consts::FRAC_PI_2;
+        // snakes start out at the origin heading up
+        let mut game = Game::new_blank_game();
+        let snake_id = game.create_snake(0.0).unwrap();
+        assert_eq!(None, steer(&game, snake_id, PI));
+        game.add_food(30.0, 0.0, 0.0).unwrap();
+        assert_eq!(None, steer(&game, snake_id, PI));
+
+        // food to the right is a quarter turn clockwise, taken a bit at a time
+        game.add_food(5.0, 0.0, 0.0).unwrap();
+        assert!((steer(&game, snake_id, PI).unwrap() + FRAC_PI_2).abs() < 1e-9);
+        assert_eq!(Some(-0.5), steer(&game, snake_id, 0.5));
+        // and the nearest food wins
+        game.add_food(-3.0, 0.0, 0.0).unwrap();
+        assert!((steer(&game, snake_id, PI).unwrap() - FRAC_PI_2).abs() < 1e-9);
+        game.add_food(0.0, 1.0, 0.0).unwrap();
+        assert_eq!(None, steer(&game, snake_id, PI));
+        assert_eq!(None, steer(&game, snake_id + 1, PI));
+    }
+}
@@ -0,0 +1,130 @@
+pub mod clocksync {
+    // How far another clock is from ours, reckoned the way NTP does it. Each
+    // round trip gives four times:
+    //   t0 we send, t1 they receive, t2 they answer, t3 we receive the answer
+    // where t0 and t3 are by our clock and t1 and t2 by theirs. Assuming the
+    // trip takes as long each way, their clock is ((t1 - t0) + (t2 - t3)) / 2
+    // ahead of ours, and the trip took (t3 - t0) - (t2 - t1) there and back.
+    //
+    // Samples are smoothed, the early ones averaged. A sample whose round trip
+    // is well over the usual one is left out, since a trip slow one way and not
+    // the other throws its offset out by up to half the difference.
+    const OUTLIER_FACTOR: f64 = 2.0;
+    // round trips this much over the usual one are never outliers, so a
+    // connection with next to no latency doesn't refuse every sample
+    const OUTLIER_SLACK: f64 = 0.005;
+
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    pub struct SyncSample {
+        // how far their clock is ahead of ours, in seconds
+        pub offset: f64,
+        pub round_trip: f64,
+    }
+    impl SyncSample {
+        pub fn from_times(t0: f64, t1: f64, t2: f64, t3: f64) -> SyncSample {
+            SyncSample {
+                offset: ((t1 - t0) + (t2 - t3)) / 2.0,
+                round_trip: (t3 - t0) - (t2 - t1),
+            }
+        }
+    }
+
+    pub struct ClockSync {
+        // how much each new sample counts, once there are enough to go on
+        smoothing: f64,
+        offset: Option<f64>,
+        round_trip: Option<f64>,
+        samples: usize,
+    }
+
+    impl ClockSync {
+        pub fn new(smoothing: f64) -> ClockSync {
+            ClockSync {
+                smoothing,
+                offset: None,
+                round_trip: None,
+                samples: 0,
+            }
+        }
+
+        // Fold in a sample, returning whether it counted towards the offset
+        pub fn add_sample(&mut self, sample: SyncSample) -> bool {
+            if !sample.offset.is_finite() || !sample.round_trip.is_finite() || sample.round_trip < 0.0 {
+                return false;
+            }
+            let outlier = match self.round_trip {
+                Some(round_trip) => sample.round_trip > round_trip * OUTLIER_FACTOR + OUTLIER_SLACK,
+                None => false,
+            };
+            self.samples += 1;
+            // the first few are averaged, then each counts as much as smoothing says
+            let weight = (1.0 / self.samples as f64).max(self.smoothing);
+            // the usual round trip moves with every sample, so a connection that
+            // slows down for good stops having its samples left out
+            self.round_trip = Some(match self.round_trip {
+                Some(round_trip) => round_trip + weight * (sample.round_trip - round_trip),
+                None => sample.round_trip,
+            });
+            if outlier {
+                return false;
+            }
+            self.offset = Some(match self.offset {
+                Some(offset) => offset + weight * (sample.offset - offset),
+                None => sample.offset,
+            });
+            true
+        }
+
+        // How far their clock is ahead of ours, once there's a sample to go on
+        pub fn get_offset(&self) -> Option<f64> {
+            self.offset
+        }
+
+        pub fn get_round_trip(&self) -> Option<f64> {
+            self.round_trip
+        }
+
+        // A time by their clock, by ours
+        pub fn to_local_time(&self, remote_time: f64) -> Option<f64> {
+            self.offset.map(|offset| remote_time - offset)
+        }
+    }
+
+    #[test]
+    fn t_from_times() {
+        // their clock is 10s ahead, and it's 0.1s each way
+        let sample = SyncSample::from_times(100.0, 110.1, 110.15, 100.25);
+        assert!((sample.offset - 10.0).abs() < 1e-9);
+        assert!((sample.round_trip - 0.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn t_smoothing() {
+        let mut sync = ClockSync::new(0.25);
+        assert_eq!(None, sync.to_local_time(5.0));
+        assert!(sync.add_sample(SyncSample { offset: 10.0, round_trip: 0.2 }));
+        assert_eq!(Some(10.0), sync.get_offset());
+        assert_eq!(Some(-5.0), sync.to_local_time(5.0));
+        // the first few are averaged
+        assert!(sync.add_sample(SyncSample { offset: 12.0, round_trip: 0.2 }));
+        assert_eq!(Some(11.0), sync.get_offset());
+
+        // a slow trip is left out of the offset, but not the round trip
+        assert!(!sync.add_sample(SyncSample { offset: 50.0, round_trip: 1.0 }));
+        assert_eq!(Some(11.0), sync.get_offset());
+        assert!(sync.get_round_trip().unwrap() > 0.2);
+
+        // nonsense is left out of both
+        let round_trip = sync.get_round_trip();
+        assert!(!sync.add_sample(SyncSample { offset: f64::NAN, round_trip: 0.2 }));
+        assert!(!sync.add_sample(SyncSample { offset: 11.0, round_trip: -0.1 }));
+        assert_eq!(round_trip, sync.get_round_trip());
+
+        // later samples count for a quarter each
+        for _i in 0..40 {
+            sync.add_sample(SyncSample { offset: 20.0, round_trip: 0.2 });
+        }
+        assert!((sync.get_offset().unwrap() - 20.0).abs() < 0.01);
+        assert!((sync.get_round_trip().unwrap() - 0.2).abs() < 0.01);
+    }
+}
@@ -0,0 +1,674 @@
+pub mod command {
+    // Checks on what players ask of a Game, before the game hears of it.
+    //
+    // Game trusts whatever it's told, which is fine for the code that drives it
+    // but not for players on the other end of a socket. A CommandGuard knows
+    // which snakes each player controls, and lets a command through only if
+    // it's for one of them, its numbers are finite, its event_time is close to
+    // the game clock, and its turn is neither sharper nor faster than a snake
+    // can turn. Everything turned away is kept in the security log, and a
+    // player turned away max_strikes times within strike_memory is kicked.
+    //
+    // Players each have a bucket of commands that fills at command_rate up to
+    // command_burst; commands when it's empty are dropped, without a strike.
+    // The turns that get through are held until flush(), which the game's
+    // driver calls before each clock advance, and each snake's are made as one
+    // net turn - so the game logs at most a turn per snake per tick, however
+    // many a player sends.
+    //
+    // A player's first snake comes with a session token, which is all it takes
+    // to come back as them. When a player's connection drops, their snakes are
+    // held for reconnect_grace - carrying straight on, unless the game's driver
+    // steers them - and a reconnect with the token takes them over again, from
+    // whichever connection had them. After that they're gone.
+    use crate::eventqueue::eventqueue::LogError;
+    use crate::game::game::{Game, GameError};
+    #[cfg(feature = "serde")]
+    use serde::{Deserialize, Serialize};
+    use std::collections::{BTreeMap, BTreeSet, VecDeque};
+    use std::f64::consts::PI;
+    use std::fmt;
+
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    pub struct CommandLimits {
+        // how far an event_time may be from the game clock, in game seconds.
+        // Times within it that are ahead of the clock, or behind the snake,
+        // are taken as now.
+        pub max_skew: f64,
+        // the sharpest single turn, in radians
+        pub max_turn: f64,
+        // how fast a snake can keep turning, in radians per game second. It may
+        // save up to a second's worth.
+        pub max_turn_rate: f64,
+        pub max_strikes: usize,
+        // game seconds a rejection counts against a player
+        pub strike_memory: f64,
+        // how many of the latest security events are kept
+        pub security_log_length: usize,
+        // commands a player can send at once
+        pub command_burst: f64,
+        // commands a player can keep sending, per game second
+        pub command_rate: f64,
+        // game seconds a dropped player has to come back for their snakes
+        pub reconnect_grace: f64,
+        // snakes a player can have alive in the game at once
+        pub max_snakes: usize,
+    }
+    impl Default for CommandLimits {
+        fn default() -> Self {
+            CommandLimits {
+                max_skew: 1.0,
+                max_turn: PI,
+                max_turn_rate: 4.0 * PI,
+                max_strikes: 10,
+                strike_memory: 60.0,
+                security_log_length: 1000,
+                command_burst: 10.0,
+                command_rate: 20.0,
+                reconnect_grace: 30.0,
+                max_snakes: 1,
+            }
+        }
+    }
+
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    #[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+    pub enum Command {
+        Turn { snake_id: usize, rad_relative: f64, event_time: f64 },
+    }
+
+    // Why a command was turned away
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    #[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+    pub enum Rejection {
+        NoSuchPlayer,
+        // the token isn't for anyone, or not any more
+        NoSuchSession,
+        // the player has been kicked, and is only waiting to be shown the door
+        Kicked,
+        // the player has sent too many commands too quickly
+        RateLimited,
+        NotYourSnake { snake_id: usize },
+        NoSuchSnake { snake_id: usize },
+        NotFinite,
+        TooEarly { event_time: f64, game_time: f64 },
+        TooLate { event_time: f64, game_time: f64 },
+        TooSharp { rad_relative: f64 },
+        TooFast { rad_relative: f64, turn_left: f64 },
+        // the game's event queue is full, so try again in a moment
+        Busy,
+        // the player already has as many snakes as they're allowed
+        TooManySnakes { max_snakes: usize },
+    }
+    impl std::error::Error for Rejection {}
+    impl fmt::Display for Rejection {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Rejection::NoSuchPlayer => write!(f, "No such player!"),
+                Rejection::NoSuchSession => write!(f, "No such session!"),
+                Rejection::Kicked => write!(f, "Kicked out of the game!"),
+                Rejection::RateLimited => write!(f, "Too many commands, slow down!"),
+                Rejection::NotYourSnake { snake_id } => write!(f, "Snake {} isn't yours!", snake_id),
+                Rejection::NoSuchSnake { snake_id } => write!(f, "Snake {} is no longer in the game!", snake_id),
+                Rejection::NotFinite => write!(f, "Numbers must be finite!"),
+                Rejection::TooEarly { event_time, game_time } => {
+                    write!(f, "Time {:.3} is too far ahead of the game at {:.3}!", event_time, game_time)
+                }
+                Rejection::TooLate { event_time, game_time } => {
+                    write!(f, "Time {:.3} is too far behind the game at {:.3}!", event_time, game_time)
+                }
+                Rejection::TooSharp { rad_relative } => write!(f, "A turn of {:.3} radians is too sharp!", rad_relative),
+                Rejection::TooFast { rad_relative, turn_left } => {
+                    write!(f, "Turning too fast: {:.3} radians asked for with {:.3} left!", rad_relative, turn_left)
+                }
+                Rejection::Busy => write!(f, "The game is too busy for that, try again!"),
+                Rejection::TooManySnakes { max_snakes } => write!(f, "Already as many snakes as a player can have ({})!", max_snakes),
+            }
+        }
+    }
+
+    // A command that was turned away
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct SecurityEvent {
+        pub game_time: f64,
+        pub player_id: usize,
+        pub command: Command,
+        pub rejection: Rejection,
+        // whether this was the last straw
+        pub kicked: bool,
+    }
+
+    struct Player {
+        snake_ids: BTreeSet<usize>,
+        // game times of the rejections still held against the player
+        strikes: VecDeque<f64>,
+        kicked: bool,
+        // radians each snake has left to turn, and as of when
+        turn_left: BTreeMap<usize, (f64, f64)>,
+        // what's in the player's bucket, and as of when. Full until first used.
+        commands_left: Option<(f64, f64)>,
+        // what they come back with, once they have a snake
+        token: Option<String>,
+        // which of their connections is the live one; each reconnect is the next
+        connection: usize,
+        // when their connection dropped, if it has and they've not come back
+        dropped_at: Option<f64>,
+    }
+
+    pub struct CommandGuard {
+        limits: CommandLimits,
+        players: BTreeMap<usize, Player>,
+        next_player_id: usize,
+        security_log: VecDeque<SecurityEvent>,
+        // whose each session token is
+        sessions: BTreeMap<String, usize>,
+        // each snake's net turn since the last flush, and when the last of it was
+        pending_turns: BTreeMap<usize, (f64, f64)>,
+    }
+
+    impl CommandGuard {
+        pub fn new(limits: CommandLimits) -> CommandGuard {
+            CommandGuard {
+                limits,
+                players: BTreeMap::new(),
+                next_player_id: 0,
+                security_log: VecDeque::new(),
+                sessions: BTreeMap::new(),
+                pending_turns: BTreeMap::new(),
+            }
+        }
+
+        pub fn add_player(&mut self) -> usize {
+            let player_id = self.next_player_id;
+            self.next_player_id += 1;
+            self.players.insert(player_id, Player {
+                snake_ids: BTreeSet::new(),
+                strikes: VecDeque::new(),
+                kicked: false,
+                turn_left: BTreeMap::new(),
+                commands_left: None,
+                token: None,
+                connection: 0,
+                dropped_at: None,
+            });
+            player_id
+        }
+
+        // Forget the player, and their turns still to be made, returning the
+        // snakes they controlled
+        pub fn remove_player(&mut self, player_id: usize) -> Vec<usize> {
+            let snake_ids: Vec<usize> = match self.players.remove(&player_id) {
+                Some(player) => {
+                    if let Some(token) = player.token {
+                        self.sessions.remove(&token);
+                    }
+                    player.snake_ids.into_iter().collect()
+                }
+                None => Vec::new(),
+            };
+            for snake_id in snake_ids.iter() {
+                self.pending_turns.remove(snake_id);
+            }
+            snake_ids
+        }
+
+        // A new snake in the game, for the player to control
+        pub fn create_snake(&mut self, game: &mut Game, player_id: usize) -> Result<usize, Rejection> {
+            let event_time = game.get_time();
+            self.take_command(player_id, event_time)?;
+            let player = self.players.get_mut(&player_id).ok_or(Rejection::NoSuchPlayer)?;
+            // the ones that have died since don't count
+            let alive = player.snake_ids.iter().filter(|snake_id| game.get_snake(**snake_id).is_ok()).count();
+            if alive >= self.limits.max_snakes {
+                return Err(Rejection::TooManySnakes { max_snakes: self.limits.max_snakes });
+            }
+            // nothing's too late at the game's own time, so it's the queue that's full
+            let snake_id = game.create_snake(event_time).map_err(|_| Rejection::Busy)?;
+            player.snake_ids.insert(snake_id);
+            if player.token.is_none() {
+                let token = new_token();
+                self.sessions.insert(token.clone(), player_id);
+                player.token = Some(token);
+            }
+            Ok(snake_id)
+        }
+
+        pub fn get_token(&self, player_id: usize) -> Option<String> {
+            self.players.get(&player_id).and_then(|player| player.token.clone())
+        }
+
+        // The player's connection went, returning the snakes to take out of the
+        // game now, if they can't come back for them. Connections that have been
+        // taken over by a reconnect don't count.
+        pub fn drop_connection(&mut self, player_id: usize, connection: usize, game_time: f64) -> Vec<usize> {
+            let player = match self.players.get_mut(&player_id) {
+                Some(player) if player.connection == connection => player,
+                _ => return Vec::new(),
+            };
+            if player.token.is_none() || player.kicked {
+                return self.remove_player(player_id);
+            }
+            player.dropped_at = Some(game_time);
+            for snake_id in player.snake_ids.iter() {
+                self.pending_turns.remove(snake_id);
+            }
+            Vec::new()
+        }
+
+        // The player the token is for and their new connection, which takes
+        // over from any they had
+        pub fn reconnect(&mut self, token: &str) -> Result<(usize, usize), Rejection> {
+            let player_id = *self.sessions.get(token).ok_or(Rejection::NoSuchSession)?;
+            let player = self.players.get_mut(&player_id).ok_or(Rejection::NoSuchSession)?;
+            if player.kicked {
+                return Err(Rejection::Kicked);
+            }
+            player.dropped_at = None;
+            player.connection += 1;
+            Ok((player_id, player.connection))
+        }
+
+        // Whether the connection is still the player's
+        pub fn is_connected(&self, player_id: usize, connection: usize) -> bool {
+            match self.players.get(&player_id) {
+                Some(player) => player.connection == connection && player.dropped_at.is_none(),
+                None => false,
+            }
+        }
+
+        // The snakes of players whose connections have dropped, waiting for them
+        pub fn get_dropped_snake_ids(&self) -> Vec<usize> {
+            self.players.values()
+                .filter(|player| player.dropped_at.is_some())
+                .flat_map(|player| player.snake_ids.iter().cloned())
+                .collect()
+        }
+
+        // Forget the dropped players whose grace is up, returning their snakes
+        pub fn expire_sessions(&mut self, game_time: f64) -> Vec<usize> {
+            let grace = self.limits.reconnect_grace;
+            let expired: Vec<usize> = self.players.iter()
+                .filter(|(_, player)| player.dropped_at.is_some_and(|dropped_at| game_time - dropped_at >= grace))
+                .map(|(player_id, _)| *player_id)
+                .collect();
+            expired.into_iter().flat_map(|player_id| self.remove_player(player_id)).collect()
+        }
+
+        pub fn get_snake_ids(&self, player_id: usize) -> Vec<usize> {
+            match self.players.get(&player_id) {
+                Some(player) => player.snake_ids.iter().cloned().collect(),
+                None => Vec::new(),
+            }
+        }
+
+        pub fn is_kicked(&self, player_id: usize) -> bool {
+            self.players.get(&player_id).map(|player| player.kicked).unwrap_or(false)
+        }
+
+        // The latest security events, oldest first
+        pub fn get_security_log(&self) -> Vec<SecurityEvent> {
+            self.security_log.iter().cloned().collect()
+        }
+
+        // Take the player's command, to be carried out at the next flush(), if
+        // it passes; or log why not
+        pub fn apply(&mut self, game: &mut Game, player_id: usize, command: Command) -> Result<(), Rejection> {
+            self.take_command(player_id, game.get_time())?;
+            let result = self.check(game, player_id, command);
+            match result {
+                Ok(Command::Turn { snake_id, rad_relative, event_time }) => {
+                    let (net_turn, _) = self.pending_turns.get(&snake_id).cloned().unwrap_or((0.0, event_time));
+                    self.pending_turns.insert(snake_id, (net_turn + rad_relative, event_time));
+                    Ok(())
+                }
+                Err(rejection) => {
+                    self.strike(game.get_time(), player_id, command, rejection);
+                    Err(rejection)
+                }
+            }
+        }
+
+        // Make the turns taken since the last flush, one per snake
+        pub fn flush(&mut self, game: &mut Game) {
+            for (snake_id, (net_turn, event_time)) in std::mem::take(&mut self.pending_turns) {
+                // turns that cancel out needn't trouble anyone
+                if net_turn == 0.0 {
+                    continue;
+                }
+                // the snake may have died in the meantime, or the turn have been
+                // held too long to make; but with the queue full, there may be
+                // room by the next flush
+                if let Err(GameError::Log(LogError::QueueFull { .. })) = game.turn_snake(snake_id, net_turn, event_time) {
+                    self.pending_turns.insert(snake_id, (net_turn, event_time));
+                }
+            }
+        }
+
+        // Take one from the player's bucket, if there's one to take
+        fn take_command(&mut self, player_id: usize, game_time: f64) -> Result<(), Rejection> {
+            let limits = self.limits;
+            let player = self.players.get_mut(&player_id).ok_or(Rejection::NoSuchPlayer)?;
+            if player.kicked {
+                return Err(Rejection::Kicked);
+            }
+            let commands_left = match player.commands_left {
+                Some((commands_left, as_of)) => (commands_left + (game_time - as_of).max(0.0) * limits.command_rate).min(limits.command_burst),
+                None => limits.command_burst,
+            };
+            if commands_left < 1.0 {
+                player.commands_left = Some((commands_left, game_time));
+                return Err(Rejection::RateLimited);
+            }
+            player.commands_left = Some((commands_left - 1.0, game_time));
+            Ok(())
+        }
+
+        // The command as it should be carried out, if it should be
+        fn check(&mut self, game: &mut Game, player_id: usize, command: Command) -> Result<Command, Rejection> {
+            let limits = self.limits;
+            let player = self.players.get_mut(&player_id).ok_or(Rejection::NoSuchPlayer)?;
+            let game_time = game.get_time();
+            match command {
+                Command::Turn { snake_id, rad_relative, event_time } => {
+                    if !player.snake_ids.contains(&snake_id) {
+                        return Err(Rejection::NotYourSnake { snake_id });
+                    }
+                    if !rad_relative.is_finite() || !event_time.is_finite() {
+                        return Err(Rejection::NotFinite);
+                    }
+                    if event_time > game_time + limits.max_skew {
+                        return Err(Rejection::TooEarly { event_time, game_time });
+                    }
+                    let too_late = Rejection::TooLate { event_time, game_time };
+                    if event_time < game_time - limits.max_skew {
+                        return Err(too_late);
+                    }
+                    let last_updated = game.get_snake(snake_id).map_err(|_| Rejection::NoSuchSnake { snake_id })?.get_last_updated();
+                    // turning in the snake's past would take it back there,
+                    // and in the game's future would run it on ahead
+                    let event_time = event_time.max(last_updated).min(game_time);
+                    if !game.get_event_queue_mut().accepts(event_time) {
+                        return Err(too_late);
+                    }
+                    if rad_relative.abs() > limits.max_turn {
+                        return Err(Rejection::TooSharp { rad_relative });
+                    }
+                    let (turn_left, as_of) = player.turn_left.get(&snake_id).cloned().unwrap_or((limits.max_turn_rate, event_time));
+                    let turn_left = (turn_left + (event_time - as_of).max(0.0) * limits.max_turn_rate).min(limits.max_turn_rate);
+                    if rad_relative.abs() > turn_left {
+                        return Err(Rejection::TooFast { rad_relative, turn_left });
+                    }
+                    player.turn_left.insert(snake_id, (turn_left - rad_relative.abs(), event_time));
+                    Ok(Command::Turn { snake_id, rad_relative, event_time })
+                }
+            }
+        }
+
+        fn strike(&mut self, game_time: f64, player_id: usize, command: Command, rejection: Rejection) {
+            let limits = self.limits;
+            let player = match self.players.get_mut(&player_id) {
+                Some(player) => player,
+                None => return,
+            };
+            player.strikes.push_back(game_time);
+            while let Some(&first) = player.strikes.front() {
+                if first > game_time - limits.strike_memory {
+                    break;
+                }
+                player.strikes.pop_front();
+            }
+            player.kicked = player.strikes.len() >= limits.max_strikes;
+            self.security_log.push_back(SecurityEvent { game_time, player_id, command, rejection, kicked: player.kicked });
+            while self.security_log.len() > limits.security_log_length {
+                self.security_log.pop_front();
+            }
+        }
+    }
+
+    // 128 random bits, as hex
+    fn new_token() -> String {
+        use rand::Rng;
+        let mut rng = rand::thread_rng();
+        format!("{:016x}{:016x}", rng.gen::<u64>(), rng.gen::<u64>())
+    }
+
+    #[test]
+    fn t_turns() {
+        let mut game = Game::new_blank_game();
+        let mut guard = CommandGuard::new(CommandLimits::default());
+        let player_id = guard.add_player();
+        let snake_id = guard.create_snake(&mut game, player_id).unwrap();
+        let other_id = guard.add_player();
+        let turn = |rad_relative: f64, event_time: f64| Command::Turn { snake_id, rad_relative, event_time };
+
+        assert_eq!(Ok(()), guard.apply(&mut game, player_id, turn(0.5, 0.0)));
+        assert_eq!(Err(Rejection::NotYourSnake { snake_id }), guard.apply(&mut game, other_id, turn(0.5, 0.0)));
+        assert_eq!(Err(Rejection::NotFinite), guard.apply(&mut game, player_id, turn(f64::NAN, 0.0)));
+        assert_eq!(Err(Rejection::NotFinite), guard.apply(&mut game, player_id, turn(0.5, f64::INFINITY)));
+        assert_eq!(Err(Rejection::TooSharp { rad_relative: -4.0 }), guard.apply(&mut game, player_id, turn(-4.0, 0.0)));
+        assert_eq!(Err(Rejection::TooEarly { event_time: 5.0, game_time: 0.0 }), guard.apply(&mut game, player_id, turn(0.5, 5.0)));
+        guard.flush(&mut game);
+        game.advance_clock(3.0);
+        assert_eq!(Err(Rejection::TooLate { event_time: 1.0, game_time: 3.0 }), guard.apply(&mut game, player_id, turn(0.5, 1.0)));
+
+        // a turn a little early happens now, and one a little late doesn't take
+        // the snake back
+        let location = game.get_snake(snake_id).unwrap().get_location();
+        assert_eq!(Ok(()), guard.apply(&mut game, player_id, turn(0.5, 2.5)));
+        assert_eq!(Ok(()), guard.apply(&mut game, player_id, turn(0.5, 3.5)));
+        guard.flush(&mut game);
+        assert_eq!(location, game.get_snake(snake_id).unwrap().get_location());
+        assert_eq!(3.0, game.get_snake(snake_id).unwrap().get_last_updated());
+
+        // only what got through was heard of, and only what didn't was logged
+        let mut turns = 0;
+        while let Some(event) = game.get_event_queue_mut().get_event() {
+            if event.kind.get_name() == "snake_turned" {
+                turns += 1;
+            }
+        }
+        // the two made as one
+        assert_eq!(2, turns);
+        let log = guard.get_security_log();
+        assert_eq!(6, log.len());
+        assert_eq!(other_id, log[0].player_id);
+        assert_eq!(turn(0.5, 1.0), log[5].command);
+    }
+
+    #[test]
+    fn t_turn_rate() {
+        let mut game = Game::new_blank_game();
+        let limits = CommandLimits { max_turn_rate: PI, ..CommandLimits::default() };
+        let mut guard = CommandGuard::new(limits);
+        let player_id = guard.add_player();
+        let snake_id = guard.create_snake(&mut game, player_id).unwrap();
+        let turn = |rad_relative: f64| Command::Turn { snake_id, rad_relative, event_time: 0.0 };
+
+        // a second's worth of turning in one go, then no more...
+        assert_eq!(Ok(()), guard.apply(&mut game, player_id, turn(PI / 2.0)));
+        assert_eq!(Ok(()), guard.apply(&mut game, player_id, turn(-PI / 2.0)));
+        assert!(matches!(guard.apply(&mut game, player_id, turn(0.1)), Err(Rejection::TooFast { .. })));
+        // ...until the clock gives the snake more
+        game.advance_clock(0.25);
+        let turn = |rad_relative: f64| Command::Turn { snake_id, rad_relative, event_time: 0.25 };
+        assert!(matches!(guard.apply(&mut game, player_id, turn(PI / 2.0)), Err(Rejection::TooFast { .. })));
+        assert_eq!(Ok(()), guard.apply(&mut game, player_id, turn(PI / 4.0)));
+    }
+
+    #[test]
+    fn t_kicked() {
+        let mut game = Game::new_blank_game();
+        let limits = CommandLimits { max_strikes: 3, strike_memory: 10.0, ..CommandLimits::default() };
+        let mut guard = CommandGuard::new(limits);
+        let player_id = guard.add_player();
+        let snake_id = guard.create_snake(&mut game, player_id).unwrap();
+        let cheat = Command::Turn { snake_id, rad_relative: f64::NAN, event_time: 0.0 };
+
+        // strikes wear off...
+        guard.apply(&mut game, player_id, cheat).unwrap_err();
+        guard.apply(&mut game, player_id, cheat).unwrap_err();
+        game.advance_clock(10.0);
+        guard.apply(&mut game, player_id, cheat).unwrap_err();
+        assert!(!guard.is_kicked(player_id));
+
+        // ...but not fast enough for a repeat offender
+        guard.apply(&mut game, player_id, cheat).unwrap_err();
+        guard.apply(&mut game, player_id, cheat).unwrap_err();
+        assert!(guard.is_kicked(player_id));
+        assert!(guard.get_security_log().last().unwrap().kicked);
+        let fair = Command::Turn { snake_id, rad_relative: 0.5, event_time: 10.0 };
+        assert_eq!(Err(Rejection::Kicked), guard.apply(&mut game, player_id, fair));
+        assert_eq!(Err(Rejection::Kicked), guard.create_snake(&mut game, player_id).map(|_| ()));
+        assert_eq!(5, guard.get_security_log().len());
+
+        assert_eq!(vec![snake_id], guard.remove_player(player_id));
+        assert_eq!(Err(Rejection::NoSuchPlayer), guard.apply(&mut game, player_id, fair));
+    }
+
+    #[test]
+    fn t_rate_limit() {
+        let mut game = Game::new_blank_game();
+        let limits = CommandLimits { command_burst: 3.0, command_rate: 2.0, ..CommandLimits::default() };
+        let mut guard = CommandGuard::new(limits);
+        let player_id = guard.add_player();
+        let snake_id = guard.create_snake(&mut game, player_id).unwrap();
+        let turn = |event_time: f64| Command::Turn { snake_id, rad_relative: 0.1, event_time };
+
+        // the burst, less the snake's creation, then nothing...
+        assert_eq!(Ok(()), guard.apply(&mut game, player_id, turn(0.0)));
+        assert_eq!(Ok(()), guard.apply(&mut game, player_id, turn(0.0)));
+        assert_eq!(Err(Rejection::RateLimited), guard.apply(&mut game, player_id, turn(0.0)));
+        assert_eq!(Err(Rejection::RateLimited), guard.apply(&mut game, player_id, turn(f64::NAN)));
+        // ...until the bucket fills again, at the steady rate
+        guard.flush(&mut game);
+        game.advance_clock(0.25);
+        assert_eq!(Err(Rejection::RateLimited), guard.apply(&mut game, player_id, turn(0.25)));
+        game.advance_clock(0.25);
+        assert_eq!(Ok(()), guard.apply(&mut game, player_id, turn(0.5)));
+
+        // too many isn't cheating
+        assert!(guard.get_security_log().is_empty());
+        assert!(!guard.is_kicked(player_id));
+    }
+
+    #[test]
+    fn t_net_turn() {
+        let mut game = Game::new_blank_game();
+        let mut guard = CommandGuard::new(CommandLimits { max_snakes: 2, ..CommandLimits::default() });
+        let player_id = guard.add_player();
+        let snake_id = guard.create_snake(&mut game, player_id).unwrap();
+        let other_id = guard.create_snake(&mut game, player_id).unwrap();
+        let velocity = game.get_snake(snake_id).unwrap().get_velocity();
+        for rad_relative in [0.5, 0.25, -0.5, 0.5].iter() {
+            guard.apply(&mut game, player_id, Command::Turn { snake_id, rad_relative: *rad_relative, event_time: 0.0 }).unwrap();
+        }
+        guard.apply(&mut game, player_id, Command::Turn { snake_id: other_id, rad_relative: 0.5, event_time: 0.0 }).unwrap();
+        guard.apply(&mut game, player_id, Command::Turn { snake_id: other_id, rad_relative: -0.5, event_time: 0.0 }).unwrap();
+        // nothing happens until the flush
+        assert_eq!(velocity, game.get_snake(snake_id).unwrap().get_velocity());
+        guard.flush(&mut game);
+
+        let mut turned = Vec::new();
+        while let Some(event) = game.get_event_queue_mut().get_event() {
+            if let crate::eventqueue::eventqueue::EventKind::SnakeTurned { snake_id, rad_relative, .. } = event.kind {
+                turned.push((snake_id, rad_relative));
+            }
+        }
+        // the other snake's turns cancelled out
+        assert_eq!(vec![(snake_id, 0.75)], turned);
+        // and nothing's left over for the next
+        guard.flush(&mut game);
+        assert!(game.get_event_queue_mut().get_event().is_none());
+    }
+
+    #[test]
+    fn t_max_snakes() {
+        let mut game = Game::new_blank_game();
+        let mut guard = CommandGuard::new(CommandLimits::default());
+        let player_id = guard.add_player();
+        let snake_id = guard.create_snake(&mut game, player_id).unwrap();
+        assert_eq!(Err(Rejection::TooManySnakes { max_snakes: 1 }), guard.create_snake(&mut game, player_id));
+        assert_eq!(vec![snake_id], guard.get_snake_ids(player_id));
+        // which isn't cheating
+        assert!(guard.get_security_log().is_empty());
+
+        // another player has their own
+        let other_id = guard.add_player();
+        assert!(guard.create_snake(&mut game, other_id).is_ok());
+
+        // once the snake's gone, there's room for another
+        game.remove_snake(snake_id, 0.0).unwrap();
+        assert!(guard.create_snake(&mut game, player_id).is_ok());
+    }
+
+    #[test]
+    fn t_busy() {
+        use crate::eventqueue::eventqueue::{OverflowPolicy, RetentionPolicy};
+        let mut game = Game::new_blank_game();
+        game.get_event_queue_mut().set_retention_policy(RetentionPolicy { max_backlog: Some(2), overflow: OverflowPolicy::Backpressure });
+        let mut guard = CommandGuard::new(CommandLimits { max_snakes: 2, ..CommandLimits::default() });
+        let player_id = guard.add_player();
+        let snake_id = guard.create_snake(&mut game, player_id).unwrap();
+        game.advance_clock(0.1);
+        assert_eq!(Err(Rejection::Busy), guard.create_snake(&mut game, player_id));
+
+        // a turn that won't fit waits for the next flush
+        let velocity = game.get_snake(snake_id).unwrap().get_velocity();
+        guard.apply(&mut game, player_id, Command::Turn { snake_id, rad_relative: 0.5, event_time: 0.1 }).unwrap();
+        guard.flush(&mut game);
+        assert_eq!(velocity, game.get_snake(snake_id).unwrap().get_velocity());
+        while game.get_event_queue_mut().get_event().is_some() {}
+        guard.flush(&mut game);
+        assert_ne!(velocity, game.get_snake(snake_id).unwrap().get_velocity());
+    }
+
+    #[test]
+    fn t_sessions() {
+        let mut game = Game::new_blank_game();
+        let limits = CommandLimits { reconnect_grace: 5.0, max_snakes: 2, ..CommandLimits::default() };
+        let mut guard = CommandGuard::new(limits);
+        let player_id = guard.add_player();
+        // no snake, no session
+        assert_eq!(None, guard.get_token(player_id));
+        let snake_id = guard.create_snake(&mut game, player_id).unwrap();
+        let token = guard.get_token(player_id).unwrap();
+        assert_eq!(32, token.len());
+        guard.create_snake(&mut game, player_id).unwrap();
+        assert_eq!(Some(token.clone()), guard.get_token(player_id));
+
+        // a dropped player's snakes wait for them, going nowhere they don't steer
+        while game.get_event_queue_mut().get_event().is_some() {}
+        guard.apply(&mut game, player_id, Command::Turn { snake_id, rad_relative: 0.5, event_time: 0.0 }).unwrap();
+        assert!(guard.drop_connection(player_id, 0, 0.0).is_empty());
+        assert!(!guard.is_connected(player_id, 0));
+        assert_eq!(guard.get_snake_ids(player_id), guard.get_dropped_snake_ids());
+        guard.flush(&mut game);
+        assert!(game.get_event_queue_mut().get_event().is_none());
+
+        // and they're theirs again on coming back
+        assert_eq!(Err(Rejection::NoSuchSession), guard.reconnect("guess"));
+        assert_eq!(Ok((player_id, 1)), guard.reconnect(&token));
+        assert!(guard.is_connected(player_id, 1));
+        assert!(guard.get_dropped_snake_ids().is_empty());
+        // a reconnect takes over from the connection before, which going
+        // doesn't drop the player
+        assert_eq!(Ok((player_id, 2)), guard.reconnect(&token));
+        assert!(!guard.is_connected(player_id, 1));
+        assert!(guard.drop_connection(player_id, 1, 1.0).is_empty());
+        assert!(guard.is_connected(player_id, 2));
+
+        // until they've been gone too long
+        assert!(guard.drop_connection(player_id, 2, 1.0).is_empty());
+        assert!(guard.expire_sessions(5.5).is_empty());
+        assert_eq!(guard.get_snake_ids(player_id), guard.expire_sessions(6.0));
+        assert_eq!(Err(Rejection::NoSuchSession), guard.reconnect(&token));
+
+        // players who never had a snake, or were kicked, just go
+        let other_id = guard.add_player();
+        assert!(guard.drop_connection(other_id, 0, 6.0).is_empty());
+        assert_eq!(Err(Rejection::NoSuchPlayer), guard.create_snake(&mut game, other_id));
+    }
+}
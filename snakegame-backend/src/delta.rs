@@ -0,0 +1,359 @@
+pub mod delta {
+    // What changed between two snapshots of the same game, so a server can send
+    // each client just that instead of the whole state every tick. A delta is
+    // always against a baseline tick - the last state the client said it has -
+    // and only applies to a snapshot of that tick.
+    use crate::snapshot::snapshot::{FoodSnapshot, GameSnapshot, SnakeName, SnakeSnapshot, TeamScore};
+    use std::collections::VecDeque;
+    use std::fmt;
+    #[cfg(feature = "serde")]
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, PartialEq, Debug)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct GameDelta {
+        // the tick of the snapshot this applies to
+        pub baseline_tick: u64,
+        // the tick it brings the snapshot up to
+        pub tick: u64,
+        pub time: f64,
+        pub next_sequence: u64,
+        pub last_state_snapshot: f64,
+        pub next_snake_id: usize,
+        pub next_food_id: usize,
+        // everything below is in id order
+        pub snakes_added: Vec<SnakeSnapshot>,
+        // snakes that moved, turned, grew or scored
+        pub snakes_changed: Vec<SnakeSnapshot>,
+        pub snakes_removed: Vec<usize>,
+        pub food_added: Vec<FoodSnapshot>,
+        pub food_removed: Vec<usize>,
+        // new teams, and teams whose score changed
+        pub team_scores: Vec<TeamScore>,
+        // snakes renamed since the baseline; a removed snake's name goes with it
+        pub names: Vec<SnakeName>,
+    }
+
+    #[derive(Debug, PartialEq)]
+    pub enum DeltaError {
+        // the delta is against a different tick than the snapshot it was applied to
+        WrongBaseline { expected: u64, found: u64 },
+        // the delta names a snake or food the baseline doesn't have, or adds one it already has
+        Mismatch(String),
+    }
+    impl std::error::Error for DeltaError {}
+    impl fmt::Display for DeltaError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                DeltaError::WrongBaseline { expected, found } => {
+                    write!(f, "Delta is against tick {}, not tick {}!", expected, found)
+                }
+                DeltaError::Mismatch(reason) => write!(f, "Delta doesn't fit its baseline: {}!", reason),
+            }
+        }
+    }
+
+    impl GameDelta {
+        // What it takes to turn baseline into current. Both have to be snapshots
+        // of the same game, with baseline the earlier one.
+        pub fn between(baseline: &GameSnapshot, current: &GameSnapshot) -> GameDelta {
+            let mut delta = GameDelta {
+                baseline_tick: baseline.tick,
+                tick: current.tick,
+                time: current.time,
+                next_sequence: current.next_sequence,
+                last_state_snapshot: current.last_state_snapshot,
+                next_snake_id: current.next_snake_id,
+                next_food_id: current.next_food_id,
+                snakes_added: Vec::new(),
+                snakes_changed: Vec::new(),
+                snakes_removed: Vec::new(),
+                food_added: Vec::new(),
+                food_removed: Vec::new(),
+                team_scores: Vec::new(),
+                names: Vec::new(),
+            };
+
+            for snake in current.snakes.iter() {
+                match baseline.snakes.iter().find(|old| old.snake_id == snake.snake_id) {
+                    None => delta.snakes_added.push(*snake),
+                    Some(old) if old != snake => delta.snakes_changed.push(*snake),
+                    Some(_) => (),
+                }
+            }
+            for old in baseline.snakes.iter() {
+                if !current.snakes.iter().any(|snake| snake.snake_id == old.snake_id) {
+                    delta.snakes_removed.push(old.snake_id);
+                }
+            }
+
+            // food never changes once it's down, so it's only ever added or removed
+            for food in current.food.iter() {
+                if !baseline.food.contains(food) {
+                    delta.food_added.push(*food);
+                }
+            }
+            for old in baseline.food.iter() {
+                if !current.food.iter().any(|food| food.food_id == old.food_id) {
+                    delta.food_removed.push(old.food_id);
+                }
+            }
+
+            for team in current.team_scores.iter() {
+                if !baseline.team_scores.contains(team) {
+                    delta.team_scores.push(*team);
+                }
+            }
+
+            for name in current.names.iter() {
+                if !baseline.names.contains(name) {
+                    delta.names.push(name.clone());
+                }
+            }
+            delta
+        }
+
+        // Nothing but the clock moved on
+        pub fn is_empty(&self) -> bool {
+            self.snakes_added.is_empty()
+                && self.snakes_changed.is_empty()
+                && self.snakes_removed.is_empty()
+                && self.food_added.is_empty()
+                && self.food_removed.is_empty()
+                && self.team_scores.is_empty()
+                && self.names.is_empty()
+        }
+
+        // The snapshot this delta was taken against, brought up to the delta's tick
+        pub fn apply(&self, baseline: &GameSnapshot) -> Result<GameSnapshot, DeltaError> {
+            if baseline.tick != self.baseline_tick {
+                return Err(DeltaError::WrongBaseline {
+                    expected: self.baseline_tick,
+                    found: baseline.tick,
+                });
+            }
+            let mut updated = baseline.clone();
+            updated.tick = self.tick;
+            updated.time = self.time;
+            updated.next_sequence = self.next_sequence;
+            updated.last_state_snapshot = self.last_state_snapshot;
+            updated.next_snake_id = self.next_snake_id;
+            updated.next_food_id = self.next_food_id;
+
+            for snake_id in self.snakes_removed.iter() {
+                let idx = updated.snakes.iter().position(|snake| snake.snake_id == *snake_id)
+                    .ok_or_else(|| DeltaError::Mismatch(format!("no snake {} to remove", snake_id)))?;
+                updated.snakes.remove(idx);
+            }
+            for snake in self.snakes_changed.iter() {
+                let old = updated.snakes.iter_mut().find(|old| old.snake_id == snake.snake_id)
+                    .ok_or_else(|| DeltaError::Mismatch(format!("no snake {} to change", snake.snake_id)))?;
+                *old = *snake;
+            }
+            for snake in self.snakes_added.iter() {
+                if updated.snakes.iter().any(|old| old.snake_id == snake.snake_id) {
+                    return Err(DeltaError::Mismatch(format!("snake {} already there", snake.snake_id)));
+                }
+                updated.snakes.push(*snake);
+            }
+            updated.snakes.sort_by_key(|snake| snake.snake_id);
+
+            for food_id in self.food_removed.iter() {
+                let idx = updated.food.iter().position(|food| food.food_id == *food_id)
+                    .ok_or_else(|| DeltaError::Mismatch(format!("no food {} to remove", food_id)))?;
+                updated.food.remove(idx);
+            }
+            for food in self.food_added.iter() {
+                if updated.food.iter().any(|old| old.food_id == food.food_id) {
+                    return Err(DeltaError::Mismatch(format!("food {} already there", food.food_id)));
+                }
+                updated.food.push(*food);
+            }
+            updated.food.sort_by_key(|food| food.food_id);
+
+            for team in self.team_scores.iter() {
+                match updated.team_scores.iter_mut().find(|old| old.team_id == team.team_id) {
+                    Some(old) => *old = *team,
+                    None => updated.team_scores.push(*team),
+                }
+            }
+            updated.team_scores.sort_by_key(|team| team.team_id);
+
+            updated.names.retain(|name| !self.snakes_removed.contains(&name.snake_id));
+            for name in self.names.iter() {
+                if !updated.snakes.iter().any(|snake| snake.snake_id == name.snake_id) {
+                    return Err(DeltaError::Mismatch(format!("no snake {} to name", name.snake_id)));
+                }
+                match updated.names.iter_mut().find(|old| old.snake_id == name.snake_id) {
+                    Some(old) => *old = name.clone(),
+                    None => updated.names.push(name.clone()),
+                }
+            }
+            updated.names.sort_by_key(|name| name.snake_id);
+            Ok(updated)
+        }
+    }
+
+    // The last few snapshots a server sent out, so it can send each client a
+    // delta against whichever tick that client last acknowledged
+    pub struct SnapshotHistory {
+        capacity: usize,
+        // oldest first
+        snapshots: VecDeque<GameSnapshot>,
+    }
+
+    impl SnapshotHistory {
+        // Keeps at most capacity snapshots - clients further behind than that
+        // need a full snapshot instead of a delta
+        pub fn new(capacity: usize) -> SnapshotHistory {
+            SnapshotHistory {
+                capacity: capacity.max(1),
+                snapshots: VecDeque::new(),
+            }
+        }
+
+        pub fn record(&mut self, snapshot: GameSnapshot) {
+            if self.snapshots.len() == self.capacity {
+                self.snapshots.pop_front();
+            }
+            self.snapshots.push_back(snapshot);
+        }
+
+        pub fn get(&self, tick: u64) -> Option<&GameSnapshot> {
+            self.snapshots.iter().find(|snapshot| snapshot.tick == tick)
+        }
+
+        pub fn get_latest(&self) -> Option<&GameSnapshot> {
+            self.snapshots.back()
+        }
+
+        // The latest state as a delta against acked_tick, or None if that tick
+        // has already been forgotten (or was never recorded)
+        pub fn delta_since(&self, acked_tick: u64) -> Option<GameDelta> {
+            let baseline = self.get(acked_tick)?;
+            let latest = self.get_latest()?;
+            Some(GameDelta::between(baseline, latest))
+        }
+    }
+
+    fn _play_ticks(game: &mut crate::game::game::Game, ticks: usize) {
+        for _i in 0..ticks {
+            game.advance_clock(0.25);
+        }
+    }
+
+    #[test]
+    fn t_between_and_apply() {
+        use crate::game::game::Game;
+        use crate::game::common::{Coordinates, Velocity};
+        let mut game = Game::new_blank_game();
+        let snake1_id = game.create_snake(0.0).unwrap();
+        game.add_food(0.0, 2.0, 0.0).unwrap();
+        game.add_food(10.0, 10.0, 0.0).unwrap();
+        let baseline = game.snapshot();
+
+        // the snake eats, a new one is born on a team, more food goes down
+        _play_ticks(&mut game, 10);
+        let snake2_id = game.create_team_snake(4, game.get_time()).unwrap();
+        game.add_food(-10.0, -10.0, game.get_time()).unwrap();
+        game.rename_snake(snake2_id, "Kaa", game.get_time()).unwrap();
+        let current = game.snapshot();
+
+        let delta = GameDelta::between(&baseline, &current);
+        assert_eq!(0, delta.baseline_tick);
+        assert_eq!(10, delta.tick);
+        assert_eq!(vec![snake2_id], delta.snakes_added.iter().map(|snake| snake.snake_id).collect::<Vec<usize>>());
+        assert_eq!(vec![snake1_id], delta.snakes_changed.iter().map(|snake| snake.snake_id).collect::<Vec<usize>>());
+        assert!(delta.snakes_removed.is_empty());
+        assert_eq!(vec![0], delta.food_removed);
+        assert_eq!(1, delta.food_added.len());
+        assert_eq!(vec![TeamScore { team_id: 4, score: 0.0 }], delta.team_scores);
+        assert_eq!(vec![SnakeName { snake_id: snake2_id, name: "Kaa".to_string() }], delta.names);
+        assert!(!delta.is_empty());
+        assert_eq!(current, delta.apply(&baseline).unwrap());
+
+        // and snakes meeting head on and dying
+        let mut game = Game::new_blank_game();
+        let snake1_id = game.create_snake(0.0).unwrap();
+        let snake2_id = game.create_snake(0.0).unwrap();
+        game._place_snake(snake2_id, Coordinates {x: 0.0, y: 0.5}, Velocity {delta_x: 0.0, delta_y: -1.0}).unwrap();
+        game.rename_snake(snake1_id, "Nagini", 0.0).unwrap();
+        let baseline = game.snapshot();
+        _play_ticks(&mut game, 1);
+        let current = game.snapshot();
+        let delta = GameDelta::between(&baseline, &current);
+        assert_eq!(vec![snake1_id, snake2_id], delta.snakes_removed);
+        assert!(current.names.is_empty());
+        assert_eq!(current, delta.apply(&baseline).unwrap());
+
+        // nothing happening is nearly nothing to send
+        let delta = GameDelta::between(&current, &current);
+        assert!(delta.is_empty());
+        assert_eq!(current, delta.apply(&current).unwrap());
+    }
+
+    #[test]
+    fn t_apply_errors() {
+        use crate::game::game::Game;
+        let mut game = Game::new_blank_game();
+        game.create_snake(0.0).unwrap();
+        let baseline = game.snapshot();
+        _play_ticks(&mut game, 2);
+        let middle = game.snapshot();
+        _play_ticks(&mut game, 2);
+        let current = game.snapshot();
+
+        let delta = GameDelta::between(&baseline, &current);
+        assert_eq!(
+            Err(DeltaError::WrongBaseline { expected: 0, found: 2 }),
+            delta.apply(&middle)
+        );
+
+        let mut bad = GameDelta::between(&middle, &current);
+        bad.snakes_removed.push(7);
+        assert!(matches!(bad.apply(&middle), Err(DeltaError::Mismatch(_))));
+        let mut bad = GameDelta::between(&middle, &current);
+        bad.snakes_added.push(current.snakes[0]);
+        assert!(matches!(bad.apply(&middle), Err(DeltaError::Mismatch(_))));
+    }
+
+    #[test]
+    fn t_snapshot_history() {
+        use crate::game::game::Game;
+        let mut game = Game::new_blank_game();
+        game.create_snake(0.0).unwrap();
+        game.add_food(0.0, 1.0, 0.0).unwrap();
+        let mut history = SnapshotHistory::new(3);
+        assert!(history.delta_since(0).is_none());
+        for _i in 0..4 {
+            history.record(game.snapshot());
+            _play_ticks(&mut game, 1);
+        }
+
+        // a client that acked tick 1 catches up to tick 3 from its own copy
+        let client_baseline = history.get(1).unwrap().clone();
+        let delta = history.delta_since(1).unwrap();
+        assert_eq!(1, delta.baseline_tick);
+        assert_eq!(3, delta.tick);
+        assert_eq!(history.get_latest(), delta.apply(&client_baseline).ok().as_ref());
+
+        // tick 0 has been forgotten - that client needs the whole snapshot
+        assert!(history.get(0).is_none());
+        assert!(history.delta_since(0).is_none());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn t_delta_serde() {
+        use crate::game::game::Game;
+        let mut game = Game::new_blank_game();
+        game.create_snake(0.0).unwrap();
+        let baseline = game.snapshot();
+        _play_ticks(&mut game, 1);
+        let delta = GameDelta::between(&baseline, &game.snapshot());
+
+        let json = serde_json::to_string(&delta).unwrap();
+        assert!(json.starts_with(r#"{"baseline_tick":0,"tick":1,"#));
+        assert_eq!(delta, serde_json::from_str(&json).unwrap());
+    }
+}
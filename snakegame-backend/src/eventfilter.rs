@@ -0,0 +1,153 @@
+pub mod eventfilter {
+    use crate::eventqueue::eventqueue::Event;
+    use crate::game::common::Coordinates;
+
+    // Which events a subscriber wants. Combine with And/Or, e.g. everything
+    // about snake 3 plus all the food: Snake(3).or(Kind("food_added")).
+    #[derive(Clone, PartialEq, Debug)]
+    pub enum EventFilter {
+        Everything,
+        // kinds by their snake_case name, e.g. "snake_ate"
+        Kind(String),
+        // events about this snake
+        Snake(usize),
+        // events about this food
+        Food(usize),
+        // events with a location inside the rectangle, edges included. Events
+        // that don't say where they happened never match.
+        Region { min: Coordinates, max: Coordinates },
+        And(Vec<EventFilter>),
+        Or(Vec<EventFilter>),
+    }
+
+    impl EventFilter {
+        pub fn matches(&self, event: &Event) -> bool {
+            match self {
+                EventFilter::Everything => true,
+                EventFilter::Kind(name) => event.kind.get_name() == name,
+                EventFilter::Snake(snake_id) => event.kind.get_snake_id() == Some(*snake_id),
+                EventFilter::Food(food_id) => event.kind.get_food_id() == Some(*food_id),
+                EventFilter::Region { min, max } => match event.kind.get_location() {
+                    Some(loc) => loc.x >= min.x && loc.x <= max.x && loc.y >= min.y && loc.y <= max.y,
+                    None => false,
+                },
+                EventFilter::And(filters) => filters.iter().all(|filter| filter.matches(event)),
+                EventFilter::Or(filters) => filters.iter().any(|filter| filter.matches(event)),
+            }
+        }
+
+        pub fn and(self, other: EventFilter) -> EventFilter {
+            match self {
+                EventFilter::And(mut filters) => {
+                    filters.push(other);
+                    EventFilter::And(filters)
+                }
+                _ => EventFilter::And(vec![self, other]),
+            }
+        }
+
+        pub fn or(self, other: EventFilter) -> EventFilter {
+            match self {
+                EventFilter::Or(mut filters) => {
+                    filters.push(other);
+                    EventFilter::Or(filters)
+                }
+                _ => EventFilter::Or(vec![self, other]),
+            }
+        }
+    }
+
+    #[test]
+    fn t_matches() {
+        use crate::eventqueue::eventqueue::EventKind;
+        let ate = Event {
+            kind: EventKind::SnakeAte { snake_id: 1, food_id: 2 },
+            event_time: 0.0,
+            sequence: 0,
+            tick: 0,
+        };
+        let food = Event {
+            kind: EventKind::FoodAdded {
+                food_id: 2,
+                loc: Coordinates { x: 5.0, y: -5.0 },
+                nutrition: 1.0,
+            },
+            event_time: 0.0,
+            sequence: 0,
+            tick: 0,
+        };
+
+        assert!(EventFilter::Everything.matches(&ate));
+        assert!(EventFilter::Kind("snake_ate".to_string()).matches(&ate));
+        assert!(!EventFilter::Kind("snake_ate".to_string()).matches(&food));
+        assert!(EventFilter::Snake(1).matches(&ate));
+        assert!(!EventFilter::Snake(2).matches(&ate));
+        assert!(!EventFilter::Snake(1).matches(&food));
+        assert!(EventFilter::Food(2).matches(&ate));
+        assert!(EventFilter::Food(2).matches(&food));
+
+        let region = EventFilter::Region {
+            min: Coordinates { x: 0.0, y: -10.0 },
+            max: Coordinates { x: 5.0, y: 0.0 },
+        };
+        assert!(region.matches(&food));
+        // no location, no match
+        assert!(!region.matches(&ate));
+        let elsewhere = EventFilter::Region {
+            min: Coordinates { x: -10.0, y: -10.0 },
+            max: Coordinates { x: 0.0, y: 0.0 },
+        };
+        assert!(!elsewhere.matches(&food));
+    }
+
+    #[test]
+    fn t_combine() {
+        use crate::eventqueue::eventqueue::EventKind;
+        let ate = Event {
+            kind: EventKind::SnakeAte { snake_id: 1, food_id: 2 },
+            event_time: 0.0,
+            sequence: 0,
+            tick: 0,
+        };
+        let killed = Event {
+            kind: EventKind::SnakeKilled { snake_id: 1 },
+            event_time: 0.0,
+            sequence: 0,
+            tick: 0,
+        };
+        let other_killed = Event {
+            kind: EventKind::SnakeKilled { snake_id: 3 },
+            event_time: 0.0,
+            sequence: 0,
+            tick: 0,
+        };
+
+        let snake1_meals = EventFilter::Snake(1).and(EventFilter::Kind("snake_ate".to_string()));
+        assert!(snake1_meals.matches(&ate));
+        assert!(!snake1_meals.matches(&killed));
+
+        let meals_or_deaths = EventFilter::Kind("snake_ate".to_string())
+            .or(EventFilter::Kind("snake_killed".to_string()));
+        assert!(meals_or_deaths.matches(&ate));
+        assert!(meals_or_deaths.matches(&other_killed));
+
+        // chaining flattens rather than nesting
+        let three_snakes = EventFilter::Snake(1).or(EventFilter::Snake(2)).or(EventFilter::Snake(3));
+        assert_eq!(
+            EventFilter::Or(vec![EventFilter::Snake(1), EventFilter::Snake(2), EventFilter::Snake(3)]),
+            three_snakes
+        );
+
+        // and the two mix
+        let snake1_deaths_or_any_meal = EventFilter::Snake(1)
+            .and(EventFilter::Kind("snake_killed".to_string()))
+            .or(EventFilter::Kind("snake_ate".to_string()));
+        assert!(snake1_deaths_or_any_meal.matches(&killed));
+        assert!(snake1_deaths_or_any_meal.matches(&ate));
+        assert!(!snake1_deaths_or_any_meal.matches(&other_killed));
+
+        // empty combinations behave like all() and any()
+        assert!(EventFilter::And(vec![]).matches(&ate));
+        assert!(!EventFilter::Or(vec![]).matches(&ate));
+    }
+}
@@ -1,17 +1,26 @@
 pub mod eventqueue {
+    use crate::eventfilter::eventfilter::EventFilter;
     use crate::food::food::Food;
     use crate::game::common::{Coordinates, Velocity};
     use crate::snake::snake::Snake;
-    use std::collections::VecDeque;
+    use std::collections::{HashMap, VecDeque};
     use std::fmt;
+    #[cfg(feature = "serde")]
+    use serde::{Deserialize, Serialize};
 
-    #[derive(PartialEq, Copy, Clone, Debug)]
+    // With the serde feature, each kind serializes as an object tagged with its
+    // snake_case name, e.g. {"type":"snake_ate","snake_id":0,"food_id":3}.
+    // The tags and field names are part of the wire format - add, don't rename.
+    #[derive(PartialEq, Clone, Debug)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    #[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
     pub enum EventKind {
         SnakeBorn {
             snake_id: usize,
             loc: Coordinates,
             vel: Velocity,
             size: f64,
+            team: Option<usize>,
         },
         SnakeKilled {
             snake_id: usize,
@@ -29,12 +38,181 @@ pub mod eventqueue {
             loc: Coordinates,
             nutrition: f64,
         },
+        // the game clock moved on to event_time, and everything in it moved with it
+        ClockAdvanced {
+            delta_t: f64,
+        },
+        // where every live snake is, so clients can resync without replaying
+        // the physics themselves
+        StateSnapshot {
+            snakes: Vec<SnakeState>,
+        },
+        FoodRemoved {
+            food_id: usize,
+            reason: FoodRemovalReason,
+        },
+        // size is what the snake has grown to
+        SnakeGrew {
+            snake_id: usize,
+            size: f64,
+        },
+        // snake_id and other_snake_id ran into each other, loc being the head
+        // that was hit. Whoever didn't survive it gets a SnakeKilled after.
+        SnakeCollided {
+            snake_id: usize,
+            other_snake_id: usize,
+            loc: Coordinates,
+        },
+        // speed is the snake's new speed, in game distance per second
+        SnakeBoosted {
+            snake_id: usize,
+            speed: f64,
+        },
+        SnakeRenamed {
+            snake_id: usize,
+            name: String,
+        },
+        // score is the snake's new total
+        ScoreChanged {
+            snake_id: usize,
+            score: f64,
+        },
+        GameStarted,
+        // the winning team, if there was one
+        GameEnded {
+            winner: Option<usize>,
+        },
+        // taken out of the game from outside, e.g. when its player left,
+        // rather than killed in it
+        SnakeRemoved {
+            snake_id: usize,
+        },
     }
+
+    // Why food went away
     #[derive(PartialEq, Copy, Clone, Debug)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    #[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+    pub enum FoodRemovalReason {
+        // a snake ate it - there's a SnakeAte just before
+        Consumed,
+        // it was left too long
+        Expired,
+    }
+    impl fmt::Display for FoodRemovalReason {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                FoodRemovalReason::Consumed => write!(f, "consumed"),
+                FoodRemovalReason::Expired => write!(f, "expired"),
+            }
+        }
+    }
+
+    // One snake as of a StateSnapshot
+    #[derive(PartialEq, Copy, Clone, Debug)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct SnakeState {
+        pub snake_id: usize,
+        pub loc: Coordinates,
+        pub vel: Velocity,
+        pub size: f64,
+    }
+    // Serializes as the kind's object with the event_time, sequence and tick alongside the tag, e.g.
+    // {"type":"snake_turned","snake_id":0,"rad_relative":0.785,"event_time":1.0,"sequence":12,"tick":4}
+    #[derive(PartialEq, Clone, Debug)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
     pub struct Event {
+        #[cfg_attr(feature = "serde", serde(flatten))]
         pub kind: EventKind,
         pub event_time: f64,
+        // Filled in by log_event(): one more than the event logged before it, so
+        // consumers can spot gaps and duplicates. Recordings from before these
+        // were added read as 0.
+        #[cfg_attr(feature = "serde", serde(default))]
+        pub sequence: u64,
+        // the simulation tick it happened in - the number of clock advances before it
+        #[cfg_attr(feature = "serde", serde(default))]
+        pub tick: u64,
+    }
+
+    impl Event {
+        // An event ready to log, which will fill in its sequence and tick
+        pub fn new(kind: EventKind, event_time: f64) -> Event {
+            Event {
+                kind,
+                event_time,
+                sequence: 0,
+                tick: 0,
+            }
+        }
     }
+
+    impl EventKind {
+        // The kind's snake_case name, as used for the serde "type" tag
+        pub fn get_name(&self) -> &'static str {
+            match self {
+                EventKind::SnakeBorn { .. } => "snake_born",
+                EventKind::SnakeKilled { .. } => "snake_killed",
+                EventKind::SnakeTurned { .. } => "snake_turned",
+                EventKind::SnakeAte { .. } => "snake_ate",
+                EventKind::FoodAdded { .. } => "food_added",
+                EventKind::ClockAdvanced { .. } => "clock_advanced",
+                EventKind::StateSnapshot { .. } => "state_snapshot",
+                EventKind::FoodRemoved { .. } => "food_removed",
+                EventKind::SnakeGrew { .. } => "snake_grew",
+                EventKind::SnakeCollided { .. } => "snake_collided",
+                EventKind::SnakeBoosted { .. } => "snake_boosted",
+                EventKind::SnakeRenamed { .. } => "snake_renamed",
+                EventKind::ScoreChanged { .. } => "score_changed",
+                EventKind::GameStarted => "game_started",
+                EventKind::GameEnded { .. } => "game_ended",
+                EventKind::SnakeRemoved { .. } => "snake_removed",
+            }
+        }
+
+        pub fn get_snake_id(&self) -> Option<usize> {
+            match *self {
+                EventKind::SnakeBorn { snake_id, .. } => Some(snake_id),
+                EventKind::SnakeKilled { snake_id } => Some(snake_id),
+                EventKind::SnakeTurned { snake_id, .. } => Some(snake_id),
+                EventKind::SnakeAte { snake_id, .. } => Some(snake_id),
+                EventKind::FoodAdded { .. } => None,
+                EventKind::ClockAdvanced { .. } => None,
+                // about every snake, not any one of them
+                EventKind::StateSnapshot { .. } => None,
+                EventKind::FoodRemoved { .. } => None,
+                EventKind::SnakeGrew { snake_id, .. } => Some(snake_id),
+                // the other snake is in other_snake_id
+                EventKind::SnakeCollided { snake_id, .. } => Some(snake_id),
+                EventKind::SnakeBoosted { snake_id, .. } => Some(snake_id),
+                EventKind::SnakeRenamed { snake_id, .. } => Some(snake_id),
+                EventKind::ScoreChanged { snake_id, .. } => Some(snake_id),
+                EventKind::GameStarted => None,
+                EventKind::GameEnded { .. } => None,
+                EventKind::SnakeRemoved { snake_id } => Some(snake_id),
+            }
+        }
+
+        pub fn get_food_id(&self) -> Option<usize> {
+            match *self {
+                EventKind::SnakeAte { food_id, .. } => Some(food_id),
+                EventKind::FoodAdded { food_id, .. } => Some(food_id),
+                EventKind::FoodRemoved { food_id, .. } => Some(food_id),
+                _ => None,
+            }
+        }
+
+        // Where it happened, for the kinds that say
+        pub fn get_location(&self) -> Option<Coordinates> {
+            match *self {
+                EventKind::SnakeBorn { loc, .. } => Some(loc),
+                EventKind::FoodAdded { loc, .. } => Some(loc),
+                EventKind::SnakeCollided { loc, .. } => Some(loc),
+                _ => None,
+            }
+        }
+    }
+
     impl fmt::Display for Event {
         fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
             match self.kind {
@@ -43,11 +221,18 @@ pub mod eventqueue {
                     loc,
                     vel,
                     size,
-                } => write!(
-                    f,
-                    "{:3.1}: snake {} born at {} headed {} of size {:.1}",
-                    self.event_time, snake_id, loc, vel, size
-                ),
+                    team,
+                } => {
+                    write!(
+                        f,
+                        "{:3.1}: snake {} born at {} headed {} of size {:.1}",
+                        self.event_time, snake_id, loc, vel, size
+                    )?;
+                    if let Some(team_id) = team {
+                        write!(f, " on team {}", team_id)?;
+                    }
+                    Ok(())
+                }
                 EventKind::SnakeKilled { snake_id } => {
                     write!(f, "{:3.1}: snake {} killed", self.event_time, snake_id)
                 }
@@ -73,87 +258,602 @@ pub mod eventqueue {
                     "{:3.1}: food {} added at {} of nutrition {:.1}",
                     self.event_time, food_id, loc, nutrition
                 ),
+                EventKind::ClockAdvanced { delta_t } => write!(
+                    f,
+                    "{:3.1}: clock advanced {:.1}",
+                    self.event_time, delta_t
+                ),
+                EventKind::StateSnapshot { ref snakes } => write!(
+                    f,
+                    "{:3.1}: state snapshot of {} snakes",
+                    self.event_time,
+                    snakes.len()
+                ),
+                EventKind::FoodRemoved { food_id, reason } => write!(
+                    f,
+                    "{:3.1}: food {} {}",
+                    self.event_time, food_id, reason
+                ),
+                EventKind::SnakeGrew { snake_id, size } => write!(
+                    f,
+                    "{:3.1}: snake {} grew to size {:.1}",
+                    self.event_time, snake_id, size
+                ),
+                EventKind::SnakeCollided {
+                    snake_id,
+                    other_snake_id,
+                    loc,
+                } => write!(
+                    f,
+                    "{:3.1}: snake {} collided with snake {} at {}",
+                    self.event_time, snake_id, other_snake_id, loc
+                ),
+                EventKind::SnakeBoosted { snake_id, speed } => write!(
+                    f,
+                    "{:3.1}: snake {} boosted to speed {:.1}",
+                    self.event_time, snake_id, speed
+                ),
+                EventKind::SnakeRenamed { snake_id, ref name } => write!(
+                    f,
+                    "{:3.1}: snake {} renamed {:?}",
+                    self.event_time, snake_id, name
+                ),
+                EventKind::ScoreChanged { snake_id, score } => write!(
+                    f,
+                    "{:3.1}: snake {} score now {:.1}",
+                    self.event_time, snake_id, score
+                ),
+                EventKind::GameStarted => write!(f, "{:3.1}: game started", self.event_time),
+                EventKind::GameEnded { winner } => match winner {
+                    Some(team_id) => write!(f, "{:3.1}: game ended, won by team {}", self.event_time, team_id),
+                    None => write!(f, "{:3.1}: game ended with no winner", self.event_time),
+                },
+                EventKind::SnakeRemoved { snake_id } => {
+                    write!(f, "{:3.1}: snake {} removed", self.event_time, snake_id)
+                }
+            }
+        }
+    }
+
+    pub type SubscriberId = usize;
+
+    // The subscriber that get_event() reads for
+    pub const PRIMARY_SUBSCRIBER: SubscriberId = 0;
+
+    // How long events are kept for subscribers that haven't read them yet
+    #[derive(Copy, Clone, PartialEq, Debug, Default)]
+    pub struct RetentionPolicy {
+        // keep at most this many unread events, making room as the overflow policy says.
+        // None keeps everything until every subscriber has read it.
+        pub max_backlog: Option<usize>,
+        pub overflow: OverflowPolicy,
+    }
+
+    // What to do with an event logged when the backlog is full
+    #[derive(Copy, Clone, PartialEq, Debug, Default)]
+    pub enum OverflowPolicy {
+        // make room by dropping the oldest; subscribers that hadn't read it miss it
+        #[default]
+        DropOldest,
+        // keep the backlog as it is and drop the new event
+        DropNewest,
+        // drop the whole backlog and ask the owner for a StateSnapshot to stand
+        // in for it - see take_snapshot_request()
+        FoldIntoSnapshot,
+        // refuse the new event with LogError::QueueFull, so the caller can slow down
+        Backpressure,
+    }
+
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    pub enum LogError {
+        // further behind the latest event than the reorder window allows
+        TooLate {
+            event_time: f64,
+            // the earliest event_time the queue would still have taken
+            oldest_accepted: f64,
+        },
+        // the backlog is full and the overflow policy is Backpressure - or, for
+        // an input, DropNewest, which would only have dropped it
+        QueueFull { max_backlog: usize },
+    }
+    impl std::error::Error for LogError {}
+    impl fmt::Display for LogError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                LogError::TooLate { event_time, oldest_accepted } => write!(
+                    f,
+                    "Event at {:.3} is too late, nothing before {:.3} can be logged!",
+                    event_time, oldest_accepted
+                ),
+                LogError::QueueFull { max_backlog } => {
+                    write!(f, "Event queue is full at {} events!", max_backlog)
+                }
             }
         }
     }
 
+    // Running totals of events the queue didn't pass on, so operators can see
+    // when consumers fall behind
+    #[derive(Copy, Clone, PartialEq, Debug, Default)]
+    pub struct QueueStats {
+        // made room for newer events under DropOldest
+        pub dropped_oldest: u64,
+        // turned away under DropNewest
+        pub dropped_newest: u64,
+        // folded away under FoldIntoSnapshot
+        pub folded: u64,
+        // refused with LogError::TooLate
+        pub refused_late: u64,
+        // refused with LogError::QueueFull
+        pub refused_full: u64,
+        // the sink couldn't write them
+        pub sink_failures: u64,
+    }
+
+    // Somewhere every event goes as it's released into the log, whatever the
+    // subscribers and the retention policy make of it - e.g. a journal on disk
+    pub trait EventSink: Send {
+        fn write_event(&mut self, event: &Event) -> std::io::Result<()>;
+    }
+
+    struct Cursor {
+        // position in the log of the next event this subscriber will get
+        next: u64,
+        // events dropped by the retention policy before this subscriber read them
+        missed: u64,
+        // only events matching this are handed out
+        filter: EventFilter,
+    }
+
+    // A broadcast log - every subscriber reads every event at its own pace,
+    // and an event is freed once all of them have read it.
     pub struct EventQueue {
         queue: VecDeque<Event>,
+        // position in the log of the event at the front of the queue. An
+        // event's position is its sequence number.
+        first_position: u64,
+        // the tick newly logged events belong to
+        tick: u64,
+        cursors: HashMap<SubscriberId, Cursor>,
+        next_subscriber_id: SubscriberId,
+        retention: RetentionPolicy,
+        // how far behind the latest event_time an event may be logged, in game
+        // seconds. None logs events in the order they come.
+        reorder_window: Option<f64>,
+        // events still inside the window, in (event_time, arrival) order. They
+        // get their sequence numbers once they're released into the log.
+        pending: VecDeque<Event>,
+        // the latest event_time logged so far
+        latest_time: f64,
+        stats: QueueStats,
+        // the backlog was folded away and the owner should log a StateSnapshot
+        snapshot_wanted: bool,
+        sink: Option<Box<dyn EventSink>>,
+    }
+    impl Default for EventQueue {
+        fn default() -> Self {
+            EventQueue::new()
+        }
     }
     impl EventQueue {
         pub fn new() -> EventQueue {
+            EventQueue::new_at(0, 0)
+        }
+
+        // A queue carrying on from an earlier one, e.g. for a restored game, so
+        // the sequence numbers keep going up
+        pub fn new_at(next_sequence: u64, tick: u64) -> EventQueue {
+            let mut cursors = HashMap::new();
+            cursors.insert(PRIMARY_SUBSCRIBER, Cursor { next: next_sequence, missed: 0, filter: EventFilter::Everything });
             EventQueue {
                 queue: VecDeque::new(),
+                first_position: next_sequence,
+                tick,
+                cursors,
+                next_subscriber_id: PRIMARY_SUBSCRIBER + 1,
+                retention: RetentionPolicy::default(),
+                reorder_window: None,
+                pending: VecDeque::new(),
+                latest_time: f64::NEG_INFINITY,
+                stats: QueueStats::default(),
+                snapshot_wanted: false,
+                sink: None,
+            }
+        }
+
+        fn end_position(&self) -> u64 {
+            self.first_position + self.queue.len() as u64
+        }
+
+        // The sequence number the next logged event will get
+        pub fn get_next_sequence(&self) -> u64 {
+            self.end_position()
+        }
+
+        pub fn set_tick(&mut self, tick: u64) {
+            self.tick = tick;
+        }
+
+        // New subscribers get the events logged from now on
+        pub fn subscribe(&mut self) -> SubscriberId {
+            let next = self.end_position();
+            self.add_subscriber(next, EventFilter::Everything)
+        }
+
+        // ...or everything still held, for those that want as much history as there is
+        pub fn subscribe_from_oldest(&mut self) -> SubscriberId {
+            let next = self.first_position;
+            self.add_subscriber(next, EventFilter::Everything)
+        }
+
+        // Subscribers that only want some of the events, e.g. a spectator following one snake
+        pub fn subscribe_filtered(&mut self, filter: EventFilter) -> SubscriberId {
+            let next = self.end_position();
+            self.add_subscriber(next, filter)
+        }
+
+        // Pick up after the last event a subscriber saw, e.g. on reconnecting.
+        // Events since then that are no longer held count as missed.
+        pub fn subscribe_after(&mut self, sequence: u64, filter: EventFilter) -> SubscriberId {
+            let next = sequence.saturating_add(1).min(self.end_position());
+            let subscriber_id = self.add_subscriber(next.max(self.first_position), filter);
+            if next < self.first_position {
+                self.cursors.get_mut(&subscriber_id).unwrap().missed = self.first_position - next;
+            }
+            subscriber_id
+        }
+
+        fn add_subscriber(&mut self, next: u64, filter: EventFilter) -> SubscriberId {
+            let subscriber_id = self.next_subscriber_id;
+            self.next_subscriber_id += 1;
+            self.cursors.insert(subscriber_id, Cursor { next, missed: 0, filter });
+            subscriber_id
+        }
+
+        // Works for the primary subscriber too, for owners that never call get_event()
+        pub fn unsubscribe(&mut self, subscriber_id: SubscriberId) {
+            self.cursors.remove(&subscriber_id);
+            self.free_read_events();
+        }
+
+        // The sink gets everything the queue still holds, then each event as
+        // it's released, so attach it before anything is read to have the whole
+        // game. Returns the sink it replaces.
+        pub fn set_sink(&mut self, sink: Option<Box<dyn EventSink>>) -> Option<Box<dyn EventSink>> {
+            let old_sink = std::mem::replace(&mut self.sink, sink);
+            if let Some(sink) = self.sink.as_mut() {
+                for event in self.queue.iter() {
+                    if sink.write_event(event).is_err() {
+                        self.stats.sink_failures += 1;
+                    }
+                }
             }
+            old_sink
         }
 
-        pub fn log_snake_born(&mut self, event_time: f64, snake_id: usize, new_snake: Snake) {
-            self.log_event(Event {
-                kind: EventKind::SnakeBorn {
-                    snake_id: snake_id,
+        pub fn set_retention_policy(&mut self, retention: RetentionPolicy) {
+            self.retention = retention;
+            self.apply_retention();
+        }
+
+        pub fn log_snake_born(&mut self, event_time: f64, snake_id: usize, new_snake: Snake) -> Result<(), LogError> {
+            self.log_event(Event::new(
+                EventKind::SnakeBorn {
+                    snake_id,
                     loc: new_snake.get_location(),
                     vel: new_snake.get_velocity(),
                     size: new_snake.get_size(),
+                    team: new_snake.get_team(),
                 },
-                event_time: event_time,
-            });
+                event_time,
+            ))
         }
 
-        pub fn _log_snake_killed(&mut self, event_time: f64, snake_id: usize) {
-            self.log_event(Event {
-                kind: EventKind::SnakeKilled { snake_id: snake_id },
-                event_time: event_time,
-            });
+        pub fn log_snake_killed(&mut self, event_time: f64, snake_id: usize) -> Result<(), LogError> {
+            self.log_event(Event::new(
+                EventKind::SnakeKilled { snake_id },
+                event_time,
+            ))
         }
 
-        pub fn log_snake_turned(&mut self, event_time: f64, snake_id: usize, rad_relative: f64) {
-            self.log_event(Event {
-                kind: EventKind::SnakeTurned {
-                    snake_id: snake_id,
-                    rad_relative: rad_relative,
+        pub fn log_snake_turned(&mut self, event_time: f64, snake_id: usize, rad_relative: f64) -> Result<(), LogError> {
+            self.log_event(Event::new(
+                EventKind::SnakeTurned {
+                    snake_id,
+                    rad_relative,
                 },
-                event_time: event_time,
-            });
+                event_time,
+            ))
         }
 
-        pub fn log_snake_ate(&mut self, event_time: f64, snake_id: usize, food_id: usize) {
-            self.log_event(Event {
-                kind: EventKind::SnakeAte {
-                    snake_id: snake_id,
-                    food_id: food_id,
+        pub fn log_snake_ate(&mut self, event_time: f64, snake_id: usize, food_id: usize) -> Result<(), LogError> {
+            self.log_event(Event::new(
+                EventKind::SnakeAte {
+                    snake_id,
+                    food_id,
                 },
-                event_time: event_time,
-            });
+                event_time,
+            ))
         }
 
-        pub fn log_food_added(&mut self, event_time: f64, food_id: usize, new_food: Food) {
-            self.log_event(Event {
-                kind: EventKind::FoodAdded {
-                    food_id: food_id,
+        pub fn log_food_added(&mut self, event_time: f64, food_id: usize, new_food: Food) -> Result<(), LogError> {
+            self.log_event(Event::new(
+                EventKind::FoodAdded {
+                    food_id,
                     loc: new_food.get_location(),
                     nutrition: new_food.get_nutrition(),
                 },
-                event_time: event_time,
-            });
+                event_time,
+            ))
+        }
+
+        pub fn log_clock_advanced(&mut self, event_time: f64, delta_t: f64) -> Result<(), LogError> {
+            self.log_event(Event::new(
+                EventKind::ClockAdvanced { delta_t },
+                event_time,
+            ))
+        }
+
+        pub fn log_snake_boosted(&mut self, event_time: f64, snake_id: usize, speed: f64) -> Result<(), LogError> {
+            self.log_event(Event::new(
+                EventKind::SnakeBoosted { snake_id, speed },
+                event_time,
+            ))
+        }
+
+        pub fn log_snake_renamed(&mut self, event_time: f64, snake_id: usize, name: &str) -> Result<(), LogError> {
+            self.log_event(Event::new(
+                EventKind::SnakeRenamed {
+                    snake_id,
+                    name: name.to_string(),
+                },
+                event_time,
+            ))
+        }
+
+        // Events can come in out of time order by up to the window; the queue
+        // holds on to them until nothing earlier can still arrive, then hands
+        // them out in time order. Anything later than that is refused.
+        pub fn set_reorder_window(&mut self, reorder_window: Option<f64>) {
+            self.reorder_window = reorder_window;
+            self.release_pending();
+        }
+
+        pub fn get_reorder_window(&self) -> Option<f64> {
+            self.reorder_window
+        }
+
+        // Whether an event at event_time would be taken, or refused as too late
+        pub fn accepts(&self, event_time: f64) -> bool {
+            match self.reorder_window {
+                Some(window) => event_time >= self.latest_time - window,
+                None => true,
+            }
+        }
+
+        // Stamps the event with the current tick, and the next sequence number
+        // once it's in the log. With a reorder window, it may be held back for
+        // events from earlier in the window that haven't come in yet.
+        pub fn log_event(&mut self, event: Event) -> Result<(), LogError> {
+            self.check(event.event_time, false)?;
+            self.push(event);
+            Ok(())
+        }
+
+        // Whether an input at event_time, e.g. a turn, would make it into the
+        // log. Owners check before acting on an input, and turn it away if not,
+        // so that everything the game plays is in the log to be replayed - which
+        // means refusing it if DropNewest would only have dropped it.
+        pub fn check_input(&mut self, event_time: f64) -> Result<(), LogError> {
+            self.check(event_time, true)
+        }
+
+        // Logs what the owner worked out for itself from the inputs it took,
+        // e.g. a meal or a collision, which it can't take back. That's never
+        // refused, as it's at the owner's clock and nothing logged is ahead of
+        // that, but the retention policy still applies once it's in.
+        pub fn log_outcome(&mut self, event: Event) {
+            self.push(event);
         }
 
-        pub fn log_event(&mut self, event: Event) {
+        fn check(&mut self, event_time: f64, input: bool) -> Result<(), LogError> {
+            if !self.accepts(event_time) {
+                self.stats.refused_late += 1;
+                return Err(LogError::TooLate {
+                    event_time,
+                    oldest_accepted: self.latest_time - self.reorder_window.unwrap_or(0.0),
+                });
+            }
+            let refuses = match self.retention.overflow {
+                OverflowPolicy::Backpressure => true,
+                OverflowPolicy::DropNewest => input,
+                _ => false,
+            };
+            if let (Some(max_backlog), true) = (self.retention.max_backlog, refuses) {
+                if self.queue.len() + self.pending.len() >= max_backlog {
+                    self.stats.refused_full += 1;
+                    return Err(LogError::QueueFull { max_backlog });
+                }
+            }
+            Ok(())
+        }
+
+        fn push(&mut self, mut event: Event) {
+            event.tick = self.tick;
+            self.latest_time = self.latest_time.max(event.event_time);
+            // after anything logged at the same time or earlier
+            let idx = self.pending.iter().position(|held| held.event_time > event.event_time).unwrap_or(self.pending.len());
+            self.pending.insert(idx, event);
+            self.release_pending();
+        }
+
+        // Put the held events nothing can come in ahead of any more into the log
+        fn release_pending(&mut self) {
+            let release_before = match self.reorder_window {
+                Some(window) => self.latest_time - window,
+                None => f64::INFINITY,
+            };
+            while self.pending.front().is_some_and(|held| held.event_time <= release_before) {
+                let event = self.pending.pop_front().unwrap();
+                self.append(event);
+            }
+        }
+
+        // Everything still held back, whether or not the window has passed, e.g.
+        // when the game is over
+        pub fn flush(&mut self) {
+            while let Some(event) = self.pending.pop_front() {
+                self.append(event);
+            }
+        }
+
+        pub fn get_pending_count(&self) -> usize {
+            self.pending.len()
+        }
+
+        pub fn get_stats(&self) -> QueueStats {
+            self.stats
+        }
+
+        // True once after the backlog has been folded away, when the owner
+        // should log a StateSnapshot for subscribers to pick up from
+        pub fn take_snapshot_request(&mut self) -> bool {
+            std::mem::take(&mut self.snapshot_wanted)
+        }
+
+        fn append(&mut self, mut event: Event) {
+            if let (Some(max_backlog), OverflowPolicy::DropNewest) = (self.retention.max_backlog, self.retention.overflow) {
+                if self.queue.len() >= max_backlog {
+                    // it never gets a sequence number, so nobody sees a gap
+                    self.stats.dropped_newest += 1;
+                    return;
+                }
+            }
+            event.sequence = self.end_position();
+            // caught up subscribers that don't want this event can skip it now,
+            // so it isn't held for them
+            let end_position = self.end_position();
+            for cursor in self.cursors.values_mut() {
+                if cursor.next == end_position && !cursor.filter.matches(&event) {
+                    cursor.next += 1;
+                }
+            }
+            if let Some(sink) = self.sink.as_mut() {
+                if sink.write_event(&event).is_err() {
+                    self.stats.sink_failures += 1;
+                }
+            }
             self.queue.push_back(event);
+            self.apply_retention();
         }
 
         pub fn get_event(&mut self) -> Option<Event> {
-            self.queue.pop_front()
+            self.get_event_for(PRIMARY_SUBSCRIBER)
+        }
+
+        // The subscriber's next unread event, or None if it's caught up (or unknown)
+        pub fn get_event_for(&mut self, subscriber_id: SubscriberId) -> Option<Event> {
+            let cursor = self.cursors.get_mut(&subscriber_id)?;
+            let mut found = None;
+            while let Some(event) = self.queue.get((cursor.next - self.first_position) as usize) {
+                cursor.next += 1;
+                if cursor.filter.matches(event) {
+                    found = Some(event.clone());
+                    break;
+                }
+            }
+            self.free_read_events();
+            found
+        }
+
+        pub fn get_unread_count(&self, subscriber_id: SubscriberId) -> usize {
+            match self.cursors.get(&subscriber_id) {
+                Some(cursor) => self.queue.iter()
+                    .skip((cursor.next - self.first_position) as usize)
+                    .filter(|event| cursor.filter.matches(event))
+                    .count(),
+                None => 0,
+            }
+        }
+
+        pub fn get_missed_count(&self, subscriber_id: SubscriberId) -> u64 {
+            match self.cursors.get(&subscriber_id) {
+                Some(cursor) => cursor.missed,
+                None => 0,
+            }
+        }
+
+        // Up to limit of the events still held, oldest first, after the given
+        // sequence number - for looking back over the log without reading it as
+        // any subscriber
+        pub fn get_held_events(&self, after: Option<u64>, limit: usize) -> Vec<Event> {
+            let start = match after {
+                Some(sequence) => sequence.saturating_add(1).max(self.first_position),
+                None => self.first_position,
+            };
+            self.queue.iter()
+                .skip((start - self.first_position) as usize)
+                .take(limit)
+                .cloned()
+                .collect()
+        }
+
+        // The sequence number of the oldest event still held - or of the next
+        // one logged, if none are
+        pub fn get_oldest_held_sequence(&self) -> u64 {
+            self.first_position
         }
 
         pub fn _is_empty(&self) -> bool {
             self.queue.is_empty()
         }
+
+        // Drop events that every subscriber has read
+        fn free_read_events(&mut self) {
+            let oldest_unread = self.cursors.values().map(|cursor| cursor.next).min().unwrap_or_else(|| self.end_position());
+            while self.first_position < oldest_unread {
+                self.queue.pop_front();
+                self.first_position += 1;
+            }
+        }
+
+        // Drop events past the backlog limit, read or not
+        fn apply_retention(&mut self) {
+            if let Some(max_backlog) = self.retention.max_backlog {
+                if self.queue.len() > max_backlog {
+                    let to_drop = match self.retention.overflow {
+                        OverflowPolicy::FoldIntoSnapshot => {
+                            self.snapshot_wanted = true;
+                            self.stats.folded += self.queue.len() as u64;
+                            self.queue.len()
+                        }
+                        // DropNewest and Backpressure only get here when the
+                        // policy is tightened, and the oldest go then too
+                        _ => {
+                            let to_drop = self.queue.len() - max_backlog;
+                            self.stats.dropped_oldest += to_drop as u64;
+                            to_drop
+                        }
+                    };
+                    self.queue.drain(..to_drop);
+                    self.first_position += to_drop as u64;
+                }
+                let first_position = self.first_position;
+                for cursor in self.cursors.values_mut() {
+                    if cursor.next < first_position {
+                        cursor.missed += first_position - cursor.next;
+                        cursor.next = first_position;
+                    }
+                }
+            }
+            self.free_read_events();
+        }
     }
 
     #[test]
     fn t_new() {
         let test_event_queue = EventQueue::new();
-        assert!(true, test_event_queue._is_empty());
+        assert!(test_event_queue._is_empty());
         assert_eq!(0, test_event_queue.queue.len());
     }
 
@@ -171,17 +871,22 @@ pub mod eventqueue {
                     delta_y: 0.0,
                 },
                 size: 0.0,
+                team: None,
             },
             event_time: 0.0,
+            sequence: 0,
+            tick: 0,
         };
-        test_event_queue.log_event(event);
+        test_event_queue.log_event(event).unwrap();
         assert_eq!(1, test_event_queue.queue.len());
 
         let event = Event {
             kind: EventKind::SnakeKilled { snake_id: 0 },
             event_time: 0.0,
+            sequence: 0,
+            tick: 0,
         };
-        test_event_queue.log_event(event);
+        test_event_queue.log_event(event).unwrap();
         assert_eq!(2, test_event_queue.queue.len());
 
         let event = Event {
@@ -190,8 +895,10 @@ pub mod eventqueue {
                 rad_relative: 0.0,
             },
             event_time: 0.0,
+            sequence: 0,
+            tick: 0,
         };
-        test_event_queue.log_event(event);
+        test_event_queue.log_event(event).unwrap();
         assert_eq!(3, test_event_queue.queue.len());
 
         let event = Event {
@@ -200,8 +907,10 @@ pub mod eventqueue {
                 food_id: 0,
             },
             event_time: 0.0,
+            sequence: 0,
+            tick: 0,
         };
-        test_event_queue.log_event(event);
+        test_event_queue.log_event(event).unwrap();
         assert_eq!(4, test_event_queue.queue.len());
 
         let event = Event {
@@ -211,9 +920,20 @@ pub mod eventqueue {
                 nutrition: 0.0,
             },
             event_time: 0.0,
+            sequence: 0,
+            tick: 0,
         };
-        test_event_queue.log_event(event);
+        test_event_queue.log_event(event).unwrap();
         assert_eq!(5, test_event_queue.queue.len());
+
+        let event = Event {
+            kind: EventKind::ClockAdvanced { delta_t: 0.0 },
+            event_time: 0.0,
+            sequence: 0,
+            tick: 0,
+        };
+        test_event_queue.log_event(event).unwrap();
+        assert_eq!(6, test_event_queue.queue.len());
     }
 
     #[test]
@@ -231,10 +951,13 @@ pub mod eventqueue {
                     delta_y: 0.0,
                 },
                 size: 0.0,
+                team: None,
             },
             event_time: 0.0,
+            sequence: 0,
+            tick: 0,
         };
-        test_event_queue.log_event(event_logged);
+        test_event_queue.log_event(event_logged.clone()).unwrap();
         assert_eq!(1, test_event_queue.queue.len());
 
         let event_gotten = test_event_queue.get_event().unwrap();
@@ -248,14 +971,18 @@ pub mod eventqueue {
                 rad_relative: 0.0,
             },
             event_time: 0.0,
+            sequence: 1,
+            tick: 0,
         };
-        test_event_queue.log_event(event_logged1);
+        test_event_queue.log_event(event_logged1.clone()).unwrap();
 
         let event_logged2 = Event {
             kind: EventKind::SnakeKilled { snake_id: 0 },
             event_time: 0.0,
+            sequence: 2,
+            tick: 0,
         };
-        test_event_queue.log_event(event_logged2);
+        test_event_queue.log_event(event_logged2.clone()).unwrap();
         assert_eq!(2, test_event_queue.queue.len());
 
         let event_gotten1 = test_event_queue.get_event().unwrap();
@@ -270,6 +997,446 @@ pub mod eventqueue {
         assert!(event_empty.is_none());
     }
 
+    #[test]
+    fn t_sequence_and_tick() {
+        let mut test_event_queue = EventQueue::new();
+        assert_eq!(0, test_event_queue.get_next_sequence());
+        test_event_queue.log_food_added(0.0, 0, Food::new(1.0, 1.0, 0.0)).unwrap();
+        test_event_queue.log_food_added(0.0, 1, Food::new(2.0, 2.0, 0.0)).unwrap();
+        test_event_queue.set_tick(1);
+        test_event_queue.log_clock_advanced(0.5, 0.5).unwrap();
+        test_event_queue.log_snake_ate(0.5, 0, 1).unwrap();
+        test_event_queue.log_snake_ate(0.5, 1, 0).unwrap();
+
+        // events at the same time still tell apart, in the order they were logged
+        let stamps: Vec<(u64, u64)> = (0..5).map(|_| {
+            let event = test_event_queue.get_event().unwrap();
+            (event.sequence, event.tick)
+        }).collect();
+        assert_eq!(vec![(0, 0), (1, 0), (2, 1), (3, 1), (4, 1)], stamps);
+        assert_eq!(5, test_event_queue.get_next_sequence());
+
+        // a queue carrying on from another keeps counting
+        let mut test_event_queue = EventQueue::new_at(5, 1);
+        test_event_queue.log_snake_killed(0.5, 0).unwrap();
+        let event = test_event_queue.get_event().unwrap();
+        assert_eq!(5, event.sequence);
+        assert_eq!(1, event.tick);
+    }
+
+    #[test]
+    fn t_subscribe_after() {
+        let mut test_event_queue = EventQueue::new();
+        test_event_queue.set_retention_policy(RetentionPolicy { max_backlog: Some(4), ..RetentionPolicy::default() });
+        for snake_id in 0..6 {
+            test_event_queue.log_snake_killed(0.0, snake_id).unwrap();
+        }
+        // sequences 2 to 5 are still held
+
+        // a client that saw up to 3 picks up at 4
+        let reconnected = test_event_queue.subscribe_after(3, EventFilter::Everything);
+        assert_eq!(0, test_event_queue.get_missed_count(reconnected));
+        assert_eq!(Some(4), test_event_queue.get_event_for(reconnected).map(|event| event.sequence));
+        assert_eq!(Some(5), test_event_queue.get_event_for(reconnected).map(|event| event.sequence));
+        assert_eq!(None, test_event_queue.get_event_for(reconnected));
+
+        // one that saw up to 0 has missed 1, and gets the rest
+        let too_late = test_event_queue.subscribe_after(0, EventFilter::Everything);
+        assert_eq!(1, test_event_queue.get_missed_count(too_late));
+        assert_eq!(Some(2), test_event_queue.get_event_for(too_late).map(|event| event.sequence));
+
+        // one that's somehow ahead just waits for what's next
+        let ahead = test_event_queue.subscribe_after(100, EventFilter::Everything);
+        assert_eq!(0, test_event_queue.get_unread_count(ahead));
+        test_event_queue.log_snake_killed(0.0, 6).unwrap();
+        assert_eq!(Some(6), test_event_queue.get_event_for(ahead).map(|event| event.sequence));
+
+        // filters apply as usual
+        let spectator = test_event_queue.subscribe_after(3, EventFilter::Snake(5));
+        assert_eq!(Some(5), test_event_queue.get_event_for(spectator).map(|event| event.sequence));
+        assert_eq!(None, test_event_queue.get_event_for(spectator));
+    }
+
+    #[test]
+    fn t_reorder_window() {
+        fn killed_at(snake_id: usize, event_time: f64) -> Event {
+            Event::new(EventKind::SnakeKilled { snake_id }, event_time)
+        }
+        fn read_all(test_event_queue: &mut EventQueue) -> Vec<(usize, u64)> {
+            let mut read = Vec::new();
+            while let Some(event) = test_event_queue.get_event() {
+                read.push((event.kind.get_snake_id().unwrap(), event.sequence));
+            }
+            read
+        }
+
+        let mut test_event_queue = EventQueue::new();
+        test_event_queue.set_reorder_window(Some(1.0));
+        test_event_queue.log_event(killed_at(0, 0.0)).unwrap();
+        test_event_queue.log_event(killed_at(1, 2.0)).unwrap();
+        // 0 is more than the window behind 1, so it's safe to hand out; 1 isn't yet
+        assert_eq!(vec![(0, 0)], read_all(&mut test_event_queue));
+        assert_eq!(1, test_event_queue.get_pending_count());
+
+        // something from earlier in the window goes ahead of 1
+        test_event_queue.log_event(killed_at(2, 1.5)).unwrap();
+        test_event_queue.log_event(killed_at(3, 3.0)).unwrap();
+        assert_eq!(vec![(2, 1), (1, 2)], read_all(&mut test_event_queue));
+
+        // something from before the window is refused, not slipped in
+        assert!(!test_event_queue.accepts(0.5));
+        assert_eq!(
+            Err(LogError::TooLate { event_time: 0.5, oldest_accepted: 2.0 }),
+            test_event_queue.log_event(killed_at(4, 0.5))
+        );
+        assert_eq!(1, test_event_queue.get_stats().refused_late);
+
+        // events at the same time stay in the order they came
+        test_event_queue.log_event(killed_at(5, 3.0)).unwrap();
+        test_event_queue.log_snake_killed(2.5, 6).unwrap();
+        assert!(read_all(&mut test_event_queue).is_empty());
+        test_event_queue.flush();
+        assert_eq!(vec![(6, 3), (3, 4), (5, 5)], read_all(&mut test_event_queue));
+
+        // turning the window off lets everything held go
+        test_event_queue.log_event(killed_at(7, 10.0)).unwrap();
+        assert_eq!(1, test_event_queue.get_pending_count());
+        test_event_queue.set_reorder_window(None);
+        assert_eq!(vec![(7, 6)], read_all(&mut test_event_queue));
+        // and without one, late is fine
+        test_event_queue.log_event(killed_at(8, 0.0)).unwrap();
+        assert_eq!(vec![(8, 7)], read_all(&mut test_event_queue));
+    }
+
+    #[test]
+    fn t_subscribers() {
+        let mut test_event_queue = EventQueue::new();
+        let event1 = Event {
+            kind: EventKind::SnakeKilled { snake_id: 1 },
+            event_time: 0.0,
+            sequence: 0,
+            tick: 0,
+        };
+        let event2 = Event {
+            kind: EventKind::SnakeKilled { snake_id: 2 },
+            event_time: 1.0,
+            sequence: 1,
+            tick: 0,
+        };
+        let event3 = Event {
+            kind: EventKind::SnakeKilled { snake_id: 3 },
+            event_time: 2.0,
+            sequence: 2,
+            tick: 0,
+        };
+        test_event_queue.log_event(event1.clone()).unwrap();
+
+        // a new subscriber only hears about what happens after it subscribed...
+        let late_subscriber = test_event_queue.subscribe();
+        // ...unless it asks for the history
+        let history_subscriber = test_event_queue.subscribe_from_oldest();
+        assert_ne!(late_subscriber, history_subscriber);
+        test_event_queue.log_event(event2.clone()).unwrap();
+        test_event_queue.log_event(event3.clone()).unwrap();
+
+        // every subscriber gets every event, each at its own pace
+        assert_eq!(Some(event1.clone()), test_event_queue.get_event());
+        assert_eq!(Some(event2.clone()), test_event_queue.get_event_for(late_subscriber));
+        assert_eq!(Some(event1.clone()), test_event_queue.get_event_for(history_subscriber));
+        assert_eq!(Some(event2.clone()), test_event_queue.get_event_for(history_subscriber));
+        assert_eq!(Some(event3.clone()), test_event_queue.get_event_for(history_subscriber));
+        assert_eq!(None, test_event_queue.get_event_for(history_subscriber));
+        assert_eq!(1, test_event_queue.get_unread_count(late_subscriber));
+        assert_eq!(2, test_event_queue.get_unread_count(PRIMARY_SUBSCRIBER));
+
+        // events are only freed once everyone has read them
+        assert_eq!(2, test_event_queue.queue.len());
+        assert_eq!(Some(event2.clone()), test_event_queue.get_event());
+        assert_eq!(Some(event3.clone()), test_event_queue.get_event());
+        assert_eq!(1, test_event_queue.queue.len());
+        assert_eq!(Some(event3), test_event_queue.get_event_for(late_subscriber));
+        assert!(test_event_queue._is_empty());
+
+        // a subscriber that leaves stops holding events back
+        test_event_queue.log_event(event1.clone()).unwrap();
+        test_event_queue.unsubscribe(late_subscriber);
+        // (logged again, it's a new event with its own number)
+        let event1_again = Event { sequence: 3, ..event1 };
+        assert_eq!(Some(event1_again.clone()), test_event_queue.get_event());
+        assert_eq!(Some(event1_again), test_event_queue.get_event_for(history_subscriber));
+        assert!(test_event_queue._is_empty());
+        assert_eq!(None, test_event_queue.get_event_for(late_subscriber));
+
+        // ...even the primary one
+        let only_subscriber = test_event_queue.subscribe();
+        test_event_queue.unsubscribe(PRIMARY_SUBSCRIBER);
+        test_event_queue.unsubscribe(history_subscriber);
+        test_event_queue.log_event(event2.clone()).unwrap();
+        assert_eq!(Some(Event { sequence: 4, ..event2 }), test_event_queue.get_event_for(only_subscriber));
+        assert!(test_event_queue._is_empty());
+        assert_eq!(None, test_event_queue.get_event());
+    }
+
+    #[test]
+    fn t_filtered_subscribers() {
+        let mut test_event_queue = EventQueue::new();
+        test_event_queue.unsubscribe(PRIMARY_SUBSCRIBER);
+        let scoreboard = test_event_queue.subscribe_filtered(EventFilter::Kind("snake_ate".to_string()).or(EventFilter::Kind("snake_killed".to_string())));
+        let spectator = test_event_queue.subscribe_filtered(EventFilter::Snake(1));
+
+        let born = Event {
+            kind: EventKind::SnakeBorn {
+                snake_id: 1,
+                loc: Coordinates { x: 0.0, y: 0.0 },
+                vel: Velocity {
+                    delta_x: 0.0,
+                    delta_y: 1.0,
+                },
+                size: 1.0,
+                team: None,
+            },
+            event_time: 0.0,
+            sequence: 0,
+            tick: 0,
+        };
+        let other_snake_ate = Event {
+            kind: EventKind::SnakeAte {
+                snake_id: 2,
+                food_id: 0,
+            },
+            event_time: 1.0,
+            sequence: 1,
+            tick: 0,
+        };
+        let killed = Event {
+            kind: EventKind::SnakeKilled { snake_id: 1 },
+            event_time: 2.0,
+            sequence: 2,
+            tick: 0,
+        };
+        test_event_queue.log_event(born.clone()).unwrap();
+        test_event_queue.log_event(other_snake_ate.clone()).unwrap();
+        test_event_queue.log_event(killed.clone()).unwrap();
+
+        assert_eq!(2, test_event_queue.get_unread_count(scoreboard));
+        assert_eq!(Some(other_snake_ate), test_event_queue.get_event_for(scoreboard));
+        assert_eq!(Some(killed.clone()), test_event_queue.get_event_for(scoreboard));
+        assert_eq!(None, test_event_queue.get_event_for(scoreboard));
+
+        assert_eq!(2, test_event_queue.get_unread_count(spectator));
+        assert_eq!(Some(born), test_event_queue.get_event_for(spectator));
+        assert_eq!(Some(killed), test_event_queue.get_event_for(spectator));
+        assert!(test_event_queue._is_empty());
+
+        // events nobody wants aren't held for anyone
+        test_event_queue.log_event(Event {
+            kind: EventKind::SnakeTurned {
+                snake_id: 2,
+                rad_relative: 1.0,
+            },
+            event_time: 3.0,
+            sequence: 0,
+            tick: 0,
+        }).unwrap();
+        assert!(test_event_queue._is_empty());
+    }
+
+    #[test]
+    fn t_retention_policy() {
+        let mut test_event_queue = EventQueue::new();
+        let fast_subscriber = test_event_queue.subscribe();
+        test_event_queue.set_retention_policy(RetentionPolicy { max_backlog: Some(2), ..RetentionPolicy::default() });
+
+        for snake_id in 0..5 {
+            test_event_queue.log_event(Event {
+                kind: EventKind::SnakeKilled { snake_id },
+                event_time: 0.0,
+                sequence: 0,
+                tick: 0,
+            }).unwrap();
+            assert!(test_event_queue.get_event_for(fast_subscriber).is_some());
+        }
+
+        // the primary subscriber never read anything, so it lost all but the last two
+        assert_eq!(2, test_event_queue.queue.len());
+        assert_eq!(3, test_event_queue.get_missed_count(PRIMARY_SUBSCRIBER));
+        assert_eq!(0, test_event_queue.get_missed_count(fast_subscriber));
+        assert_eq!(2, test_event_queue.get_unread_count(PRIMARY_SUBSCRIBER));
+        let event = test_event_queue.get_event().unwrap();
+        assert_eq!(EventKind::SnakeKilled { snake_id: 3 }, event.kind);
+
+        // tightening the policy applies straight away
+        test_event_queue.set_retention_policy(RetentionPolicy { max_backlog: Some(0), ..RetentionPolicy::default() });
+        assert!(test_event_queue._is_empty());
+        assert_eq!(4, test_event_queue.get_missed_count(PRIMARY_SUBSCRIBER));
+        assert_eq!(4, test_event_queue.get_stats().dropped_oldest);
+    }
+
+    #[test]
+    fn t_held_events() {
+        let mut test_event_queue = EventQueue::new();
+        test_event_queue.set_retention_policy(RetentionPolicy { max_backlog: Some(4), ..RetentionPolicy::default() });
+        for snake_id in 0..6 {
+            test_event_queue.log_snake_killed(0.0, snake_id).unwrap();
+        }
+        // the first two have gone
+        assert_eq!(2, test_event_queue.get_oldest_held_sequence());
+        let sequences = |events: Vec<Event>| -> Vec<u64> { events.iter().map(|event| event.sequence).collect() };
+        assert_eq!(vec![2, 3, 4, 5], sequences(test_event_queue.get_held_events(None, 10)));
+        assert_eq!(vec![2, 3, 4, 5], sequences(test_event_queue.get_held_events(Some(0), 10)));
+        assert_eq!(vec![4, 5], sequences(test_event_queue.get_held_events(Some(3), 10)));
+        assert_eq!(vec![2, 3], sequences(test_event_queue.get_held_events(None, 2)));
+        assert!(test_event_queue.get_held_events(Some(5), 10).is_empty());
+        assert!(test_event_queue.get_held_events(Some(u64::MAX), 10).is_empty());
+
+        // looking doesn't read
+        assert_eq!(4, test_event_queue.get_unread_count(PRIMARY_SUBSCRIBER));
+    }
+
+    #[test]
+    fn t_sink() {
+        use std::sync::{Arc, Mutex};
+        struct Collector(Arc<Mutex<Vec<Event>>>, bool);
+        impl EventSink for Collector {
+            fn write_event(&mut self, event: &Event) -> std::io::Result<()> {
+                if self.1 {
+                    return Err(std::io::Error::other("disk full"));
+                }
+                self.0.lock().unwrap().push(event.clone());
+                Ok(())
+            }
+        }
+
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let mut test_event_queue = EventQueue::new();
+        test_event_queue.set_reorder_window(Some(1.0));
+        test_event_queue.log_snake_killed(0.0, 0).unwrap();
+        test_event_queue.log_snake_killed(2.0, 1).unwrap();
+        // what the queue already holds goes first
+        assert!(test_event_queue.set_sink(Some(Box::new(Collector(written.clone(), false)))).is_none());
+        assert_eq!(1, written.lock().unwrap().len());
+
+        // then each event as it's released, in log order, even those nobody keeps
+        test_event_queue.set_retention_policy(RetentionPolicy { max_backlog: Some(0), ..RetentionPolicy::default() });
+        test_event_queue.log_snake_killed(1.5, 2).unwrap();
+        test_event_queue.flush();
+        let ids: Vec<usize> = written.lock().unwrap().iter().map(|event| event.kind.get_snake_id().unwrap()).collect();
+        assert_eq!(vec![0, 2, 1], ids);
+        let sequences: Vec<u64> = written.lock().unwrap().iter().map(|event| event.sequence).collect();
+        assert_eq!(vec![0, 1, 2], sequences);
+
+        // a sink that can't keep up is counted, not fatal
+        test_event_queue.set_sink(Some(Box::new(Collector(written.clone(), true))));
+        test_event_queue.log_outcome(Event::new(EventKind::SnakeKilled { snake_id: 3 }, 3.0));
+        test_event_queue.flush();
+        assert_eq!(1, test_event_queue.get_stats().sink_failures);
+        assert!(test_event_queue.set_sink(None).is_some());
+    }
+
+    #[test]
+    fn t_overflow_policies() {
+        fn fill(overflow: OverflowPolicy) -> (EventQueue, Vec<Result<(), LogError>>) {
+            let mut test_event_queue = EventQueue::new();
+            test_event_queue.set_retention_policy(RetentionPolicy { max_backlog: Some(3), overflow });
+            let results = (0..5).map(|snake_id| test_event_queue.log_snake_killed(0.0, snake_id)).collect();
+            (test_event_queue, results)
+        }
+        fn read_ids(test_event_queue: &mut EventQueue) -> Vec<usize> {
+            let mut ids = Vec::new();
+            while let Some(event) = test_event_queue.get_event() {
+                ids.push(event.kind.get_snake_id().unwrap());
+            }
+            ids
+        }
+
+        let (mut test_event_queue, results) = fill(OverflowPolicy::DropOldest);
+        assert!(results.iter().all(|result| result.is_ok()));
+        assert_eq!(2, test_event_queue.get_stats().dropped_oldest);
+        assert_eq!(2, test_event_queue.get_missed_count(PRIMARY_SUBSCRIBER));
+        assert_eq!(vec![2, 3, 4], read_ids(&mut test_event_queue));
+
+        let (mut test_event_queue, results) = fill(OverflowPolicy::DropNewest);
+        assert!(results.iter().all(|result| result.is_ok()));
+        assert_eq!(2, test_event_queue.get_stats().dropped_newest);
+        // nothing in the log went missing, and the numbering has no gaps
+        assert_eq!(0, test_event_queue.get_missed_count(PRIMARY_SUBSCRIBER));
+        assert_eq!(3, test_event_queue.get_next_sequence());
+        // an input is turned away instead, so whoever sent it knows
+        assert_eq!(Err(LogError::QueueFull { max_backlog: 3 }), test_event_queue.check_input(0.0));
+        assert_eq!(vec![0, 1, 2], read_ids(&mut test_event_queue));
+        assert_eq!(Ok(()), test_event_queue.check_input(0.0));
+
+        let (mut test_event_queue, results) = fill(OverflowPolicy::Backpressure);
+        assert_eq!(vec![Ok(()), Ok(()), Ok(()), Err(LogError::QueueFull { max_backlog: 3 }), Err(LogError::QueueFull { max_backlog: 3 })], results);
+        assert_eq!(2, test_event_queue.get_stats().refused_full);
+        // once the subscriber catches up there's room again
+        assert_eq!(vec![0, 1, 2], read_ids(&mut test_event_queue));
+        assert!(test_event_queue.log_snake_killed(0.0, 3).is_ok());
+        // what the owner has already played out still goes in
+        test_event_queue.log_outcome(Event::new(EventKind::SnakeKilled { snake_id: 4 }, 0.0));
+        test_event_queue.log_outcome(Event::new(EventKind::SnakeKilled { snake_id: 5 }, 0.0));
+        assert_eq!(Err(LogError::QueueFull { max_backlog: 3 }), test_event_queue.check_input(0.0));
+        assert_eq!(vec![3, 4, 5], read_ids(&mut test_event_queue));
+
+        let (mut test_event_queue, results) = fill(OverflowPolicy::FoldIntoSnapshot);
+        assert!(results.iter().all(|result| result.is_ok()));
+        // the fourth event folded the first four away; the fifth is still there
+        assert_eq!(4, test_event_queue.get_stats().folded);
+        assert!(test_event_queue.take_snapshot_request());
+        assert!(!test_event_queue.take_snapshot_request());
+        assert_eq!(vec![4], read_ids(&mut test_event_queue));
+    }
+
+    #[test]
+    fn t_event_kind_accessors() {
+        let kind = EventKind::SnakeAte {
+            snake_id: 1,
+            food_id: 2,
+        };
+        assert_eq!("snake_ate", kind.get_name());
+        assert_eq!(Some(1), kind.get_snake_id());
+        assert_eq!(Some(2), kind.get_food_id());
+        assert_eq!(None, kind.get_location());
+
+        let kind = EventKind::FoodAdded {
+            food_id: 3,
+            loc: Coordinates { x: 1.0, y: 2.0 },
+            nutrition: 1.0,
+        };
+        assert_eq!("food_added", kind.get_name());
+        assert_eq!(None, kind.get_snake_id());
+        assert_eq!(Some(3), kind.get_food_id());
+        assert_eq!(Some(Coordinates { x: 1.0, y: 2.0 }), kind.get_location());
+
+        let kind = EventKind::StateSnapshot { snakes: vec![] };
+        assert_eq!("state_snapshot", kind.get_name());
+        assert_eq!(None, kind.get_snake_id());
+        assert_eq!(None, kind.get_location());
+
+        let kind = EventKind::FoodRemoved {
+            food_id: 3,
+            reason: FoodRemovalReason::Consumed,
+        };
+        assert_eq!("food_removed", kind.get_name());
+        assert_eq!(None, kind.get_snake_id());
+        assert_eq!(Some(3), kind.get_food_id());
+
+        // collisions happen somewhere, and are about the first snake
+        let kind = EventKind::SnakeCollided {
+            snake_id: 1,
+            other_snake_id: 2,
+            loc: Coordinates { x: 1.0, y: 2.0 },
+        };
+        assert_eq!("snake_collided", kind.get_name());
+        assert_eq!(Some(1), kind.get_snake_id());
+        assert_eq!(Some(Coordinates { x: 1.0, y: 2.0 }), kind.get_location());
+
+        let kind = EventKind::GameEnded { winner: Some(1) };
+        assert_eq!("game_ended", kind.get_name());
+        assert_eq!(None, kind.get_snake_id());
+        assert_eq!(None, kind.get_food_id());
+    }
+
     #[test]
     fn t_event_fmt() {
         let event = Event {
@@ -281,14 +1448,36 @@ pub mod eventqueue {
                     delta_y: 0.0,
                 },
                 size: 0.0,
+                team: None,
             },
             event_time: 0.0,
+            sequence: 0,
+            tick: 0,
         };
         assert_eq!("0.0: snake 0 born at (0.0,0.0) headed <0.0,0.0> of size 0.0", format!("{}", event));
 
+        let event = Event {
+            kind: EventKind::SnakeBorn {
+                snake_id: 1,
+                loc: Coordinates { x: 0.0, y: 0.0 },
+                vel: Velocity {
+                    delta_x: 0.0,
+                    delta_y: 1.0,
+                },
+                size: 1.0,
+                team: Some(2),
+            },
+            event_time: 0.0,
+            sequence: 0,
+            tick: 0,
+        };
+        assert_eq!("0.0: snake 1 born at (0.0,0.0) headed <0.0,1.0> of size 1.0 on team 2", format!("{}", event));
+
         let event = Event {
             kind: EventKind::SnakeKilled { snake_id: 0 },
             event_time: 0.0,
+            sequence: 0,
+            tick: 0,
         };
         assert_eq!("0.0: snake 0 killed", format!("{}", event));
 
@@ -298,6 +1487,8 @@ pub mod eventqueue {
                 rad_relative: 1.0,
             },
             event_time: 0.0,
+            sequence: 0,
+            tick: 0,
         };
         assert_eq!("0.0: snake 0 turned +1.000 rad", format!("{}", event));
 
@@ -307,6 +1498,8 @@ pub mod eventqueue {
                 food_id: 0,
             },
             event_time: 0.0,
+            sequence: 0,
+            tick: 0,
         };
         assert_eq!("0.0: snake 0 ate food 0", format!("{}", event));
 
@@ -317,7 +1510,398 @@ pub mod eventqueue {
                 nutrition: 0.0,
             },
             event_time: 0.0,
+            sequence: 0,
+            tick: 0,
         };
         assert_eq!("0.0: food 0 added at (0.0,0.0) of nutrition 0.0", format!("{}", event));
+
+        let event = Event {
+            kind: EventKind::ClockAdvanced { delta_t: 0.5 },
+            event_time: 1.5,
+            sequence: 0,
+            tick: 0,
+        };
+        assert_eq!("1.5: clock advanced 0.5", format!("{}", event));
+
+        let event = Event {
+            kind: EventKind::StateSnapshot {
+                snakes: vec![SnakeState {
+                    snake_id: 0,
+                    loc: Coordinates { x: 0.0, y: 1.5 },
+                    vel: Velocity {
+                        delta_x: 0.0,
+                        delta_y: 1.0,
+                    },
+                    size: 1.0,
+                }],
+            },
+            event_time: 1.5,
+            sequence: 0,
+            tick: 0,
+        };
+        assert_eq!("1.5: state snapshot of 1 snakes", format!("{}", event));
+
+        let event = Event {
+            kind: EventKind::FoodRemoved {
+                food_id: 7,
+                reason: FoodRemovalReason::Consumed,
+            },
+            event_time: 2.0,
+            sequence: 0,
+            tick: 0,
+        };
+        assert_eq!("2.0: food 7 consumed", format!("{}", event));
+
+        let event = Event {
+            kind: EventKind::FoodRemoved {
+                food_id: 8,
+                reason: FoodRemovalReason::Expired,
+            },
+            event_time: 2.0,
+            sequence: 0,
+            tick: 0,
+        };
+        assert_eq!("2.0: food 8 expired", format!("{}", event));
+
+        let event = Event {
+            kind: EventKind::SnakeGrew {
+                snake_id: 0,
+                size: 1.1,
+            },
+            event_time: 2.0,
+            sequence: 0,
+            tick: 0,
+        };
+        assert_eq!("2.0: snake 0 grew to size 1.1", format!("{}", event));
+
+        let event = Event {
+            kind: EventKind::SnakeCollided {
+                snake_id: 0,
+                other_snake_id: 1,
+                loc: Coordinates { x: 0.5, y: 2.0 },
+            },
+            event_time: 2.5,
+            sequence: 0,
+            tick: 0,
+        };
+        assert_eq!("2.5: snake 0 collided with snake 1 at (0.5,2.0)", format!("{}", event));
+
+        let event = Event {
+            kind: EventKind::SnakeBoosted {
+                snake_id: 0,
+                speed: 2.0,
+            },
+            event_time: 3.0,
+            sequence: 0,
+            tick: 0,
+        };
+        assert_eq!("3.0: snake 0 boosted to speed 2.0", format!("{}", event));
+
+        let event = Event {
+            kind: EventKind::SnakeRenamed {
+                snake_id: 0,
+                name: "Monty".to_string(),
+            },
+            event_time: 3.0,
+            sequence: 0,
+            tick: 0,
+        };
+        assert_eq!("3.0: snake 0 renamed \"Monty\"", format!("{}", event));
+
+        let event = Event {
+            kind: EventKind::ScoreChanged {
+                snake_id: 0,
+                score: 4.0,
+            },
+            event_time: 3.0,
+            sequence: 0,
+            tick: 0,
+        };
+        assert_eq!("3.0: snake 0 score now 4.0", format!("{}", event));
+
+        let event = Event {
+            kind: EventKind::GameStarted,
+            event_time: 0.0,
+            sequence: 0,
+            tick: 0,
+        };
+        assert_eq!("0.0: game started", format!("{}", event));
+
+        let event = Event {
+            kind: EventKind::GameEnded { winner: Some(2) },
+            event_time: 10.0,
+            sequence: 0,
+            tick: 0,
+        };
+        assert_eq!("10.0: game ended, won by team 2", format!("{}", event));
+
+        let event = Event {
+            kind: EventKind::GameEnded { winner: None },
+            event_time: 10.0,
+            sequence: 0,
+            tick: 0,
+        };
+        assert_eq!("10.0: game ended with no winner", format!("{}", event));
+
+        let event = Event {
+            kind: EventKind::SnakeRemoved { snake_id: 4 },
+            event_time: 10.5,
+            sequence: 0,
+            tick: 0,
+        };
+        assert_eq!("10.5: snake 4 removed", format!("{}", event));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn t_event_serde() {
+        // every kind, with the JSON it must keep producing
+        let events = vec![
+            (
+                Event {
+                    kind: EventKind::SnakeBorn {
+                        snake_id: 1,
+                        loc: Coordinates { x: 0.5, y: 0.0 },
+                        vel: Velocity {
+                            delta_x: 0.0,
+                            delta_y: 1.0,
+                        },
+                        size: 1.0,
+                        team: Some(2),
+                    },
+                    event_time: 0.5,
+                    sequence: 0,
+                    tick: 0,
+                },
+                r#"{"type":"snake_born","snake_id":1,"loc":{"x":0.5,"y":0.0},"vel":{"delta_x":0.0,"delta_y":1.0},"size":1.0,"team":2,"event_time":0.5,"sequence":0,"tick":0}"#,
+            ),
+            (
+                Event {
+                    kind: EventKind::SnakeBorn {
+                        snake_id: 1,
+                        loc: Coordinates { x: 0.0, y: 0.0 },
+                        vel: Velocity {
+                            delta_x: 0.0,
+                            delta_y: 1.0,
+                        },
+                        size: 1.0,
+                        team: None,
+                    },
+                    event_time: 0.0,
+                    sequence: 0,
+                    tick: 0,
+                },
+                r#"{"type":"snake_born","snake_id":1,"loc":{"x":0.0,"y":0.0},"vel":{"delta_x":0.0,"delta_y":1.0},"size":1.0,"team":null,"event_time":0.0,"sequence":0,"tick":0}"#,
+            ),
+            (
+                Event {
+                    kind: EventKind::SnakeKilled { snake_id: 3 },
+                    event_time: 2.0,
+                    sequence: 0,
+                    tick: 0,
+                },
+                r#"{"type":"snake_killed","snake_id":3,"event_time":2.0,"sequence":0,"tick":0}"#,
+            ),
+            (
+                Event {
+                    kind: EventKind::SnakeTurned {
+                        snake_id: 0,
+                        rad_relative: -0.25,
+                    },
+                    event_time: 1.0,
+                    sequence: 0,
+                    tick: 0,
+                },
+                r#"{"type":"snake_turned","snake_id":0,"rad_relative":-0.25,"event_time":1.0,"sequence":0,"tick":0}"#,
+            ),
+            (
+                Event {
+                    kind: EventKind::SnakeAte {
+                        snake_id: 0,
+                        food_id: 7,
+                    },
+                    event_time: 1.5,
+                    sequence: 0,
+                    tick: 0,
+                },
+                r#"{"type":"snake_ate","snake_id":0,"food_id":7,"event_time":1.5,"sequence":0,"tick":0}"#,
+            ),
+            (
+                Event {
+                    kind: EventKind::FoodAdded {
+                        food_id: 7,
+                        loc: Coordinates { x: -10.0, y: 10.0 },
+                        nutrition: 1.0,
+                    },
+                    event_time: 0.0,
+                    sequence: 0,
+                    tick: 0,
+                },
+                r#"{"type":"food_added","food_id":7,"loc":{"x":-10.0,"y":10.0},"nutrition":1.0,"event_time":0.0,"sequence":0,"tick":0}"#,
+            ),
+            (
+                Event {
+                    kind: EventKind::ClockAdvanced { delta_t: 0.25 },
+                    event_time: 2.25,
+                    sequence: 0,
+                    tick: 0,
+                },
+                r#"{"type":"clock_advanced","delta_t":0.25,"event_time":2.25,"sequence":0,"tick":0}"#,
+            ),
+            (
+                Event {
+                    kind: EventKind::StateSnapshot {
+                        snakes: vec![SnakeState {
+                            snake_id: 0,
+                            loc: Coordinates { x: 0.0, y: 2.25 },
+                            vel: Velocity {
+                                delta_x: 0.0,
+                                delta_y: 1.0,
+                            },
+                            size: 1.5,
+                        }],
+                    },
+                    event_time: 2.25,
+                    sequence: 0,
+                    tick: 0,
+                },
+                r#"{"type":"state_snapshot","snakes":[{"snake_id":0,"loc":{"x":0.0,"y":2.25},"vel":{"delta_x":0.0,"delta_y":1.0},"size":1.5}],"event_time":2.25,"sequence":0,"tick":0}"#,
+            ),
+            (
+                Event {
+                    kind: EventKind::FoodRemoved {
+                        food_id: 7,
+                        reason: FoodRemovalReason::Consumed,
+                    },
+                    event_time: 1.5,
+                    sequence: 0,
+                    tick: 0,
+                },
+                r#"{"type":"food_removed","food_id":7,"reason":"consumed","event_time":1.5,"sequence":0,"tick":0}"#,
+            ),
+            (
+                Event {
+                    kind: EventKind::FoodRemoved {
+                        food_id: 8,
+                        reason: FoodRemovalReason::Expired,
+                    },
+                    event_time: 1.5,
+                    sequence: 0,
+                    tick: 0,
+                },
+                r#"{"type":"food_removed","food_id":8,"reason":"expired","event_time":1.5,"sequence":0,"tick":0}"#,
+            ),
+            (
+                Event {
+                    kind: EventKind::SnakeGrew {
+                        snake_id: 0,
+                        size: 1.25,
+                    },
+                    event_time: 1.5,
+                    sequence: 0,
+                    tick: 0,
+                },
+                r#"{"type":"snake_grew","snake_id":0,"size":1.25,"event_time":1.5,"sequence":0,"tick":0}"#,
+            ),
+            (
+                Event {
+                    kind: EventKind::SnakeCollided {
+                        snake_id: 0,
+                        other_snake_id: 1,
+                        loc: Coordinates { x: 0.5, y: 2.0 },
+                    },
+                    event_time: 2.5,
+                    sequence: 0,
+                    tick: 0,
+                },
+                r#"{"type":"snake_collided","snake_id":0,"other_snake_id":1,"loc":{"x":0.5,"y":2.0},"event_time":2.5,"sequence":0,"tick":0}"#,
+            ),
+            (
+                Event {
+                    kind: EventKind::SnakeBoosted {
+                        snake_id: 0,
+                        speed: 2.0,
+                    },
+                    event_time: 3.0,
+                    sequence: 0,
+                    tick: 0,
+                },
+                r#"{"type":"snake_boosted","snake_id":0,"speed":2.0,"event_time":3.0,"sequence":0,"tick":0}"#,
+            ),
+            (
+                Event {
+                    kind: EventKind::SnakeRenamed {
+                        snake_id: 0,
+                        name: "Monty".to_string(),
+                    },
+                    event_time: 3.0,
+                    sequence: 0,
+                    tick: 0,
+                },
+                r#"{"type":"snake_renamed","snake_id":0,"name":"Monty","event_time":3.0,"sequence":0,"tick":0}"#,
+            ),
+            (
+                Event {
+                    kind: EventKind::ScoreChanged {
+                        snake_id: 0,
+                        score: 4.0,
+                    },
+                    event_time: 3.0,
+                    sequence: 0,
+                    tick: 0,
+                },
+                r#"{"type":"score_changed","snake_id":0,"score":4.0,"event_time":3.0,"sequence":0,"tick":0}"#,
+            ),
+            (
+                Event {
+                    kind: EventKind::GameStarted,
+                    event_time: 0.0,
+                    sequence: 0,
+                    tick: 0,
+                },
+                r#"{"type":"game_started","event_time":0.0,"sequence":0,"tick":0}"#,
+            ),
+            (
+                Event {
+                    kind: EventKind::GameEnded { winner: Some(2) },
+                    event_time: 10.0,
+                    sequence: 0,
+                    tick: 0,
+                },
+                r#"{"type":"game_ended","winner":2,"event_time":10.0,"sequence":0,"tick":0}"#,
+            ),
+            (
+                Event {
+                    kind: EventKind::GameEnded { winner: None },
+                    event_time: 10.0,
+                    sequence: 0,
+                    tick: 0,
+                },
+                r#"{"type":"game_ended","winner":null,"event_time":10.0,"sequence":0,"tick":0}"#,
+            ),
+            (
+                Event {
+                    kind: EventKind::SnakeRemoved { snake_id: 4 },
+                    event_time: 10.5,
+                    sequence: 0,
+                    tick: 0,
+                },
+                r#"{"type":"snake_removed","snake_id":4,"event_time":10.5,"sequence":0,"tick":0}"#,
+            ),
+        ];
+        for (event, expected_json) in events {
+            let json = serde_json::to_string(&event).unwrap();
+            assert_eq!(expected_json, json);
+            let round_trip: Event = serde_json::from_str(&json).unwrap();
+            assert_eq!(event, round_trip);
+        }
+
+        // recordings from before events were numbered still read
+        let unnumbered: Event = serde_json::from_str(r#"{"type":"snake_killed","snake_id":3,"event_time":2.0}"#).unwrap();
+        assert_eq!(0, unnumbered.sequence);
+        assert_eq!(0, unnumbered.tick);
+
+        // unknown kinds are refused rather than guessed at
+        assert!(serde_json::from_str::<Event>(r#"{"type":"snake_danced","snake_id":0,"event_time":0.0,"sequence":0,"tick":0}"#).is_err());
     }
 }
@@ -1,18 +1,39 @@
 pub mod food {
     use std::fmt;
     use crate::game::common::Coordinates;
+    use crate::snapshot::snapshot::FoodSnapshot;
 
     #[derive(Copy, Clone)]
     pub struct Food {
         location: Coordinates,
         nutrition: f64,
+        // game time the food was put down, for food that expires
+        added: f64,
     }
 
     impl Food {
-        pub fn new(x_placement:f64, y_placement:f64) -> Food {
+        pub fn new(x_placement:f64, y_placement:f64, event_time: f64) -> Food {
             Food {
                 location: Coordinates{x: x_placement, y: y_placement},
                 nutrition: 1.0,
+                added: event_time,
+            }
+        }
+
+        pub fn from_snapshot(saved: &FoodSnapshot) -> Food {
+            Food {
+                location: saved.location,
+                nutrition: saved.nutrition,
+                added: saved.added,
+            }
+        }
+
+        pub fn snapshot(&self, food_id: usize) -> FoodSnapshot {
+            FoodSnapshot {
+                food_id,
+                location: self.location,
+                nutrition: self.nutrition,
+                added: self.added,
             }
         }
 
@@ -23,6 +44,10 @@ pub mod food {
         pub fn get_nutrition(&self) -> f64 {
             self.nutrition
         }
+
+        pub fn get_added(&self) -> f64 {
+            self.added
+        }
     }
 
     impl fmt::Display for Food {
@@ -37,7 +62,7 @@ pub mod food {
 
     #[test]
     fn t_new() {
-        let test_food = Food::new(0.0,0.0);
+        let test_food = Food::new(0.0,0.0,2.5);
 
         let loc = test_food.get_location();
         assert_eq!(0.0,loc.x);
@@ -46,11 +71,14 @@ pub mod food {
         // new food starts out with nutrition of 1
         let nut = test_food.get_nutrition();
         assert_eq!(1.0,nut);
+
+        // and remembers when it was put down
+        assert_eq!(2.5,test_food.get_added());
     }
 
     #[test]
     fn t_food_fmt() {
-        let test_food = Food::new(0.0,0.0);
+        let test_food = Food::new(0.0,0.0,0.0);
         assert_eq!("food at (0.0, 0.0) with nutrition of 1.0", format!("{}",test_food));
     }
 
@@ -1,7 +1,11 @@
 pub mod common {
     use std::fmt;
+    #[cfg(feature = "serde")]
+    use serde::{Deserialize, Serialize};
 
+    // Serializes as {"x":0.0,"y":0.0}
     #[derive(Copy, Clone, PartialEq, Debug)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
     pub struct Coordinates {pub x:f64, pub y:f64}
     impl fmt::Display for Coordinates {
         fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -19,7 +23,9 @@ pub mod common {
         assert_eq!("(0.0,0.0)", format!("{}",test_coordinates));
     }
 
+    // Serializes as {"delta_x":0.0,"delta_y":1.0}
     #[derive(Copy, Clone, PartialEq, Debug)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
     pub struct Velocity {pub delta_x:f64, pub delta_y:f64}
     impl fmt::Display for Velocity {
         fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -37,23 +43,130 @@ pub mod common {
         assert_eq!("<0.0,0.0>", format!("{}",test_velocity));
     }
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn t_common_serde() {
+        let test_coordinates = Coordinates{x: 1.5, y:-2.0};
+        let json = serde_json::to_string(&test_coordinates).unwrap();
+        assert_eq!(r#"{"x":1.5,"y":-2.0}"#, json);
+        assert_eq!(test_coordinates, serde_json::from_str(&json).unwrap());
+
+        let test_velocity = Velocity{delta_x: 0.0, delta_y:1.0};
+        let json = serde_json::to_string(&test_velocity).unwrap();
+        assert_eq!(r#"{"delta_x":0.0,"delta_y":1.0}"#, json);
+        assert_eq!(test_velocity, serde_json::from_str(&json).unwrap());
+    }
+
 }
 
 pub mod game {
     use crate::snake::snake::Snake;
     use crate::snake::snake::SweepArea;
     use crate::food::food::Food;
-    use crate::eventqueue::eventqueue::EventQueue;
-    //use crate::game::common::{Coordinates,Velocity};
-    use std::collections::HashMap;
+    use crate::eventqueue::eventqueue::{Event,EventKind,EventQueue,FoodRemovalReason,LogError,SnakeState};
+    use crate::team::team::{TeamRules,WinCondition};
+    use crate::rules::rules::{GameMode,Rules};
+    use crate::snapshot::snapshot::{GameSnapshot,SnakeName,SnapshotError,TeamScore,SNAPSHOT_VERSION};
+    use crate::game::common::{Coordinates,Velocity};
+    use std::collections::{BTreeMap,BTreeSet};
+
+    #[cfg(feature = "serde")]
+    use serde::{Deserialize, Serialize};
+
+    // how far a new snake is born from any other snake's body
+    pub const SPAWN_CLEARANCE: f64 = 10.0;
+    // how many spots are tried for it before taking the roomiest
+    const SPAWN_TRIES: usize = 20;
+
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct GameConfig {
+        pub size: f64,
+        pub mode: GameMode,
+        pub team_rules: TeamRules,
+        // game seconds between StateSnapshot events, or None for no snapshots
+        pub snapshot_interval: Option<f64>,
+        // game seconds food lies uneaten before it's taken away, or None to keep it
+        pub food_lifetime: Option<f64>,
+    }
+    impl Default for GameConfig {
+        fn default() -> Self {
+            GameConfig {
+                size: 100.0,
+                mode: GameMode::Continuous,
+                team_rules: TeamRules::default(),
+                snapshot_interval: None,
+                food_lifetime: None,
+            }
+        }
+    }
+    impl GameConfig {
+        // Whether a game could be played by this config. Sizes and times have
+        // to be real numbers, and above nothing where the game would divide by
+        // them or pick spots within them.
+        pub fn check(&self) -> Result<(), BadConfig> {
+            fn positive(value: f64) -> bool {
+                value.is_finite() && value > 0.0
+            }
+            if !positive(self.size) {
+                return Err(BadConfig("size must be above 0"));
+            }
+            if let GameMode::Grid { tick_length } = self.mode {
+                if !positive(tick_length) {
+                    return Err(BadConfig("a grid's tick_length must be above 0"));
+                }
+            }
+            if let WinCondition::ScoreTarget(target) = self.team_rules.win_condition {
+                if !target.is_finite() {
+                    return Err(BadConfig("a score target must be a number"));
+                }
+            }
+            if let Some(interval) = self.snapshot_interval {
+                if !positive(interval) {
+                    return Err(BadConfig("snapshot_interval must be above 0"));
+                }
+            }
+            if let Some(lifetime) = self.food_lifetime {
+                if !positive(lifetime) {
+                    return Err(BadConfig("food_lifetime must be above 0"));
+                }
+            }
+            Ok(())
+        }
+    }
+
+    // What's wrong with a game config
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    pub struct BadConfig(pub &'static str);
+    impl std::error::Error for BadConfig {}
+    impl fmt::Display for BadConfig {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "Bad game config, {}!", self.0)
+        }
+    }
 
     pub struct Game {
-        size: f64,
+        config: GameConfig,
+        rules: Box<dyn Rules>,
+        // where the starting food went, so the game can be replayed
+        seed: u64,
         time: f64,
-        snake: HashMap<usize,Snake>,
+        // how many times the clock has advanced
+        tick: u64,
+        // when the last StateSnapshot event went out
+        last_state_snapshot: f64,
+        // ordered maps, so that every run of the same inputs plays out the same way
+        snake: BTreeMap<usize,Snake>,
         next_snake_id: usize,
-        food: HashMap<usize,Food>,
+        food: BTreeMap<usize,Food>,
         next_food_id: usize,
+        // running score of each team, including members that have since been killed
+        team_score: BTreeMap<usize,f64>,
+        // what the players have called their snakes, for those that have been renamed
+        names: BTreeMap<usize,String>,
+        // Inputs the queue won't take, e.g. too late for its reorder window or
+        // with the backlog full, are refused with GameError::Log and not played,
+        // so the log always has everything needed to replay the game.
         eventqueue: EventQueue,
     }
 
@@ -71,16 +184,60 @@ pub mod game {
         }
     }
 
+    // Why an input to the game was refused
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    pub enum GameError {
+        NotFound,
+        // the event queue wouldn't log it, so it wasn't played
+        Log(LogError),
+    }
+    impl std::error::Error for GameError {}
+    impl fmt::Display for GameError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                GameError::NotFound => write!(f, "{}", NotFound),
+                GameError::Log(log_error) => write!(f, "{}", log_error),
+            }
+        }
+    }
+    impl From<NotFound> for GameError {
+        fn from(_: NotFound) -> Self {
+            GameError::NotFound
+        }
+    }
+    impl From<LogError> for GameError {
+        fn from(log_error: LogError) -> Self {
+            GameError::Log(log_error)
+        }
+    }
+
+    impl Default for Game {
+        fn default() -> Self {
+            Game::new()
+        }
+    }
+
     impl Game {
         pub fn new() -> Game {
+            Game::new_with_config(GameConfig::default())
+        }
+
+        pub fn new_with_config(config: GameConfig) -> Game {
             use rand::Rng;
-            let mut rng = rand::thread_rng();
-            let mut new_game = Game::new_blank_game();
+            Game::new_seeded(config, rand::thread_rng().gen())
+        }
+
+        pub fn new_seeded(config: GameConfig, seed: u64) -> Game {
+            use rand::{Rng, SeedableRng};
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            let mut new_game = Game::new_blank_game_with_config(config);
+            new_game.seed = seed;
+            let size = new_game.get_size();
             
             for _i in 1..10 {
-                let x = rng.gen_range(new_game.size / -2.0 , new_game.size / 2.0);
-                let y = rng.gen_range(new_game.size / -2.0 , new_game.size / 2.0);
-                new_game.add_food(x, y, 0.0);
+                let x = rng.gen_range(size / -2.0 , size / 2.0);
+                let y = rng.gen_range(size / -2.0 , size / 2.0);
+                new_game.add_food(x, y, 0.0).expect("A new game's queue takes its starting food");
             }
             new_game
         }
@@ -88,70 +245,266 @@ pub mod game {
         fn _new_test_game() -> Game {
             let mut new_game = Game::new_blank_game();
             // lay out the food in a predicatible pattern
-            new_game.add_food(-50.0, 50.0, 0.0);
-            new_game.add_food(-40.0, 40.0, 0.0);
-            new_game.add_food(-30.0, 30.0, 0.0);
-            new_game.add_food(-20.0, 20.0, 0.0);
-            new_game.add_food(-10.0, 10.0, 0.0);
-            new_game.add_food( 10.0,-10.0, 0.0);
-            new_game.add_food( 20.0,-20.0, 0.0);
-            new_game.add_food( 30.0,-30.0, 0.0);
-            new_game.add_food( 40.0,-40.0, 0.0);
-            new_game.add_food( 50.0,-50.0, 0.0);
+            new_game.add_food(-50.0, 50.0, 0.0).unwrap();
+            new_game.add_food(-40.0, 40.0, 0.0).unwrap();
+            new_game.add_food(-30.0, 30.0, 0.0).unwrap();
+            new_game.add_food(-20.0, 20.0, 0.0).unwrap();
+            new_game.add_food(-10.0, 10.0, 0.0).unwrap();
+            new_game.add_food( 10.0,-10.0, 0.0).unwrap();
+            new_game.add_food( 20.0,-20.0, 0.0).unwrap();
+            new_game.add_food( 30.0,-30.0, 0.0).unwrap();
+            new_game.add_food( 40.0,-40.0, 0.0).unwrap();
+            new_game.add_food( 50.0,-50.0, 0.0).unwrap();
             new_game
         }
 
-        fn new_blank_game() -> Game {
+        pub fn new_blank_game() -> Game {
+            Game::new_blank_game_with_config(GameConfig::default())
+        }
+
+        pub fn new_blank_game_with_config(config: GameConfig) -> Game {
             Game {
-                size: 100.0,
+                config,
+                rules: config.mode.rules(),
+                seed: 0,
                 time: 0.0,
-                snake: HashMap::new(),
+                tick: 0,
+                last_state_snapshot: 0.0,
+                snake: BTreeMap::new(),
                 next_snake_id: 0,
-                food: HashMap::new(),
+                food: BTreeMap::new(),
                 next_food_id: 0,
+                team_score: BTreeMap::new(),
+                names: BTreeMap::new(),
                 eventqueue: EventQueue::new(),
             }
         }
 
+        pub fn snapshot(&self) -> GameSnapshot {
+            GameSnapshot {
+                version: SNAPSHOT_VERSION,
+                config: self.config,
+                seed: self.seed,
+                time: self.time,
+                tick: self.tick,
+                next_sequence: self.eventqueue.get_next_sequence(),
+                last_state_snapshot: self.last_state_snapshot,
+                next_snake_id: self.next_snake_id,
+                next_food_id: self.next_food_id,
+                snakes: self.snake.iter().map(|(snake_id, this_snake)| this_snake.snapshot(*snake_id)).collect(),
+                food: self.food.iter().map(|(food_id, this_food)| this_food.snapshot(*food_id)).collect(),
+                team_scores: self.team_score.iter().map(|(team_id, score)| TeamScore { team_id: *team_id, score: *score }).collect(),
+                names: self.names.iter().map(|(snake_id, name)| SnakeName { snake_id: *snake_id, name: name.clone() }).collect(),
+            }
+        }
+
+        // Rebuild a game from a snapshot. It plays on exactly as the game the
+        // snapshot was taken from would have, but with an empty event queue
+        // that carries on the sequence numbers.
+        pub fn restore(saved: &GameSnapshot) -> Result<Game, SnapshotError> {
+            saved.check()?;
+            let mut restored = Game::new_blank_game_with_config(saved.config);
+            restored.seed = saved.seed;
+            restored.time = saved.time;
+            restored.tick = saved.tick;
+            restored.eventqueue = EventQueue::new_at(saved.next_sequence, saved.tick);
+            restored.last_state_snapshot = saved.last_state_snapshot;
+            restored.next_snake_id = saved.next_snake_id;
+            restored.next_food_id = saved.next_food_id;
+            for saved_snake in saved.snakes.iter() {
+                restored.snake.insert(saved_snake.snake_id, Snake::from_snapshot(saved_snake));
+            }
+            for saved_food in saved.food.iter() {
+                restored.food.insert(saved_food.food_id, Food::from_snapshot(saved_food));
+            }
+            for team in saved.team_scores.iter() {
+                restored.team_score.insert(team.team_id, team.score);
+            }
+            for saved_name in saved.names.iter() {
+                restored.names.insert(saved_name.snake_id, saved_name.name.clone());
+            }
+            Ok(restored)
+        }
+
         pub fn advance_clock(&mut self, delta_t: f64) {
             // Ignore attempts to turn back time
             if delta_t < 0.0 {return};
+            let decided = self.get_winning_team().is_some();
 
             self.time += delta_t;
-            let mut all_snake_coverage:HashMap<usize,SweepArea> = HashMap::new(); 
+            self.tick += 1;
+            self.eventqueue.set_tick(self.tick);
+            self.log_outcome(EventKind::ClockAdvanced { delta_t });
+            let mut all_snake_coverage:BTreeMap<usize,SweepArea> = BTreeMap::new(); 
             {
                 for (snake_id,this_snake) in self.snake.iter_mut() {
-                    all_snake_coverage.insert(*snake_id,this_snake.update(self.time));
+                    all_snake_coverage.insert(*snake_id,self.rules.move_snake(this_snake, self.time));
                 }
             }
-            for (snake_id,coverage) in all_snake_coverage { 
+            for (snake_id,coverage) in all_snake_coverage.iter() { 
                 let mut food_idxs_to_eat:Vec<usize> = Vec::new();
                 for (food_id, this_food) in self.food.iter() {
-                    // simple algorithm - if food location is inside rectangle enclosing coverage area, it's a hit
-                    let foodloc = this_food.get_location();
-                    if foodloc.x > coverage.start_loc.x.min(coverage.end_loc.x) - coverage.width / 2.0 &&
-                        foodloc.x < coverage.start_loc.x.max(coverage.end_loc.x) + coverage.width / 2.0 &&
-                        foodloc.y > coverage.start_loc.y.min(coverage.end_loc.y) - coverage.width / 2.0 &&
-                        foodloc.y < coverage.start_loc.y.max(coverage.end_loc.y) +  coverage.width / 2.0 {
+                    if coverage.covers(this_food.get_location(), 0.0) {
                             food_idxs_to_eat.push(*food_id);
                     }
                 }
-                self.snake_eats_food(snake_id,food_idxs_to_eat,self.time);
+                self.snake_eats_food(*snake_id,food_idxs_to_eat);
             }
-            
+            self.expire_food();
+            self.check_collisions(&all_snake_coverage);
+            // the tick that decides the game ends it
+            if !decided {
+                if let Some(team_id) = self.get_winning_team() {
+                    self.log_outcome(EventKind::GameEnded { winner: Some(team_id) });
+                }
+            }
+            self.log_state_snapshot_if_due();
         }
 
-        fn snake_eats_food(&mut self, snake_id:usize, food_ids:Vec<usize>, event_time:f64) {
+        // Tell everyone the game is under way, e.g. once the players have joined
+        pub fn start(&mut self) {
+            self.log_outcome(EventKind::GameStarted);
+        }
+
+        // Tell everyone the game is over, e.g. when it's stopped from outside
+        pub fn end(&mut self) {
+            let winner = self.get_winning_team();
+            self.log_outcome(EventKind::GameEnded { winner });
+        }
+
+        // The clock, and what the game works out for itself from its inputs,
+        // can't be turned away, however full the queue - see
+        // EventQueue::log_outcome()
+        fn log_outcome(&mut self, kind: EventKind) {
+            self.eventqueue.log_outcome(Event::new(kind, self.time));
+        }
+
+        fn log_state_snapshot_if_due(&mut self) {
+            // the nudge keeps float error in the clock from putting a snapshot off a tick
+            let due = match self.config.snapshot_interval {
+                Some(interval) => self.time - self.last_state_snapshot + 1e-9 >= interval,
+                None => false,
+            };
+            // a queue that folded its backlog away wants one to stand in for it
+            let requested = self.eventqueue.take_snapshot_request();
+            if !due && !requested {
+                return;
+            }
+            self.last_state_snapshot = self.time;
+            let snakes = self.snake.iter()
+                .map(|(snake_id, this_snake)| SnakeState {
+                    snake_id: *snake_id,
+                    loc: this_snake.get_location(),
+                    vel: this_snake.get_velocity(),
+                    size: this_snake.get_size(),
+                })
+                .collect();
+            self.log_outcome(EventKind::StateSnapshot { snakes });
+        }
+
+        // Take away food that has lain uneaten for the config's food_lifetime.
+        // Food that a snake reached this tick has already been eaten.
+        fn expire_food(&mut self) {
+            let lifetime = match self.config.food_lifetime {
+                Some(lifetime) => lifetime,
+                None => return,
+            };
+            // the same nudge as for state snapshots
+            let expired:Vec<usize> = self.food.iter()
+                .filter(|(_, this_food)| self.time - this_food.get_added() + 1e-9 >= lifetime)
+                .map(|(food_id, _)| *food_id)
+                .collect();
+            for food_id in expired {
+                self.food.remove(&food_id);
+                self.log_outcome(EventKind::FoodRemoved { food_id, reason: FoodRemovalReason::Expired });
+            }
+        }
+
+        fn check_collisions(&mut self, all_snake_coverage: &BTreeMap<usize,SweepArea>) {
+            let mut snake_ids:Vec<usize> = self.snake.keys().copied().collect();
+            snake_ids.sort_unstable();
+            let mut snakes_to_kill:BTreeSet<usize> = BTreeSet::new();
+            let mut collisions:Vec<EventKind> = Vec::new();
+            for (idx, snake1_id) in snake_ids.iter().enumerate() {
+                for snake2_id in snake_ids[idx + 1..].iter() {
+                    let snake1 = &self.snake[snake1_id];
+                    let snake2 = &self.snake[snake2_id];
+                    if !self.config.team_rules.can_collide(snake1.get_team(), snake2.get_team()) {
+                        continue;
+                    }
+                    // a hit if either snake swept over the other's head
+                    let impact = if all_snake_coverage[snake1_id].covers(snake2.get_location(), self.rules.body_radius(snake2)) {
+                        Some(snake2.get_location())
+                    } else if all_snake_coverage[snake2_id].covers(snake1.get_location(), self.rules.body_radius(snake1)) {
+                        Some(snake1.get_location())
+                    } else {
+                        None
+                    };
+                    if let Some(loc) = impact {
+                        collisions.push(EventKind::SnakeCollided { snake_id: *snake1_id, other_snake_id: *snake2_id, loc });
+                        // the bigger snake survives, evenly matched snakes both die
+                        if snake1.get_size() <= snake2.get_size() {
+                            snakes_to_kill.insert(*snake1_id);
+                        }
+                        if snake2.get_size() <= snake1.get_size() {
+                            snakes_to_kill.insert(*snake2_id);
+                        }
+                    }
+                }
+            }
+            for collision in collisions {
+                self.log_outcome(collision);
+            }
+            for snake_id in snakes_to_kill {
+                if self.snake.remove(&snake_id).is_some() {
+                    self.names.remove(&snake_id);
+                    self.log_outcome(EventKind::SnakeKilled { snake_id });
+                }
+            }
+        }
+
+        // When an input is played, and logged. With a reorder window the queue
+        // sorts the log by time, but the game has already played up to now, so
+        // an input from earlier in the window - or from ahead of the clock - is
+        // played now, where a replay of the log will come to it too.
+        fn input_time(&self, event_time: f64) -> f64 {
+            match self.eventqueue.get_reorder_window() {
+                Some(_) => self.time,
+                None => event_time,
+            }
+        }
+
+        // Take a snake out of the game, e.g. when its player leaves. That's never
+        // turned away, as whoever asked has already let the snake go.
+        pub fn remove_snake(&mut self, snake_id:usize, event_time:f64) -> Result<(), NotFound> {
+            if self.snake.remove(&snake_id).is_some() {
+                self.names.remove(&snake_id);
+                let event_time = self.input_time(event_time);
+                self.eventqueue.log_outcome(Event::new(EventKind::SnakeRemoved { snake_id }, event_time));
+                Ok(())
+            } else {
+                Err(NotFound)
+            }
+        }
+
+        fn snake_eats_food(&mut self, snake_id:usize, food_ids:Vec<usize>) {
             for food_to_eat_id in food_ids {
                 let food_to_eat = self.food.remove(&food_to_eat_id).unwrap();
                 self.feed_snake(snake_id, food_to_eat.get_nutrition()).expect("Invalid snake ID");
-                self.eventqueue.log_snake_ate(event_time, snake_id, food_to_eat_id);
+                self.log_outcome(EventKind::SnakeAte { snake_id, food_id: food_to_eat_id });
+                self.log_outcome(EventKind::FoodRemoved { food_id: food_to_eat_id, reason: FoodRemovalReason::Consumed });
+                let fed_snake = self.snake[&snake_id];
+                self.log_outcome(EventKind::SnakeGrew { snake_id, size: fed_snake.get_size() });
+                self.log_outcome(EventKind::ScoreChanged { snake_id, score: fed_snake.get_score() });
             }
         }
 
         fn feed_snake(&mut self, snake_id:usize, nutrition:f64) -> Result<(), NotFound> {
             if let Some(the_snake) = self.snake.get_mut(&snake_id) {
-                let _coverage = the_snake.eat(nutrition);
+                self.rules.feed_snake(the_snake, nutrition);
+                if let Some(team_id) = the_snake.get_team() {
+                    *self.team_score.entry(team_id).or_insert(0.0) += nutrition;
+                }
                 Ok(())
             } else {
                 Err(NotFound)
@@ -160,20 +513,119 @@ pub mod game {
         }
 
         pub fn get_size(&self) -> f64 {
-            self.size
+            self.config.size
+        }
+
+        pub fn get_config(&self) -> GameConfig {
+            self.config
+        }
+
+        pub fn get_seed(&self) -> u64 {
+            self.seed
         }
 
         pub fn get_time(&self) -> f64 {
             self.time
         }
 
-        pub fn create_snake(&mut self,event_time: f64) -> usize {
+        pub fn get_tick(&self) -> u64 {
+            self.tick
+        }
+
+        pub fn create_snake(&mut self,event_time: f64) -> Result<usize, LogError> {
+            self.spawn_snake(Snake::new(self.input_time(event_time)), event_time)
+        }
+
+        pub fn create_team_snake(&mut self, team_id:usize, event_time: f64) -> Result<usize, LogError> {
+            let snake_id = self.spawn_snake(Snake::new_on_team(team_id, self.input_time(event_time)), event_time)?;
+            self.team_score.entry(team_id).or_insert(0.0);
+            Ok(snake_id)
+        }
+
+        fn spawn_snake(&mut self, mut new_snake: Snake, event_time: f64) -> Result<usize, LogError> {
             let snake_id = self.next_snake_id;
+            new_snake.set_location(self.spawn_point(snake_id));
+            self.eventqueue.check_input(event_time)?;
+            self.eventqueue.log_snake_born(new_snake.get_last_updated(), snake_id, new_snake)?;
             self.next_snake_id += 1;
-            let new_snake = Snake::new(event_time);
-            self.eventqueue.log_snake_born(event_time, snake_id, new_snake);
             self.snake.insert(snake_id,new_snake);
-            snake_id
+            Ok(snake_id)
+        }
+
+        // Where a new snake goes: the centre if there's room, otherwise a spot
+        // picked from the seed and its id - so a replay puts it in the same
+        // place - at least SPAWN_CLEARANCE from every other snake. In a crowded
+        // game it's the roomiest of the spots tried.
+        fn spawn_point(&self, snake_id:usize) -> Coordinates {
+            use rand::{Rng, SeedableRng};
+            let room = |loc:Coordinates| self.snake.values()
+                .map(|this_snake| {
+                    let there = this_snake.get_location();
+                    (loc.x - there.x).hypot(loc.y - there.y) - self.rules.body_radius(this_snake)
+                })
+                .fold(f64::INFINITY, f64::min);
+            let mut rng = rand::rngs::StdRng::seed_from_u64(self.seed ^ (snake_id as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+            let size = self.get_size();
+            let mut best = Coordinates {x: 0.0, y: 0.0};
+            let mut best_room = room(best);
+            for _i in 0..SPAWN_TRIES {
+                if best_room >= SPAWN_CLEARANCE {
+                    break;
+                }
+                // snakes go on the same cells food does
+                let loc = self.rules.place_food(rng.gen_range(size / -2.0, size / 2.0), rng.gen_range(size / -2.0, size / 2.0));
+                let loc_room = room(loc);
+                if loc_room > best_room {
+                    best = loc;
+                    best_room = loc_room;
+                }
+            }
+            best
+        }
+
+        pub fn get_team_score(&self, team_id:usize) -> Result<f64, NotFound> {
+            if let Some(score) = self.team_score.get(&team_id) {
+                Ok(*score)
+            } else {
+                Err(NotFound)
+            }
+        }
+
+        pub fn get_team_members(&self, team_id:usize) -> Vec<usize> {
+            let mut members:Vec<usize> = self.snake.iter()
+                .filter(|(_, this_snake)| this_snake.get_team() == Some(team_id))
+                .map(|(snake_id, _)| *snake_id)
+                .collect();
+            members.sort_unstable();
+            members
+        }
+
+        pub fn get_winning_team(&self) -> Option<usize> {
+            match self.config.team_rules.win_condition {
+                WinCondition::NoWinner => None,
+                WinCondition::LastTeamStanding => {
+                    // it takes at least two teams to have a contest
+                    if self.team_score.len() < 2 {
+                        return None;
+                    }
+                    let standing:BTreeSet<usize> = self.snake.values().filter_map(|this_snake| this_snake.get_team()).collect();
+                    if standing.len() == 1 {
+                        standing.into_iter().next()
+                    } else {
+                        None
+                    }
+                }
+                WinCondition::ScoreTarget(target) => {
+                    // if more than one team got there in the same tick, the highest score wins
+                    let mut winner:Option<(usize,f64)> = None;
+                    for (team_id, score) in self.team_score.iter() {
+                        if *score >= target && winner.is_none_or(|(_, best)| *score > best) {
+                            winner = Some((*team_id, *score));
+                        }
+                    }
+                    winner.map(|(team_id, _)| team_id)
+                }
+            }
         }
 
         pub fn get_snake(&self, snake_id:usize) -> Result<&Snake, NotFound> {
@@ -184,23 +636,82 @@ pub mod game {
             }
         }
 
-        pub fn turn_snake(&mut self, snake_id:usize,rad_relative: f64, event_time: f64) -> Result<(), NotFound> {
+        pub fn get_snake_ids(&self) -> Vec<usize> {
+            self.snake.keys().copied().collect()
+        }
+
+        // Snakes whose heads are within radius of loc, lowest id first
+        pub fn get_snakes_near(&self, loc:Coordinates, radius:f64) -> Vec<usize> {
+            self.snake.iter()
+                .filter(|(_, this_snake)| within(this_snake.get_location(), loc, radius))
+                .map(|(snake_id, _)| *snake_id)
+                .collect()
+        }
+
+        // Food within radius of loc, lowest id first
+        pub fn get_food_near(&self, loc:Coordinates, radius:f64) -> Vec<usize> {
+            self.food.iter()
+                .filter(|(_, this_food)| within(this_food.get_location(), loc, radius))
+                .map(|(food_id, _)| *food_id)
+                .collect()
+        }
+
+        pub fn turn_snake(&mut self, snake_id:usize,rad_relative: f64, event_time: f64) -> Result<(), GameError> {
+            let played_time = self.input_time(event_time);
+            if let Some(the_snake) = self.snake.get_mut(&snake_id) {
+                // log the turn the snake actually makes, which the rules may have limited
+                let rad_relative = self.rules.effective_turn(rad_relative);
+                self.eventqueue.check_input(event_time)?;
+                self.eventqueue.log_snake_turned(played_time, snake_id, rad_relative)?;
+                let _coverage = self.rules.turn_snake(the_snake, rad_relative, played_time);
+                Ok(())
+            } else {
+                Err(GameError::NotFound)
+            }
+        }
+
+        pub fn boost_snake(&mut self, snake_id:usize, speed: f64, event_time: f64) -> Result<(), GameError> {
+            let played_time = self.input_time(event_time);
             if let Some(the_snake) = self.snake.get_mut(&snake_id) {
-                self.eventqueue.log_snake_turned(event_time, snake_id, rad_relative);
-                let _coverage = the_snake.turn(rad_relative, event_time);
+                // log the speed the snake actually goes, which the rules may have rounded
+                let speed = self.rules.effective_speed(speed);
+                self.eventqueue.check_input(event_time)?;
+                self.eventqueue.log_snake_boosted(played_time, snake_id, speed)?;
+                let _coverage = self.rules.boost_snake(the_snake, speed, played_time);
                 Ok(())
+            } else {
+                Err(GameError::NotFound)
+            }
+        }
+
+        pub fn rename_snake(&mut self, snake_id:usize, name: &str, event_time: f64) -> Result<(), GameError> {
+            if !self.snake.contains_key(&snake_id) {
+                return Err(GameError::NotFound);
+            }
+            self.eventqueue.check_input(event_time)?;
+            self.eventqueue.log_snake_renamed(self.input_time(event_time), snake_id, name)?;
+            self.names.insert(snake_id, name.to_string());
+            Ok(())
+        }
+
+        // What the snake's player called it, if they've named it
+        pub fn get_snake_name(&self, snake_id:usize) -> Result<Option<&str>, NotFound> {
+            if self.snake.contains_key(&snake_id) {
+                Ok(self.names.get(&snake_id).map(|name| name.as_str()))
             } else {
                 Err(NotFound)
             }
         }
 
-        pub fn add_food(&mut self, x:f64, y:f64, event_time: f64) -> usize {
+        pub fn add_food(&mut self, x:f64, y:f64, event_time: f64) -> Result<usize, LogError> {
             let food_id = self.next_food_id;
+            let loc = self.rules.place_food(x, y);
+            let new_food = Food::new(loc.x,loc.y,self.input_time(event_time));
+            self.eventqueue.check_input(event_time)?;
+            self.eventqueue.log_food_added(new_food.get_added(), food_id, new_food)?;
             self.next_food_id += 1;
-            let new_food = Food::new(x,y);
-            self.eventqueue.log_food_added(event_time, food_id, new_food);
             self.food.insert(food_id,new_food);
-            food_id
+            Ok(food_id)
         }
 
         pub fn _get_food(&self, food_id: usize) -> Result<&Food, NotFound> {
@@ -215,12 +726,32 @@ pub mod game {
             self.food.len()
         }
 
+        // Pick a snake up and set it down somewhere else, going some other
+        // way, to set up a test
+        pub fn _place_snake(&mut self, snake_id: usize, location: Coordinates, velocity: Velocity) -> Result<(), NotFound> {
+            if let Some(the_snake) = self.snake.get_mut(&snake_id) {
+                let mut saved = the_snake.snapshot(snake_id);
+                saved.location = location;
+                saved.velocity = velocity;
+                *the_snake = Snake::from_snapshot(&saved);
+                Ok(())
+            } else {
+                Err(NotFound)
+            }
+        }
+
         pub fn get_event_queue_mut(&mut self) -> &mut EventQueue {
             &mut self.eventqueue
         }
 
     }
 
+    // Whether loc is no further than radius from centre
+    pub fn within(loc:Coordinates, centre:Coordinates, radius:f64) -> bool {
+        let (delta_x, delta_y) = (loc.x - centre.x, loc.y - centre.y);
+        delta_x * delta_x + delta_y * delta_y <= radius * radius
+    }
+
     #[test]
     fn t_new() {
         // new games start out at zero game time and default size of 100
@@ -291,7 +822,7 @@ pub mod game {
         // SHould really test for the error type, but don't know how.
         //assert!(result.map_err(|e| e.kind()));
 
-        let snake_id = test_game.create_snake(0.0);
+        let snake_id = test_game.create_snake(0.0).unwrap();
         assert!(test_game.get_snake(snake_id).is_ok());
 
         assert!(test_game.get_snake(snake_id + 1).is_err());
@@ -300,7 +831,7 @@ pub mod game {
     #[test]
     fn t_turn_snake() {
         use assert_approx_eq::assert_approx_eq;
-        const PI:f64 = 3.14159;
+        use std::f64::consts::{PI, FRAC_1_SQRT_2};
         let mut test_game = Game::new_blank_game();
 
         // Can't turn a snake that isn't there
@@ -309,7 +840,7 @@ pub mod game {
         // SHould really test for the error type, but don't know how.
         //assert!(result.map_err(|e| e.kind()));
 
-        let snake_id = test_game.create_snake(0.0);
+        let snake_id = test_game.create_snake(0.0).unwrap();
         // new snake should be at velY = 1 
         {
             let test_snake = test_game.get_snake(snake_id).unwrap();
@@ -343,8 +874,8 @@ pub mod game {
         {
             let test_snake = test_game.get_snake(snake_id).unwrap();
             let vel = test_snake.get_velocity();
-            assert_approx_eq!( 0.7071,vel.delta_x,1e-5);
-            assert_approx_eq!(-0.7071,vel.delta_y,1e-5);
+            assert_approx_eq!( FRAC_1_SQRT_2,vel.delta_x,1e-5);
+            assert_approx_eq!(-FRAC_1_SQRT_2,vel.delta_y,1e-5);
         }
 
         // snake should be at pos (.71,-1.71) after two seconds
@@ -352,8 +883,8 @@ pub mod game {
         {
             let test_snake = test_game.get_snake(snake_id).unwrap();
             let loc = test_snake.get_location();
-            assert_approx_eq!( 0.7071,loc.x,1e-5);
-            assert_approx_eq!(-1.7071,loc.y,1e-5);
+            assert_approx_eq!( FRAC_1_SQRT_2,loc.x,1e-5);
+            assert_approx_eq!(-1.0 - FRAC_1_SQRT_2,loc.y,1e-5);
             }
         
     }
@@ -362,8 +893,8 @@ pub mod game {
     fn t_create_snake() {
 
         let mut test_game = Game::new_blank_game();
-        let snake1_id = test_game.create_snake(0.0);
-        let snake2_id = test_game.create_snake(0.0);
+        let snake1_id = test_game.create_snake(0.0).unwrap();
+        let snake2_id = test_game.create_snake(0.0).unwrap();
         assert_ne!(snake1_id, snake2_id);
 
         // make sure the two snakes are independent
@@ -376,13 +907,443 @@ pub mod game {
         assert_ne!(vel.delta_y,vel2.delta_y);
     }
 
+    #[test]
+    fn t_config_check() {
+        assert_eq!(Ok(()), GameConfig::default().check());
+        let config = GameConfig { mode: GameMode::classic_grid(), snapshot_interval: Some(5.0), ..GameConfig::default() };
+        assert_eq!(Ok(()), config.check());
+        for size in [0.0, -10.0, f64::NAN, f64::INFINITY].iter() {
+            assert!(GameConfig { size: *size, ..GameConfig::default() }.check().is_err(), "{}", size);
+        }
+        assert!(GameConfig { mode: GameMode::Grid { tick_length: 0.0 }, ..GameConfig::default() }.check().is_err());
+        assert!(GameConfig { snapshot_interval: Some(f64::NAN), ..GameConfig::default() }.check().is_err());
+        assert_eq!(Err(BadConfig("food_lifetime must be above 0")), GameConfig { food_lifetime: Some(0.0), ..GameConfig::default() }.check());
+        let mut config = GameConfig::default();
+        config.team_rules.win_condition = WinCondition::ScoreTarget(f64::INFINITY);
+        assert_eq!(Err(BadConfig("a score target must be a number")), config.check());
+    }
+
+    #[test]
+    fn t_new_with_config() {
+        let config = GameConfig {size: 50.0, ..GameConfig::default()};
+        let test_game = Game::new_with_config(config);
+        assert_eq!(50.0,test_game.get_size());
+        assert_eq!(config,test_game.get_config());
+
+        // random food is scattered inside the smaller arena
+        for food_id in 0..test_game._get_food_count() {
+            let loc = test_game._get_food(food_id).unwrap().get_location();
+            assert!(loc.x.abs() <= 25.0);
+            assert!(loc.y.abs() <= 25.0);
+        }
+    }
+
+    #[test]
+    fn t_new_seeded() {
+        // the same seed lays out the same food
+        let test_game1 = Game::new_seeded(GameConfig::default(), 32);
+        let test_game2 = Game::new_seeded(GameConfig::default(), 32);
+        assert_eq!(32,test_game1.get_seed());
+        assert_eq!(test_game1._get_food_count(),test_game2._get_food_count());
+        for food_id in 0..test_game1._get_food_count() {
+            assert_eq!(test_game1._get_food(food_id).unwrap().get_location(),test_game2._get_food(food_id).unwrap().get_location());
+        }
+    }
+
+    #[test]
+    fn t_create_team_snake() {
+        let mut test_game = Game::new_blank_game();
+        let snake1_id = test_game.create_team_snake(1, 0.0).unwrap();
+        let snake2_id = test_game.create_team_snake(2, 0.0).unwrap();
+        let snake3_id = test_game.create_team_snake(1, 0.0).unwrap();
+        let loner_id = test_game.create_snake(0.0).unwrap();
+
+        assert_eq!(Some(1),test_game.get_snake(snake1_id).unwrap().get_team());
+        assert_eq!(Some(2),test_game.get_snake(snake2_id).unwrap().get_team());
+        assert_eq!(None,test_game.get_snake(loner_id).unwrap().get_team());
+        assert_eq!(vec![snake1_id,snake3_id],test_game.get_team_members(1));
+        assert_eq!(vec![snake2_id],test_game.get_team_members(2));
+        assert!(test_game.get_team_members(3).is_empty());
+
+        // team membership is announced when the snake is born
+        use crate::eventqueue::eventqueue::EventKind;
+        let event = test_game.get_event_queue_mut().get_event().unwrap();
+        match event.kind {
+            EventKind::SnakeBorn {snake_id, team, ..} => {
+                assert_eq!(snake1_id,snake_id);
+                assert_eq!(Some(1),team);
+            }
+            _ => panic!("Expected a SnakeBorn event, got {}", event),
+        }
+    }
+
+    #[test]
+    fn t_team_score() {
+        let mut test_game = Game::new_blank_game();
+        let snake1_id = test_game.create_team_snake(1, 0.0).unwrap();
+        let snake2_id = test_game.create_team_snake(1, 0.0).unwrap();
+        let snake3_id = test_game.create_team_snake(2, 0.0).unwrap();
+        assert_eq!(0.0,test_game.get_team_score(1).unwrap());
+        assert!(test_game.get_team_score(3).is_err());
+
+        test_game.feed_snake(snake1_id, 2.0).unwrap();
+        test_game.feed_snake(snake2_id, 3.0).unwrap();
+        test_game.feed_snake(snake3_id, 1.0).unwrap();
+        assert_eq!(5.0,test_game.get_team_score(1).unwrap());
+        assert_eq!(1.0,test_game.get_team_score(2).unwrap());
+
+        // points stay with the team after the snake that scored them is gone
+        test_game.remove_snake(snake1_id, 0.0).unwrap();
+        assert_eq!(5.0,test_game.get_team_score(1).unwrap());
+    }
+
+    // Set two snakes up to meet at (0,1) on the next one second tick
+    fn _head_on(test_game: &mut Game, snake1_id: usize, snake2_id: usize) {
+        test_game._place_snake(snake1_id, Coordinates {x: 0.0, y: 0.0}, Velocity {delta_x: 0.0, delta_y: 1.0}).unwrap();
+        test_game._place_snake(snake2_id, Coordinates {x: 0.0, y: 2.0}, Velocity {delta_x: 0.0, delta_y: -1.0}).unwrap();
+    }
+
+    #[test]
+    fn t_spawn_clearance() {
+        // snakes aren't born on top of each other, so they're all still
+        // alive a tick later
+        let mut test_game = Game::new_blank_game();
+        let snake1_id = test_game.create_snake(0.0).unwrap();
+        let snake2_id = test_game.create_snake(0.0).unwrap();
+        let (loc1, loc2) = (test_game.get_snake(snake1_id).unwrap().get_location(), test_game.get_snake(snake2_id).unwrap().get_location());
+        assert!((loc1.x - loc2.x).hypot(loc1.y - loc2.y) >= SPAWN_CLEARANCE);
+        test_game.advance_clock(1.0);
+        assert!(test_game.get_snake(snake1_id).is_ok());
+        assert!(test_game.get_snake(snake2_id).is_ok());
+
+        // the first snake still gets the centre, and the rest get the same
+        // spots every time the game's played
+        let mut test_game2 = Game::new_blank_game();
+        test_game2.create_snake(0.0).unwrap();
+        test_game2.create_snake(0.0).unwrap();
+        assert_eq!(Coordinates {x: 0.0, y: 0.0}, loc1);
+        assert_eq!(loc2, test_game2.get_snake(snake2_id).unwrap().get_location());
+    }
+
+    #[test]
+    fn t_collision() {
+        let mut test_game = Game::new_blank_game();
+        // snakes that meet head on run into each other, and the bigger one survives
+        let snake1_id = test_game.create_snake(0.0).unwrap();
+        let snake2_id = test_game.create_snake(0.0).unwrap();
+        _head_on(&mut test_game, snake1_id, snake2_id);
+        test_game.feed_snake(snake1_id, 10.0).unwrap();
+        test_game.advance_clock(1.0);
+        assert!(test_game.get_snake(snake1_id).is_ok());
+        assert!(test_game.get_snake(snake2_id).is_err());
+
+        // the collision is announced, then the smaller one's death
+        use crate::eventqueue::eventqueue::EventKind;
+        let mut announced = Vec::new();
+        while let Some(event) = test_game.get_event_queue_mut().get_event() {
+            match event.kind {
+                EventKind::SnakeCollided {snake_id, other_snake_id, loc} => {
+                    assert_eq!((snake1_id, snake2_id), (snake_id, other_snake_id));
+                    // where snake 2's head was when snake 1 ran over it
+                    assert_eq!(1.0, loc.y);
+                    announced.push(event.kind.get_name());
+                }
+                EventKind::SnakeKilled {snake_id} => {
+                    assert_eq!(snake2_id, snake_id);
+                    announced.push(event.kind.get_name());
+                }
+                _ => (),
+            }
+        }
+        assert_eq!(vec!["snake_collided", "snake_killed"],announced);
+    }
+
+    #[test]
+    fn t_friendly_fire() {
+        // with friendly fire on, evenly matched teammates both die
+        let mut test_game = Game::new_blank_game();
+        let snake1_id = test_game.create_team_snake(1, 0.0).unwrap();
+        let snake2_id = test_game.create_team_snake(1, 0.0).unwrap();
+        _head_on(&mut test_game, snake1_id, snake2_id);
+        test_game.advance_clock(1.0);
+        assert!(test_game.get_snake(snake1_id).is_err());
+        assert!(test_game.get_snake(snake2_id).is_err());
+
+        // with friendly fire off, teammates pass through each other
+        let mut config = GameConfig::default();
+        config.team_rules.friendly_fire = false;
+        let mut test_game = Game::new_blank_game_with_config(config);
+        let snake1_id = test_game.create_team_snake(1, 0.0).unwrap();
+        let snake2_id = test_game.create_team_snake(1, 0.0).unwrap();
+        _head_on(&mut test_game, snake1_id, snake2_id);
+        test_game.advance_clock(1.0);
+        assert!(test_game.get_snake(snake1_id).is_ok());
+        assert!(test_game.get_snake(snake2_id).is_ok());
+
+        // ...but still collide with the other team, here cutting across
+        // snake 1 as it goes on north
+        let snake3_id = test_game.create_team_snake(2, 1.0).unwrap();
+        test_game._place_snake(snake3_id, Coordinates {x: 1.0, y: 2.0}, Velocity {delta_x: -1.0, delta_y: 0.0}).unwrap();
+        test_game.advance_clock(1.0);
+        assert!(test_game.get_snake(snake1_id).is_err());
+        assert!(test_game.get_snake(snake2_id).is_ok());
+        assert!(test_game.get_snake(snake3_id).is_err());
+    }
+
+    #[test]
+    fn t_last_team_standing() {
+        let mut config = GameConfig::default();
+        config.team_rules.win_condition = WinCondition::LastTeamStanding;
+        let mut test_game = Game::new_blank_game_with_config(config);
+        let snake1_id = test_game.create_team_snake(1, 0.0).unwrap();
+        assert_eq!(None,test_game.get_winning_team());
+
+        let snake2_id = test_game.create_team_snake(2, 0.0).unwrap();
+        assert_eq!(None,test_game.get_winning_team());
+
+        test_game.remove_snake(snake2_id, 0.0).unwrap();
+        assert_eq!(Some(1),test_game.get_winning_team());
+
+        test_game.remove_snake(snake1_id, 0.0).unwrap();
+        assert_eq!(None,test_game.get_winning_team());
+    }
+
+    #[test]
+    fn t_score_target() {
+        let mut config = GameConfig::default();
+        config.team_rules.win_condition = WinCondition::ScoreTarget(3.0);
+        let mut test_game = Game::new_blank_game_with_config(config);
+        let snake1_id = test_game.create_team_snake(1, 0.0).unwrap();
+        let snake2_id = test_game.create_team_snake(2, 0.0).unwrap();
+        let snake3_id = test_game.create_team_snake(2, 0.0).unwrap();
+
+        test_game.feed_snake(snake1_id, 2.0).unwrap();
+        test_game.feed_snake(snake2_id, 2.0).unwrap();
+        assert_eq!(None,test_game.get_winning_team());
+
+        test_game.feed_snake(snake3_id, 2.0).unwrap();
+        assert_eq!(Some(2),test_game.get_winning_team());
+    }
+
+    #[test]
+    fn t_game_events() {
+        use crate::eventqueue::eventqueue::EventKind;
+        let mut config = GameConfig::default();
+        config.team_rules.win_condition = WinCondition::ScoreTarget(1.0);
+        let mut test_game = Game::new_blank_game_with_config(config);
+        test_game.start();
+        let snake1_id = test_game.create_team_snake(1, 0.0).unwrap();
+        let food1_id = test_game.add_food(0.0, 0.5, 0.0).unwrap();
+        test_game.advance_clock(1.0);
+        // the game's been won, which only ends it once
+        test_game.advance_clock(1.0);
+
+        let mut events = Vec::new();
+        while let Some(event) = test_game.get_event_queue_mut().get_event() {
+            events.push(event.kind);
+        }
+        let names:Vec<&str> = events.iter().map(|kind| kind.get_name()).collect();
+        assert_eq!(vec!["game_started", "snake_born", "food_added", "clock_advanced", "snake_ate",
+            "food_removed", "snake_grew", "score_changed", "game_ended", "clock_advanced"], names);
+        assert_eq!(EventKind::FoodRemoved {food_id: food1_id, reason: FoodRemovalReason::Consumed}, events[5]);
+        assert_eq!(EventKind::SnakeGrew {snake_id: snake1_id, size: test_game.get_snake(snake1_id).unwrap().get_size()}, events[6]);
+        assert_eq!(EventKind::ScoreChanged {snake_id: snake1_id, score: 1.0}, events[7]);
+        assert_eq!(EventKind::GameEnded {winner: Some(1)}, events[8]);
+    }
+
+    #[test]
+    fn t_fold_into_snapshot() {
+        use crate::eventqueue::eventqueue::{EventKind,OverflowPolicy,RetentionPolicy,PRIMARY_SUBSCRIBER};
+        let mut test_game = Game::new_blank_game();
+        test_game.get_event_queue_mut().set_retention_policy(RetentionPolicy {
+            max_backlog: Some(5),
+            overflow: OverflowPolicy::FoldIntoSnapshot,
+        });
+        test_game.create_snake(0.0).unwrap();
+        for _i in 0..5 {
+            test_game.advance_clock(0.1);
+        }
+        // the sixth event overflowed, and a snapshot took the place of all six
+        let queue = test_game.get_event_queue_mut();
+        assert_eq!(6, queue.get_stats().folded);
+        assert_eq!(6, queue.get_missed_count(PRIMARY_SUBSCRIBER));
+        let event = queue.get_event().unwrap();
+        assert!(matches!(event.kind, EventKind::StateSnapshot { ref snakes } if snakes.len() == 1));
+        assert_eq!(6, event.sequence);
+        assert!(queue.get_event().is_none());
+    }
+
+    #[test]
+    fn t_event_ticks() {
+        let mut test_game = Game::new_blank_game();
+        test_game.create_snake(0.0).unwrap();
+        test_game.advance_clock(0.5);
+        test_game.add_food(10.0, 10.0, 0.5).unwrap();
+        test_game.advance_clock(0.5);
+        let mut stamps = Vec::new();
+        while let Some(event) = test_game.get_event_queue_mut().get_event() {
+            stamps.push((event.kind.get_name(), event.sequence, event.tick));
+        }
+        assert_eq!(vec![
+            ("snake_born", 0, 0),
+            ("clock_advanced", 1, 1),
+            ("food_added", 2, 1),
+            ("clock_advanced", 3, 2),
+        ], stamps);
+        assert_eq!(2, test_game.get_tick());
+    }
+
+    #[test]
+    fn t_state_snapshots() {
+        use crate::eventqueue::eventqueue::EventKind;
+        use assert_approx_eq::assert_approx_eq;
+        // no snapshots unless asked for
+        let mut test_game = Game::new_blank_game();
+        test_game.create_snake(0.0).unwrap();
+        for _i in 0..20 {
+            test_game.advance_clock(0.1);
+        }
+        let mut snapshot_count = 0;
+        while let Some(event) = test_game.get_event_queue_mut().get_event() {
+            if let EventKind::StateSnapshot { .. } = event.kind {
+                snapshot_count += 1;
+            }
+        }
+        assert_eq!(0, snapshot_count);
+
+        let config = GameConfig {snapshot_interval: Some(0.5), ..GameConfig::default()};
+        let mut test_game = Game::new_blank_game_with_config(config);
+        let snake_id = test_game.create_snake(0.0).unwrap();
+        test_game.turn_snake(snake_id, std::f64::consts::FRAC_PI_2, 0.0).unwrap();
+        for _i in 0..20 {
+            test_game.advance_clock(0.1);
+        }
+        let mut snapshots = Vec::new();
+        while let Some(event) = test_game.get_event_queue_mut().get_event() {
+            if let EventKind::StateSnapshot { snakes } = event.kind {
+                snapshots.push((event.event_time, snakes));
+            }
+        }
+        // one every half second of game time, after the clock advance that reached it
+        assert_eq!(4, snapshots.len());
+        let (event_time, snakes) = &snapshots[3];
+        assert_approx_eq!(2.0, *event_time, 1e-9);
+        assert_eq!(1, snakes.len());
+        assert_eq!(snake_id, snakes[0].snake_id);
+        assert_approx_eq!(-2.0, snakes[0].loc.x, 1e-9);
+        assert_approx_eq!(-1.0, snakes[0].vel.delta_x, 1e-9);
+        assert_eq!(1.0, snakes[0].size);
+    }
+
+    #[test]
+    fn t_grid_game() {
+        use crate::eventqueue::eventqueue::EventKind;
+        use std::f64::consts::FRAC_PI_2;
+        let config = GameConfig {mode: GameMode::classic_grid(), ..GameConfig::default()};
+        let mut test_game = Game::new_blank_game_with_config(config);
+
+        // food lands on whole cells
+        let food_id = test_game.add_food(-1.8, 2.2, 0.0).unwrap();
+        let loc = test_game._get_food(food_id).unwrap().get_location();
+        assert_eq!(-2.0,loc.x);
+        assert_eq!(2.0,loc.y);
+
+        // one cell per tick, nothing in between
+        let snake_id = test_game.create_snake(0.0).unwrap();
+        test_game.advance_clock(0.5);
+        assert_eq!(0.0,test_game.get_snake(snake_id).unwrap().get_location().y);
+        test_game.advance_clock(0.5);
+        assert_eq!(1.0,test_game.get_snake(snake_id).unwrap().get_location().y);
+
+        // turns are a quarter turn, whatever was asked for
+        test_game.turn_snake(snake_id, 0.3, 1.0).unwrap();
+        test_game.advance_clock(1.0);
+        {
+            let test_snake = test_game.get_snake(snake_id).unwrap();
+            assert_eq!(-1.0,test_snake.get_velocity().delta_x);
+            assert_eq!(0.0,test_snake.get_velocity().delta_y);
+            assert_eq!(-1.0,test_snake.get_location().x);
+            assert_eq!(1.0,test_snake.get_location().y);
+        }
+
+        // food sitting off the snake's row is left alone
+        test_game.advance_clock(1.0);
+        assert_eq!(1,test_game._get_food_count());
+
+        // head up into the food's cell and grow by one segment
+        test_game.turn_snake(snake_id, -0.3, 3.0).unwrap();
+        test_game.advance_clock(1.0);
+        assert_eq!(0,test_game._get_food_count());
+        assert_eq!(2.0,test_game.get_snake(snake_id).unwrap().get_size());
+
+        // the events are the same kinds as the continuous game, with the turns as made
+        let mut turns = Vec::new();
+        let mut meals = Vec::new();
+        while let Some(event) = test_game.get_event_queue_mut().get_event() {
+            match event.kind {
+                EventKind::SnakeTurned {rad_relative, ..} => turns.push(rad_relative),
+                EventKind::SnakeAte {food_id, ..} => meals.push(food_id),
+                _ => (),
+            }
+        }
+        assert_eq!(vec![FRAC_PI_2,-FRAC_PI_2],turns);
+        assert_eq!(vec![food_id],meals);
+    }
+
+    #[test]
+    fn t_too_late() {
+        use crate::eventqueue::eventqueue::LogError;
+        // inputs too late for the queue's reorder window aren't played
+        let mut test_game = Game::new_blank_game();
+        test_game.get_event_queue_mut().set_reorder_window(Some(0.5));
+        let snake_id = test_game.create_snake(0.0).unwrap();
+        test_game.advance_clock(1.0);
+        let too_late = LogError::TooLate { event_time: 0.2, oldest_accepted: 0.5 };
+        assert_eq!(Err(GameError::Log(too_late)), test_game.turn_snake(snake_id, 1.0, 0.2));
+        assert_eq!(1.0, test_game.get_snake(snake_id).unwrap().get_velocity().delta_y);
+        assert_eq!(Err(too_late), test_game.add_food(1.0, 1.0, 0.2));
+        assert_eq!(0, test_game._get_food_count());
+        assert_eq!(Err(too_late), test_game.create_snake(0.2));
+        assert_eq!(vec![snake_id], test_game.get_snake_ids());
+        assert_eq!(Err(GameError::NotFound), test_game.turn_snake(snake_id + 1, 1.0, 1.0));
+
+        // and the ids they'd have had go to the next ones that are
+        assert_eq!(Ok(snake_id + 1), test_game.create_snake(1.0));
+        assert_eq!(Ok(0), test_game.add_food(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn t_queue_full() {
+        use crate::eventqueue::eventqueue::{LogError,OverflowPolicy,RetentionPolicy};
+        for overflow in [OverflowPolicy::Backpressure, OverflowPolicy::DropNewest] {
+            let mut test_game = Game::new_blank_game();
+            test_game.get_event_queue_mut().set_retention_policy(RetentionPolicy { max_backlog: Some(2), overflow });
+            let snake_id = test_game.create_snake(0.0).unwrap();
+            test_game.advance_clock(1.0);
+
+            // with the backlog full, inputs are turned away and not played
+            let full = LogError::QueueFull { max_backlog: 2 };
+            assert_eq!(Err(GameError::Log(full)), test_game.turn_snake(snake_id, 1.0, 1.0));
+            assert_eq!(1.0, test_game.get_snake(snake_id).unwrap().get_velocity().delta_y);
+            assert_eq!(Err(full), test_game.add_food(1.0, 1.0, 1.0));
+            assert_eq!(0, test_game._get_food_count());
+            assert_eq!(Err(full), test_game.create_snake(1.0));
+            assert_eq!(vec![snake_id], test_game.get_snake_ids());
+
+            // once the backlog's been read there's room again
+            while test_game.get_event_queue_mut().get_event().is_some() {}
+            assert!(test_game.turn_snake(snake_id, 1.0, 1.0).is_ok());
+            assert_ne!(1.0, test_game.get_snake(snake_id).unwrap().get_velocity().delta_y);
+        }
+    }
+
     #[test]
     fn t_add_food() {
         let mut test_game = Game::new_blank_game();
 
         assert_eq!(test_game._get_food_count(),0);
 
-        let food_id = test_game.add_food(23.0, 34.0, 0.0);
+        let food_id = test_game.add_food(23.0, 34.0, 0.0).unwrap();
         assert_eq!(test_game._get_food_count(),1);
         
         let this_food = test_game._get_food(food_id).expect("Didn't get food as expected!");
@@ -391,4 +1352,98 @@ pub mod game {
         assert_eq!(loc.y,34.0);
 
     }
+
+    #[test]
+    fn t_food_expires() {
+        // food lies for its lifetime, then goes if nothing has eaten it
+        let config = GameConfig { food_lifetime: Some(1.0), ..GameConfig::default() };
+        let mut test_game = Game::new_blank_game_with_config(config);
+        let early_id = test_game.add_food(20.0, 20.0, 0.0).unwrap();
+        for _i in 0..5 {
+            test_game.advance_clock(0.1);
+        }
+        let late_id = test_game.add_food(-20.0, 20.0, test_game.get_time()).unwrap();
+        while test_game.get_event_queue_mut().get_event().is_some() {}
+        for _i in 0..5 {
+            test_game.advance_clock(0.1);
+        }
+        assert!(test_game._get_food(early_id).is_err());
+        assert!(test_game._get_food(late_id).is_ok());
+        let mut expired = Vec::new();
+        while let Some(event) = test_game.get_event_queue_mut().get_event() {
+            if let EventKind::FoodRemoved { food_id, reason } = event.kind {
+                expired.push((food_id, reason, event.event_time));
+            }
+        }
+        assert_eq!(1, expired.len());
+        assert_eq!((early_id, FoodRemovalReason::Expired), (expired[0].0, expired[0].1));
+        assert!((expired[0].2 - 1.0).abs() < 1e-9);
+
+        // without a lifetime it stays put
+        let mut test_game = Game::new_blank_game();
+        let food_id = test_game.add_food(20.0, 20.0, 0.0).unwrap();
+        test_game.advance_clock(100.0);
+        assert!(test_game._get_food(food_id).is_ok());
+    }
+
+    #[test]
+    fn t_boost_snake() {
+        let mut test_game = Game::new_blank_game();
+        assert_eq!(Err(GameError::NotFound), test_game.boost_snake(0, 2.0, 0.0));
+
+        let snake_id = test_game.create_snake(0.0).unwrap();
+        test_game.advance_clock(1.0);
+        test_game.boost_snake(snake_id, 3.0, 1.0).unwrap();
+        test_game.advance_clock(1.0);
+        let the_snake = test_game.get_snake(snake_id).unwrap();
+        assert_eq!(3.0, the_snake.get_speed());
+        assert_eq!(4.0, the_snake.get_location().y);
+        let mut boosts = Vec::new();
+        while let Some(event) = test_game.get_event_queue_mut().get_event() {
+            if let EventKind::SnakeBoosted { .. } = event.kind {
+                boosts.push(event.kind);
+            }
+        }
+        assert_eq!(vec![EventKind::SnakeBoosted { snake_id, speed: 3.0 }], boosts);
+
+        // a grid snake only goes whole cells a tick, and logs the speed it gets
+        let config = GameConfig { mode: GameMode::classic_grid(), ..GameConfig::default() };
+        let mut test_game = Game::new_blank_game_with_config(config);
+        let snake_id = test_game.create_snake(0.0).unwrap();
+        test_game.boost_snake(snake_id, 2.4, 0.0).unwrap();
+        assert_eq!(2.0, test_game.get_snake(snake_id).unwrap().get_speed());
+    }
+
+    #[test]
+    fn t_rename_snake() {
+        let mut test_game = Game::new_blank_game();
+        assert_eq!(Err(GameError::NotFound), test_game.rename_snake(0, "Kaa", 0.0));
+
+        let snake_id = test_game.create_snake(0.0).unwrap();
+        assert_eq!(None, test_game.get_snake_name(snake_id).unwrap());
+        test_game.rename_snake(snake_id, "Kaa", 0.0).unwrap();
+        test_game.rename_snake(snake_id, "Sir Hiss", 0.5).unwrap();
+        assert_eq!(Some("Sir Hiss"), test_game.get_snake_name(snake_id).unwrap());
+        assert_eq!(vec![SnakeName { snake_id, name: "Sir Hiss".to_string() }], test_game.snapshot().names);
+
+        // the name goes with the snake
+        test_game.remove_snake(snake_id, 1.0).unwrap();
+        assert!(test_game.get_snake_name(snake_id).is_err());
+        assert!(test_game.snapshot().names.is_empty());
+    }
+
+    #[test]
+    fn t_near() {
+        let mut test_game = Game::new_blank_game();
+        let snake_id = test_game.create_snake(0.0).unwrap();
+        let near_food_id = test_game.add_food(3.0, 4.0, 0.0).unwrap();
+        let _far_food_id = test_game.add_food(3.0, 4.5, 0.0).unwrap();
+        let centre = Coordinates {x:0.0, y:0.0};
+
+        // edges included
+        assert_eq!(vec![near_food_id], test_game.get_food_near(centre, 5.0));
+        assert_eq!(vec![snake_id], test_game.get_snakes_near(centre, 0.0));
+        assert!(test_game.get_snakes_near(Coordinates {x:10.0, y:0.0}, 5.0).is_empty());
+        assert_eq!(vec![snake_id], test_game.get_snake_ids());
+    }
 }
@@ -0,0 +1,328 @@
+pub mod http {
+    // Plain HTTP/1.1 on the game server's port - one request per connection,
+    // closed after the response. Requests for a WebSocket are left unread for
+    // tungstenite; peek_head() and is_websocket_upgrade() tell them apart.
+    use serde::Serialize;
+    use std::fmt;
+    use std::io::{self, Read, Write};
+    use std::net::TcpStream;
+    use std::thread;
+    use std::time::Duration;
+
+    // longest request line and headers taken
+    const MAX_HEAD: usize = 16 * 1024;
+    const MAX_BODY: usize = 1024 * 1024;
+    const MAX_HEADERS: usize = 64;
+
+    #[derive(Debug)]
+    pub enum HttpError {
+        Io(io::Error),
+        Malformed,
+        TooLarge,
+    }
+    impl std::error::Error for HttpError {}
+    impl fmt::Display for HttpError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                HttpError::Io(error) => write!(f, "{}", error),
+                HttpError::Malformed => write!(f, "Malformed request!"),
+                HttpError::TooLarge => write!(f, "Request too large!"),
+            }
+        }
+    }
+    impl From<io::Error> for HttpError {
+        fn from(error: io::Error) -> Self {
+            HttpError::Io(error)
+        }
+    }
+
+    #[derive(Clone, PartialEq, Debug)]
+    pub struct HttpRequest {
+        pub method: String,
+        // percent-decoded, without the query
+        pub path: String,
+        pub query: Vec<(String, String)>,
+        pub headers: Vec<(String, String)>,
+        pub body: Vec<u8>,
+    }
+    impl HttpRequest {
+        pub fn get_header(&self, name: &str) -> Option<&str> {
+            self.headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+        }
+
+        pub fn get_query(&self, name: &str) -> Option<&str> {
+            self.query.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+        }
+
+        // The path split on slashes, without empty segments
+        pub fn get_segments(&self) -> Vec<&str> {
+            self.path.split('/').filter(|segment| !segment.is_empty()).collect()
+        }
+    }
+
+    #[derive(Clone, PartialEq, Debug)]
+    pub struct HttpResponse {
+        pub status: u16,
+        pub headers: Vec<(String, String)>,
+        pub body: Vec<u8>,
+    }
+    impl HttpResponse {
+        pub fn empty(status: u16) -> HttpResponse {
+            HttpResponse { status, headers: Vec::new(), body: Vec::new() }
+        }
+
+        pub fn json<T: Serialize>(status: u16, value: &T) -> HttpResponse {
+            let body = serde_json::to_vec(value).expect("Responses always serialize");
+            HttpResponse::empty(status).with_header("Content-Type", "application/json").with_body(body)
+        }
+
+        // {"error":message}
+        pub fn error(status: u16, message: &str) -> HttpResponse {
+            HttpResponse::json(status, &serde_json::json!({ "error": message }))
+        }
+
+        pub fn with_header(mut self, name: &str, value: &str) -> HttpResponse {
+            self.headers.push((name.to_string(), value.to_string()));
+            self
+        }
+
+        pub fn with_body(mut self, body: Vec<u8>) -> HttpResponse {
+            self.body = body;
+            self
+        }
+
+        pub fn get_header(&self, name: &str) -> Option<&str> {
+            self.headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+        }
+    }
+
+    fn reason_phrase(status: u16) -> &'static str {
+        match status {
+            200 => "OK",
+            201 => "Created",
+            204 => "No Content",
+            304 => "Not Modified",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            413 => "Payload Too Large",
+            500 => "Internal Server Error",
+            503 => "Service Unavailable",
+            _ => "Unknown",
+        }
+    }
+
+    // The request line and headers, left in the stream for whoever reads the
+    // request. Gives up with what it has at the end of the stream or when a
+    // read times out.
+    pub fn peek_head(stream: &TcpStream) -> io::Result<Vec<u8>> {
+        let mut buf = vec![0; MAX_HEAD];
+        let mut seen = 0;
+        loop {
+            let len = match stream.peek(&mut buf) {
+                Ok(len) => len,
+                Err(ref error) if error.kind() == io::ErrorKind::WouldBlock || error.kind() == io::ErrorKind::TimedOut => {
+                    buf.truncate(seen);
+                    return Ok(buf);
+                }
+                Err(error) => return Err(error),
+            };
+            if let Some(end) = find_head_end(&buf[..len]) {
+                buf.truncate(end);
+                return Ok(buf);
+            }
+            if len == 0 || len == buf.len() {
+                buf.truncate(len);
+                return Ok(buf);
+            }
+            if len == seen {
+                // peek returns straight away while there's anything waiting,
+                // so wait for more to turn up
+                thread::sleep(Duration::from_millis(1));
+            }
+            seen = len;
+        }
+    }
+
+    pub fn is_websocket_upgrade(head: &[u8]) -> bool {
+        let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+        let mut request = httparse::Request::new(&mut headers);
+        if request.parse(head).is_err() {
+            return false;
+        }
+        request.headers.iter().any(|header| {
+            header.name.eq_ignore_ascii_case("upgrade") && String::from_utf8_lossy(header.value).trim().eq_ignore_ascii_case("websocket")
+        })
+    }
+
+    pub fn read_request(stream: &mut TcpStream) -> Result<HttpRequest, HttpError> {
+        let mut buf = Vec::new();
+        let mut chunk = [0; 4096];
+        let head_len = loop {
+            if let Some(end) = find_head_end(&buf) {
+                break end;
+            }
+            if buf.len() > MAX_HEAD {
+                return Err(HttpError::TooLarge);
+            }
+            let len = stream.read(&mut chunk)?;
+            if len == 0 {
+                return Err(HttpError::Malformed);
+            }
+            buf.extend_from_slice(&chunk[..len]);
+        };
+
+        let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+        let mut parsed = httparse::Request::new(&mut headers);
+        match parsed.parse(&buf[..head_len]) {
+            Ok(httparse::Status::Complete(_)) => (),
+            Err(httparse::Error::TooManyHeaders) => return Err(HttpError::TooLarge),
+            _ => return Err(HttpError::Malformed),
+        }
+        let method = parsed.method.ok_or(HttpError::Malformed)?.to_string();
+        let target = parsed.path.ok_or(HttpError::Malformed)?;
+        let headers: Vec<(String, String)> = parsed.headers.iter()
+            .map(|header| (header.name.to_string(), String::from_utf8_lossy(header.value).to_string()))
+            .collect();
+        let (path, query) = match target.find('?') {
+            Some(idx) => (&target[..idx], &target[idx + 1..]),
+            None => (target, ""),
+        };
+
+        let mut request = HttpRequest {
+            method,
+            path: percent_decode(path).ok_or(HttpError::Malformed)?,
+            query: parse_query(query).ok_or(HttpError::Malformed)?,
+            headers,
+            body: buf.split_off(head_len),
+        };
+        let content_length = match request.get_header("content-length") {
+            Some(value) => value.trim().parse::<usize>().map_err(|_| HttpError::Malformed)?,
+            None => 0,
+        };
+        if content_length > MAX_BODY {
+            return Err(HttpError::TooLarge);
+        }
+        while request.body.len() < content_length {
+            let len = stream.read(&mut chunk)?;
+            if len == 0 {
+                return Err(HttpError::Malformed);
+            }
+            request.body.extend_from_slice(&chunk[..len]);
+        }
+        request.body.truncate(content_length);
+        Ok(request)
+    }
+
+    pub fn write_response(stream: &mut impl Write, response: &HttpResponse) -> io::Result<()> {
+        stream.write_all(head_of(response).as_bytes())?;
+        stream.write_all(&response.body)?;
+        stream.flush()
+    }
+
+    // The answer to a HEAD request: what GET would have sent, less the body
+    pub fn write_head(stream: &mut impl Write, response: &HttpResponse) -> io::Result<()> {
+        stream.write_all(head_of(response).as_bytes())?;
+        stream.flush()
+    }
+
+    fn head_of(response: &HttpResponse) -> String {
+        let mut head = format!("HTTP/1.1 {} {}\r\n", response.status, reason_phrase(response.status));
+        for (name, value) in response.headers.iter() {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", response.body.len()));
+        head
+    }
+
+    // Where the blank line after the headers ends
+    fn find_head_end(buf: &[u8]) -> Option<usize> {
+        buf.windows(4).position(|window| window == b"\r\n\r\n").map(|idx| idx + 4)
+    }
+
+    // None if there's a bad escape, or it doesn't decode to UTF-8
+    fn percent_decode(text: &str) -> Option<String> {
+        let bytes = text.as_bytes();
+        let mut decoded = Vec::with_capacity(bytes.len());
+        let mut idx = 0;
+        while idx < bytes.len() {
+            if bytes[idx] == b'%' {
+                let hex = std::str::from_utf8(bytes.get(idx + 1..idx + 3)?).ok()?;
+                decoded.push(u8::from_str_radix(hex, 16).ok()?);
+                idx += 3;
+            } else {
+                decoded.push(bytes[idx]);
+                idx += 1;
+            }
+        }
+        String::from_utf8(decoded).ok()
+    }
+
+    pub fn parse_query(query: &str) -> Option<Vec<(String, String)>> {
+        query.split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = match pair.find('=') {
+                    Some(idx) => (&pair[..idx], &pair[idx + 1..]),
+                    None => (pair, ""),
+                };
+                Some((percent_decode(&key.replace('+', " "))?, percent_decode(&value.replace('+', " "))?))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn t_read_request() {
+        use std::net::TcpListener;
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client.write_all(b"POST /api/games/0/food?after=3&name=Sir+Hiss%21 HTTP/1.1\r\nHost: x\r\nContent-Length: 17\r\n\r\n{\"x\":1.0,\"y\":2.0}").unwrap();
+        let (mut server, _peer) = listener.accept().unwrap();
+
+        // peeking leaves the request for reading
+        let head = peek_head(&server).unwrap();
+        assert!(head.ends_with(b"\r\n\r\n"));
+        assert!(!is_websocket_upgrade(&head));
+        let request = read_request(&mut server).unwrap();
+        assert_eq!("POST", request.method);
+        assert_eq!("/api/games/0/food", request.path);
+        assert_eq!(vec!["api", "games", "0", "food"], request.get_segments());
+        assert_eq!(Some("3"), request.get_query("after"));
+        assert_eq!(Some("Sir Hiss!"), request.get_query("name"));
+        assert_eq!(Some("x"), request.get_header("HOST"));
+        assert_eq!(b"{\"x\":1.0,\"y\":2.0}".to_vec(), request.body);
+
+        write_response(&mut server, &HttpResponse::error(404, "No snake 3")).unwrap();
+        drop(server);
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(response.contains("Content-Type: application/json\r\n"));
+        assert!(response.ends_with("\r\n\r\n{\"error\":\"No snake 3\"}"));
+
+        let mut head = Vec::new();
+        write_head(&mut head, &HttpResponse::error(404, "No snake 3")).unwrap();
+        let head = String::from_utf8(head).unwrap();
+        assert!(head.contains("Content-Length: 22\r\n"));
+        assert!(head.ends_with("\r\n\r\n"));
+    }
+
+    #[test]
+    fn t_websocket_upgrade() {
+        assert!(is_websocket_upgrade(b"GET /ws HTTP/1.1\r\nHost: x\r\nUpgrade: WebSocket\r\nConnection: Upgrade\r\n\r\n"));
+        assert!(!is_websocket_upgrade(b"GET / HTTP/1.1\r\nHost: x\r\n\r\n"));
+        assert!(!is_websocket_upgrade(b"not http at all"));
+    }
+
+    #[test]
+    fn t_decoding() {
+        assert_eq!(Some("a b/c".to_string()), percent_decode("a%20b%2Fc"));
+        assert_eq!(None, percent_decode("bad%2"));
+        assert_eq!(None, percent_decode("bad%zz"));
+        assert_eq!(None, percent_decode("%ff"));
+        assert_eq!(Some(vec![("a".to_string(), "".to_string())]), parse_query("a"));
+        assert_eq!(Some(Vec::new()), parse_query(""));
+    }
+}
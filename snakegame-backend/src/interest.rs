@@ -0,0 +1,288 @@
+pub mod interest {
+    // What each player is told about. A player hears everything within the
+    // radius of their own snake's head, as it happens, and every so often where
+    // the snakes beyond it are. Things coming into range are sent as they are
+    // then, and from there on their events; things going out of range get a
+    // notice, and nothing more but the far updates. Kills and meals out of
+    // range aren't sent at all - a snake that's gone just stops turning up in
+    // the far updates, each of which is every snake out of range.
+    //
+    // What's in range is worked out from the game as it stands once the events
+    // are in, so it's only as fine-grained as how often the events are read.
+    use crate::eventqueue::eventqueue::{Event, EventKind, SnakeState};
+    use crate::game::common::Coordinates;
+    use crate::game::game::{within, Game};
+    use crate::snapshot::snapshot::{FoodSnapshot, GameSnapshot, SnakeSnapshot};
+    use std::collections::BTreeSet;
+    #[cfg(feature = "serde")]
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    pub struct InterestConfig {
+        // how far from the player's snake they hear everything, in game distance
+        pub radius: f64,
+        // ticks between updates on the snakes out of range
+        pub far_interval: u64,
+    }
+    impl Default for InterestConfig {
+        fn default() -> Self {
+            InterestConfig { radius: 25.0, far_interval: 10 }
+        }
+    }
+
+    // Serializes like the events, e.g. {"type":"snake_left","snake_id":3}
+    #[derive(PartialEq, Clone, Debug)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    #[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+    pub enum Notice {
+        // came into range, as it is now
+        SnakeEntered { snake: SnakeSnapshot },
+        SnakeLeft { snake_id: usize },
+        FoodEntered { food: FoodSnapshot },
+        FoodLeft { food_id: usize },
+        // every snake out of range
+        FarSnakes { snakes: Vec<SnakeState> },
+    }
+
+    // One player's view of their game
+    #[derive(Clone)]
+    pub struct Interest {
+        config: InterestConfig,
+        snake_id: usize,
+        // the player's snake's head, or where it was last seen
+        centre: Coordinates,
+        // what the player has been told about and hears the events of
+        snakes: BTreeSet<usize>,
+        food: BTreeSet<usize>,
+        // the tick of the last far update, if there's been one
+        last_far_update: Option<u64>,
+    }
+
+    impl Interest {
+        pub fn new(config: InterestConfig, snake_id: usize) -> Interest {
+            Interest {
+                config,
+                snake_id,
+                centre: Coordinates { x: 0.0, y: 0.0 },
+                snakes: BTreeSet::new(),
+                food: BTreeSet::new(),
+                last_far_update: None,
+            }
+        }
+
+        // The part of a snapshot of the game the player can see, which is what
+        // their view starts from
+        pub fn view_snapshot(&mut self, mut snapshot: GameSnapshot) -> GameSnapshot {
+            if let Some(snake) = snapshot.snakes.iter().find(|snake| snake.snake_id == self.snake_id) {
+                self.centre = snake.location;
+            }
+            let (centre, radius) = (self.centre, self.config.radius);
+            snapshot.snakes.retain(|snake| within(snake.location, centre, radius));
+            snapshot.food.retain(|food| within(food.location, centre, radius));
+            self.snakes = snapshot.snakes.iter().map(|snake| snake.snake_id).collect();
+            self.food = snapshot.food.iter().map(|food| food.food_id).collect();
+            snapshot
+        }
+
+        // Of events from before the view started, e.g. what a reconnecting
+        // player missed, the ones about what's in view or turned up in range
+        pub fn view_history(&self, events: Vec<Event>) -> Vec<Event> {
+            let mut past = self.clone();
+            events.into_iter().filter_map(|event| past.view_event(event)).collect()
+        }
+
+        // Of the events logged since last time, the ones the player hears of,
+        // and then what's come into and gone out of range
+        pub fn filter(&mut self, game: &Game, events: Vec<Event>) -> (Vec<Event>, Vec<Notice>) {
+            if let Ok(snake) = game.get_snake(self.snake_id) {
+                self.centre = snake.get_location();
+            }
+            let events = events.into_iter().filter_map(|event| self.view_event(event)).collect();
+
+            let mut notices = Vec::new();
+            let snakes: BTreeSet<usize> = game.get_snakes_near(self.centre, self.config.radius).into_iter().collect();
+            for snake_id in self.snakes.difference(&snakes) {
+                notices.push(Notice::SnakeLeft { snake_id: *snake_id });
+            }
+            for snake_id in snakes.difference(&self.snakes) {
+                if let Ok(snake) = game.get_snake(*snake_id) {
+                    notices.push(Notice::SnakeEntered { snake: snake.snapshot(*snake_id) });
+                }
+            }
+            self.snakes = snakes;
+            let food: BTreeSet<usize> = game.get_food_near(self.centre, self.config.radius).into_iter().collect();
+            for food_id in self.food.difference(&food) {
+                notices.push(Notice::FoodLeft { food_id: *food_id });
+            }
+            for food_id in food.difference(&self.food) {
+                if let Ok(this_food) = game._get_food(*food_id) {
+                    notices.push(Notice::FoodEntered { food: this_food.snapshot(*food_id) });
+                }
+            }
+            self.food = food;
+
+            let tick = game.get_tick();
+            let due = match self.last_far_update {
+                Some(last) => tick >= last + self.config.far_interval,
+                None => true,
+            };
+            if due {
+                self.last_far_update = Some(tick);
+                let snakes: Vec<SnakeState> = game.get_snake_ids().into_iter()
+                    .filter(|snake_id| !self.snakes.contains(snake_id))
+                    .filter_map(|snake_id| game.get_snake(snake_id).ok().map(|snake| SnakeState {
+                        snake_id,
+                        loc: snake.get_location(),
+                        vel: snake.get_velocity(),
+                        size: snake.get_size(),
+                    }))
+                    .collect();
+                if !snakes.is_empty() {
+                    notices.push(Notice::FarSnakes { snakes });
+                }
+            }
+            (events, notices)
+        }
+
+        // The event as the player hears it, if they do. Things born in range
+        // are in view from then on, and things that go are out of it.
+        fn view_event(&mut self, mut event: Event) -> Option<Event> {
+            let heard = match event.kind {
+                EventKind::SnakeBorn { snake_id, loc, .. } => {
+                    within(loc, self.centre, self.config.radius) && self.snakes.insert(snake_id)
+                }
+                EventKind::FoodAdded { food_id, loc, .. } => {
+                    within(loc, self.centre, self.config.radius) && self.food.insert(food_id)
+                }
+                EventKind::SnakeKilled { snake_id } | EventKind::SnakeRemoved { snake_id } => self.snakes.remove(&snake_id),
+                EventKind::FoodRemoved { food_id, .. } => self.food.remove(&food_id),
+                EventKind::SnakeAte { snake_id, food_id } => self.snakes.contains(&snake_id) || self.food.contains(&food_id),
+                EventKind::SnakeCollided { snake_id, other_snake_id, .. } => {
+                    self.snakes.contains(&snake_id) || self.snakes.contains(&other_snake_id)
+                }
+                EventKind::StateSnapshot { ref mut snakes } => {
+                    snakes.retain(|snake| self.snakes.contains(&snake.snake_id));
+                    true
+                }
+                _ => match event.kind.get_snake_id() {
+                    Some(snake_id) => self.snakes.contains(&snake_id),
+                    None => true,
+                },
+            };
+            if heard {
+                Some(event)
+            } else {
+                None
+            }
+        }
+    }
+
+    fn _place(saved: &mut GameSnapshot, snake_id: usize, x: f64, y: f64) {
+        let snake = saved.snakes.iter_mut().find(|snake| snake.snake_id == snake_id).unwrap();
+        snake.location = Coordinates { x, y };
+    }
+
+    fn _drain(game: &mut Game) -> Vec<Event> {
+        let mut events = Vec::new();
+        while let Some(event) = game.get_event_queue_mut().get_event() {
+            events.push(event);
+        }
+        events
+    }
+
+    #[test]
+    fn t_filter() {
+        use std::f64::consts::{FRAC_PI_2, PI};
+        // my snake at the centre, one near, one beyond the radius
+        let mut game = Game::new_blank_game();
+        let my_snake_id = game.create_snake(0.0).unwrap();
+        let near_snake_id = game.create_snake(0.0).unwrap();
+        let far_snake_id = game.create_snake(0.0).unwrap();
+        let mut saved = game.snapshot();
+        _place(&mut saved, near_snake_id, 0.0, -10.0);
+        _place(&mut saved, far_snake_id, 30.0, 0.0);
+        let mut game = Game::restore(&saved).unwrap();
+
+        let config = InterestConfig { radius: 25.0, far_interval: 10 };
+        let mut interest = Interest::new(config, my_snake_id);
+        let snapshot = interest.view_snapshot(game.snapshot());
+        let snake_ids: Vec<usize> = snapshot.snakes.iter().map(|snake| snake.snake_id).collect();
+        assert_eq!(vec![my_snake_id, near_snake_id], snake_ids);
+        // as is what happened before then
+        let turned = |snake_id: usize| Event::new(EventKind::SnakeTurned { snake_id, rad_relative: 0.5 }, 0.0);
+        assert_eq!(vec![turned(near_snake_id)], interest.view_history(vec![turned(far_snake_id), turned(near_snake_id)]));
+
+        // only what happens nearby is heard of, and where the far snake is
+        let near_food_id = game.add_food(-5.0, 5.0, 0.0).unwrap();
+        let far_food_id = game.add_food(40.0, 0.0, 0.0).unwrap();
+        game.turn_snake(my_snake_id, -FRAC_PI_2, 0.0).unwrap();
+        game.turn_snake(near_snake_id, PI, 0.0).unwrap();
+        game.turn_snake(far_snake_id, PI, 0.0).unwrap();
+        let events = _drain(&mut game);
+        let (events, notices) = interest.filter(&game, events);
+        let heard: Vec<(&str, Option<usize>, Option<usize>)> = events.iter()
+            .map(|event| (event.kind.get_name(), event.kind.get_snake_id(), event.kind.get_food_id()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("food_added", None, Some(near_food_id)),
+                ("snake_turned", Some(my_snake_id), None),
+                ("snake_turned", Some(near_snake_id), None),
+            ],
+            heard
+        );
+        match &notices[..] {
+            [Notice::FarSnakes { snakes }] => {
+                assert_eq!(vec![far_snake_id], snakes.iter().map(|snake| snake.snake_id).collect::<Vec<usize>>());
+            }
+            other => panic!("Expected the far snakes, got {:?}", other),
+        }
+
+        // heading right, my snake leaves the near snake and food behind and
+        // comes up on the others
+        game.advance_clock(20.0);
+        let events = _drain(&mut game);
+        let (events, notices) = interest.filter(&game, events);
+        assert_eq!(vec!["clock_advanced"], events.iter().map(|event| event.kind.get_name()).collect::<Vec<&str>>());
+        assert_eq!(
+            vec![
+                Notice::SnakeLeft { snake_id: near_snake_id },
+                Notice::SnakeEntered { snake: game.get_snake(far_snake_id).unwrap().snapshot(far_snake_id) },
+                Notice::FoodLeft { food_id: near_food_id },
+                Notice::FoodEntered { food: game._get_food(far_food_id).unwrap().snapshot(far_food_id) },
+            ],
+            notices
+        );
+
+        // now it's the other way round
+        game.turn_snake(near_snake_id, FRAC_PI_2, 20.0).unwrap();
+        game.turn_snake(far_snake_id, FRAC_PI_2, 20.0).unwrap();
+        let events = _drain(&mut game);
+        let (events, _notices) = interest.filter(&game, events);
+        assert_eq!(Some(far_snake_id), events[0].kind.get_snake_id());
+        assert_eq!(1, events.len());
+
+        // and the next far update is ten ticks on from the last
+        let mut far_updates = Vec::new();
+        for _tick in 0..10 {
+            game.advance_clock(0.1);
+            let events = _drain(&mut game);
+            let (_events, notices) = interest.filter(&game, events);
+            for notice in notices {
+                if let Notice::FarSnakes { snakes } = notice {
+                    far_updates.push((game.get_tick(), snakes.iter().map(|snake| snake.snake_id).collect::<Vec<usize>>()));
+                }
+            }
+        }
+        assert_eq!(vec![(10, vec![near_snake_id])], far_updates);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn t_notice_json() {
+        let notice = Notice::SnakeLeft { snake_id: 3 };
+        let json = serde_json::to_string(&notice).unwrap();
+        assert_eq!(r#"{"type":"snake_left","snake_id":3}"#, json);
+        assert_eq!(notice, serde_json::from_str(&json).unwrap());
+    }
+}
@@ -0,0 +1,486 @@
+pub mod journal {
+    // Append-only on-disk log of events, so they outlive the process.
+    //
+    // A journal is a directory of numbered segment files, journal-00000001.log and
+    // so on. Each segment starts with the magic bytes "SNKJ" and a version byte,
+    // then holds records framed as
+    //   payload length (u32 LE), CRC-32 of the payload (u32 LE), payload
+    // where the payload is the event's JSON, which keeps every value exact for replays.
+    // Hand a JournalWriter to a game's EventQueue::set_sink() to journal the game.
+    use crate::eventqueue::eventqueue::{Event, EventSink};
+    use std::fmt;
+    use std::fs::{self, File, OpenOptions};
+    use std::io::{self, Write};
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, Instant};
+
+    const SEGMENT_MAGIC: &[u8; 4] = b"SNKJ";
+    const SEGMENT_VERSION: u8 = 1;
+    const SEGMENT_HEADER_LEN: usize = 5;
+    const FRAME_HEADER_LEN: usize = 8;
+
+    // When appended records are forced out to the disk
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    pub enum SyncPolicy {
+        // leave it to the operating system
+        Never,
+        EveryRecord,
+        EveryRecords(usize),
+        // at the first append after this long since the last sync
+        Interval(Duration),
+    }
+
+    // When to close the current segment and start a new one
+    #[derive(Copy, Clone, PartialEq, Debug, Default)]
+    pub struct RotationPolicy {
+        pub max_bytes: Option<u64>,
+        pub max_age: Option<Duration>,
+    }
+
+    // A record cut short at the end of a segment, as a crash mid-write leaves it
+    #[derive(Clone, PartialEq, Debug)]
+    pub struct TornRecord {
+        pub segment: PathBuf,
+        pub offset: u64,
+    }
+
+    #[derive(Debug)]
+    pub enum JournalError {
+        Io(io::Error),
+        // not a journal segment, or a version this reader doesn't know
+        BadSegment(PathBuf),
+        // a damaged record with good records after it - not something a crash does
+        Corrupt { segment: PathBuf, offset: u64 },
+    }
+    impl std::error::Error for JournalError {}
+    impl fmt::Display for JournalError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                JournalError::Io(err) => write!(f, "Journal I/O error: {}", err),
+                JournalError::BadSegment(segment) => write!(f, "{} isn't a journal segment!", segment.display()),
+                JournalError::Corrupt { segment, offset } => {
+                    write!(f, "Corrupt journal record in {} at byte {}!", segment.display(), offset)
+                }
+            }
+        }
+    }
+    impl From<io::Error> for JournalError {
+        fn from(err: io::Error) -> Self {
+            JournalError::Io(err)
+        }
+    }
+
+    pub struct JournalWriter {
+        dir: PathBuf,
+        sync: SyncPolicy,
+        rotation: RotationPolicy,
+        file: File,
+        segment_number: u64,
+        segment_bytes: u64,
+        segment_opened: Instant,
+        unsynced_records: usize,
+        last_sync: Instant,
+    }
+
+    impl JournalWriter {
+        // Start a new segment after any already in dir, creating dir if need be.
+        // Earlier segments are left alone, torn tails and all.
+        pub fn open(dir: &Path, sync: SyncPolicy, rotation: RotationPolicy) -> io::Result<JournalWriter> {
+            fs::create_dir_all(dir)?;
+            let segment_number = list_segments(dir)?.last().map_or(1, |(number, _)| number + 1);
+            let file = create_segment(dir, segment_number)?;
+            Ok(JournalWriter {
+                dir: dir.to_path_buf(),
+                sync,
+                rotation,
+                file,
+                segment_number,
+                segment_bytes: SEGMENT_HEADER_LEN as u64,
+                segment_opened: Instant::now(),
+                unsynced_records: 0,
+                last_sync: Instant::now(),
+            })
+        }
+
+        pub fn append(&mut self, event: &Event) -> io::Result<()> {
+            let payload = serde_json::to_vec(event)?;
+            let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + payload.len());
+            frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            frame.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+            frame.extend_from_slice(&payload);
+
+            if self.needs_rotation(frame.len() as u64) {
+                self.rotate()?;
+            }
+            // one write per record, so a crash tears at most the last one
+            self.file.write_all(&frame)?;
+            self.segment_bytes += frame.len() as u64;
+            self.unsynced_records += 1;
+
+            let due = match self.sync {
+                SyncPolicy::Never => false,
+                SyncPolicy::EveryRecord => true,
+                SyncPolicy::EveryRecords(count) => self.unsynced_records >= count,
+                SyncPolicy::Interval(interval) => self.last_sync.elapsed() >= interval,
+            };
+            if due {
+                self.sync()?;
+            }
+            Ok(())
+        }
+
+        pub fn sync(&mut self) -> io::Result<()> {
+            self.file.sync_data()?;
+            self.unsynced_records = 0;
+            self.last_sync = Instant::now();
+            Ok(())
+        }
+
+        pub fn get_segment_path(&self) -> PathBuf {
+            segment_path(&self.dir, self.segment_number)
+        }
+
+        fn needs_rotation(&self, frame_len: u64) -> bool {
+            // a segment always gets at least one record, however big
+            if self.segment_bytes == SEGMENT_HEADER_LEN as u64 {
+                return false;
+            }
+            let too_big = self.rotation.max_bytes.is_some_and(|max_bytes| self.segment_bytes + frame_len > max_bytes);
+            let too_old = self.rotation.max_age.is_some_and(|max_age| self.segment_opened.elapsed() >= max_age);
+            too_big || too_old
+        }
+
+        fn rotate(&mut self) -> io::Result<()> {
+            // whatever the policy, a finished segment goes to disk in full
+            self.sync()?;
+            self.segment_number += 1;
+            self.file = create_segment(&self.dir, self.segment_number)?;
+            self.segment_bytes = SEGMENT_HEADER_LEN as u64;
+            self.segment_opened = Instant::now();
+            Ok(())
+        }
+    }
+
+    impl EventSink for JournalWriter {
+        fn write_event(&mut self, event: &Event) -> io::Result<()> {
+            self.append(event)
+        }
+    }
+
+    // Everything that could be read back from a journal
+    #[derive(Debug, Default)]
+    pub struct JournalContents {
+        pub events: Vec<Event>,
+        // records lost to crashes, which the reader skipped
+        pub torn_records: Vec<TornRecord>,
+    }
+
+    // Read every segment in dir in order. A bad record at the very end of a
+    // segment is what a crash leaves behind, so it is skipped and reported;
+    // a bad record anywhere else is an error.
+    pub fn read_journal(dir: &Path) -> Result<JournalContents, JournalError> {
+        let mut contents = JournalContents::default();
+        for (_number, segment) in list_segments(dir)? {
+            let bytes = fs::read(&segment)?;
+            if bytes.len() < SEGMENT_HEADER_LEN || &bytes[..4] != SEGMENT_MAGIC || bytes[4] != SEGMENT_VERSION {
+                // a crash before the header was written leaves an empty file
+                if bytes.len() < SEGMENT_HEADER_LEN && SEGMENT_MAGIC.starts_with(&bytes[..bytes.len().min(4)]) {
+                    contents.torn_records.push(TornRecord { segment, offset: 0 });
+                    continue;
+                }
+                return Err(JournalError::BadSegment(segment));
+            }
+
+            let mut pos = SEGMENT_HEADER_LEN;
+            while pos < bytes.len() {
+                match read_record(&bytes[pos..]) {
+                    Some((event, len)) => {
+                        contents.events.push(event);
+                        pos += len;
+                    }
+                    None => {
+                        if is_tail(&bytes[pos..]) {
+                            contents.torn_records.push(TornRecord { segment, offset: pos as u64 });
+                            break;
+                        }
+                        return Err(JournalError::Corrupt { segment, offset: pos as u64 });
+                    }
+                }
+            }
+        }
+        Ok(contents)
+    }
+
+    // The event in the record at the start of bytes and the record's length,
+    // or None if it's incomplete or damaged
+    fn read_record(bytes: &[u8]) -> Option<(Event, usize)> {
+        if bytes.len() < FRAME_HEADER_LEN {
+            return None;
+        }
+        let len = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+        let crc = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        let payload = bytes.get(FRAME_HEADER_LEN..FRAME_HEADER_LEN.checked_add(len)?)?;
+        if crc32fast::hash(payload) != crc {
+            return None;
+        }
+        let event = serde_json::from_slice(payload).ok()?;
+        Some((event, FRAME_HEADER_LEN + len))
+    }
+
+    // Whether a bad record runs to the end of the segment, i.e. nothing good comes after it
+    fn is_tail(bytes: &[u8]) -> bool {
+        if bytes.len() < FRAME_HEADER_LEN {
+            return true;
+        }
+        let len = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+        FRAME_HEADER_LEN.saturating_add(len) >= bytes.len()
+    }
+
+    fn segment_path(dir: &Path, segment_number: u64) -> PathBuf {
+        dir.join(format!("journal-{:08}.log", segment_number))
+    }
+
+    fn create_segment(dir: &Path, segment_number: u64) -> io::Result<File> {
+        let mut file = OpenOptions::new().write(true).create_new(true).open(segment_path(dir, segment_number))?;
+        let mut header = SEGMENT_MAGIC.to_vec();
+        header.push(SEGMENT_VERSION);
+        file.write_all(&header)?;
+        Ok(file)
+    }
+
+    fn list_segments(dir: &Path) -> io::Result<Vec<(u64, PathBuf)>> {
+        let mut segments = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let number = path.file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix("journal-"))
+                .and_then(|name| name.strip_suffix(".log"))
+                .and_then(|number| number.parse::<u64>().ok());
+            if let Some(number) = number {
+                segments.push((number, path));
+            }
+        }
+        segments.sort();
+        Ok(segments)
+    }
+
+    // A fresh, empty directory for a test to keep a journal in
+    fn _test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("snakegame-journal-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn _test_events(count: usize) -> Vec<Event> {
+        use crate::eventqueue::eventqueue::EventKind;
+        (0..count)
+            .map(|snake_id| Event {
+                kind: EventKind::SnakeTurned { snake_id, rad_relative: 0.1 * snake_id as f64 },
+                event_time: snake_id as f64 / 3.0,
+                sequence: snake_id as u64,
+                tick: snake_id as u64 / 4,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn t_append_and_read() {
+        let dir = _test_dir("append");
+        let events = _test_events(10);
+        {
+            let mut journal = JournalWriter::open(&dir, SyncPolicy::EveryRecord, RotationPolicy::default()).unwrap();
+            for event in events.iter() {
+                journal.append(event).unwrap();
+            }
+        }
+        let contents = read_journal(&dir).unwrap();
+        // values come back exactly, thirds and all
+        assert_eq!(events, contents.events);
+        assert!(contents.torn_records.is_empty());
+
+        // reopening carries on in a new segment after the old one
+        {
+            let mut journal = JournalWriter::open(&dir, SyncPolicy::Never, RotationPolicy::default()).unwrap();
+            assert!(journal.get_segment_path().ends_with("journal-00000002.log"));
+            journal.append(&events[0]).unwrap();
+        }
+        let contents = read_journal(&dir).unwrap();
+        assert_eq!(11, contents.events.len());
+        assert_eq!(events[0], contents.events[10]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn t_journal_a_game() {
+        use crate::game::game::{Game, GameConfig};
+        use std::f64::consts::PI;
+        let dir = _test_dir("game");
+        let config = GameConfig { food_lifetime: Some(2.0), ..GameConfig::default() };
+        let mut game = Game::new_seeded(config, 42);
+        let journal = JournalWriter::open(&dir, SyncPolicy::Never, RotationPolicy { max_bytes: Some(2000), max_age: None }).unwrap();
+        game.get_event_queue_mut().set_sink(Some(Box::new(journal)));
+        game.get_event_queue_mut().set_reorder_window(Some(0.5));
+
+        game.start();
+        let snake1_id = game.create_snake(0.0).unwrap();
+        game.add_food(0.0, 2.0, 0.0).unwrap();
+        for _i in 0..10 {
+            game.advance_clock(0.25);
+        }
+        game.turn_snake(snake1_id, PI / 2.0, 2.3).unwrap();
+        let snake2_id = game.create_team_snake(1, 2.5).unwrap();
+        game.boost_snake(snake2_id, 1.5, 2.5).unwrap();
+        game.rename_snake(snake2_id, "Kaa", 2.5).unwrap();
+        for _i in 0..20 {
+            game.advance_clock(0.1);
+        }
+        game.get_event_queue_mut().flush();
+        // dropping the journal closes its segment
+        drop(game.get_event_queue_mut().set_sink(None));
+
+        let contents = read_journal(&dir).unwrap();
+        assert!(contents.torn_records.is_empty());
+        assert!(list_segments(&dir).unwrap().len() > 1);
+        let replayed = Game::replay(config, game.get_seed(), &contents.events).expect("The journal should replay");
+        assert_eq!(game.snapshot().snakes, replayed.snapshot().snakes);
+        assert_eq!(Some("Kaa"), replayed.get_snake_name(snake2_id).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn t_sync_policies() {
+        let dir = _test_dir("sync");
+        let events = _test_events(5);
+        let mut journal = JournalWriter::open(&dir, SyncPolicy::EveryRecords(3), RotationPolicy::default()).unwrap();
+        journal.append(&events[0]).unwrap();
+        journal.append(&events[1]).unwrap();
+        assert_eq!(2, journal.unsynced_records);
+        journal.append(&events[2]).unwrap();
+        assert_eq!(0, journal.unsynced_records);
+
+        journal.sync = SyncPolicy::Interval(Duration::from_secs(3600));
+        journal.append(&events[3]).unwrap();
+        assert_eq!(1, journal.unsynced_records);
+        journal.sync = SyncPolicy::Interval(Duration::from_secs(0));
+        journal.append(&events[4]).unwrap();
+        assert_eq!(0, journal.unsynced_records);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn t_rotation() {
+        let dir = _test_dir("rotation");
+        let events = _test_events(20);
+        {
+            let rotation = RotationPolicy { max_bytes: Some(300), max_age: None };
+            let mut journal = JournalWriter::open(&dir, SyncPolicy::Never, rotation).unwrap();
+            for event in events.iter() {
+                journal.append(event).unwrap();
+            }
+        }
+        let segments = list_segments(&dir).unwrap();
+        assert!(segments.len() > 1);
+        for (_number, segment) in segments.iter() {
+            assert!(fs::metadata(segment).unwrap().len() <= 300);
+        }
+        // the segments read back in order as one stream
+        assert_eq!(events, read_journal(&dir).unwrap().events);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let dir = _test_dir("rotation-age");
+        {
+            let rotation = RotationPolicy { max_bytes: None, max_age: Some(Duration::from_secs(0)) };
+            let mut journal = JournalWriter::open(&dir, SyncPolicy::Never, rotation).unwrap();
+            for event in events[..3].iter() {
+                journal.append(event).unwrap();
+            }
+        }
+        assert_eq!(3, list_segments(&dir).unwrap().len());
+        assert_eq!(events[..3].to_vec(), read_journal(&dir).unwrap().events);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn t_torn_tail() {
+        let dir = _test_dir("torn");
+        let events = _test_events(3);
+        let segment = {
+            let mut journal = JournalWriter::open(&dir, SyncPolicy::EveryRecord, RotationPolicy::default()).unwrap();
+            for event in events.iter() {
+                journal.append(event).unwrap();
+            }
+            journal.get_segment_path()
+        };
+        let full_len = fs::metadata(&segment).unwrap().len();
+
+        // cut the last record off partway, as a crash would
+        let file = OpenOptions::new().write(true).open(&segment).unwrap();
+        file.set_len(full_len - 4).unwrap();
+        let contents = read_journal(&dir).unwrap();
+        assert_eq!(events[..2].to_vec(), contents.events);
+        assert_eq!(1, contents.torn_records.len());
+        assert_eq!(segment, contents.torn_records[0].segment);
+
+        // after a restart the torn segment is no longer the last, and still reads fine
+        {
+            let mut journal = JournalWriter::open(&dir, SyncPolicy::EveryRecord, RotationPolicy::default()).unwrap();
+            journal.append(&events[2]).unwrap();
+        }
+        let contents = read_journal(&dir).unwrap();
+        assert_eq!(events, contents.events);
+        assert_eq!(1, contents.torn_records.len());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn t_corrupt() {
+        let dir = _test_dir("corrupt");
+        let segment = {
+            let mut journal = JournalWriter::open(&dir, SyncPolicy::EveryRecord, RotationPolicy::default()).unwrap();
+            for event in _test_events(3).iter() {
+                journal.append(event).unwrap();
+            }
+            journal.get_segment_path()
+        };
+
+        // damage the first record, with good ones after it
+        let mut bytes = fs::read(&segment).unwrap();
+        bytes[SEGMENT_HEADER_LEN + FRAME_HEADER_LEN + 2] ^= 0xff;
+        fs::write(&segment, &bytes).unwrap();
+        match read_journal(&dir) {
+            Err(JournalError::Corrupt { offset, .. }) => assert_eq!(SEGMENT_HEADER_LEN as u64, offset),
+            other => panic!("Expected corruption, got {:?}", other),
+        }
+
+        // something that isn't a journal at all
+        fs::write(&segment, b"not a journal").unwrap();
+        assert!(matches!(read_journal(&dir), Err(JournalError::BadSegment(_))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn t_replay_from_journal() {
+        use crate::game::game::{Game, GameConfig};
+        let dir = _test_dir("replay");
+        let mut game = Game::new_seeded(GameConfig::default(), 7);
+        let snake_id = game.create_snake(0.0).unwrap();
+        game.add_food(0.0, 1.0, 0.0).unwrap();
+        for _i in 0..3 {
+            game.advance_clock(0.3);
+        }
+        game.turn_snake(snake_id, 1.0, 0.9).unwrap();
+        game.advance_clock(0.3);
+        {
+            let mut journal = JournalWriter::open(&dir, SyncPolicy::Never, RotationPolicy::default()).unwrap();
+            while let Some(event) = game.get_event_queue_mut().get_event() {
+                journal.append(&event).unwrap();
+            }
+        }
+
+        let contents = read_journal(&dir).unwrap();
+        let replayed = Game::replay(GameConfig::default(), 7, &contents.events).expect("Journal should replay");
+        assert_eq!(game.get_snake(snake_id).unwrap().get_location(), replayed.get_snake(snake_id).unwrap().get_location());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
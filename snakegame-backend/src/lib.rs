@@ -0,0 +1,32 @@
+#![allow(clippy::module_inception)]
+pub mod snake;
+pub mod food;
+pub mod game;
+pub mod eventqueue;
+pub mod eventfilter;
+pub mod team;
+pub mod rules;
+pub mod wire;
+pub mod replay;
+pub mod snapshot;
+pub mod delta;
+pub mod command;
+pub mod clocksync;
+pub mod interest;
+pub mod bot;
+pub mod lobby;
+#[cfg(feature = "serde")]
+pub mod journal;
+// the connection code in these passes along tungstenite's own big Result
+#[cfg(feature = "server")]
+#[allow(clippy::result_large_err)]
+pub mod server;
+#[cfg(feature = "server")]
+#[allow(clippy::result_large_err)]
+pub mod wamp;
+#[cfg(feature = "server")]
+pub mod http;
+#[cfg(feature = "server")]
+pub mod rest;
+#[cfg(feature = "server")]
+pub mod site;
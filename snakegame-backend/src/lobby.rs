@@ -0,0 +1,430 @@
+pub mod lobby {
+    // Many games side by side, each with its own config, clock and event queue.
+    //
+    // Joining players are seated in the fullest game that still has room, and a
+    // new game is started when there's none. A game nobody has been in for the
+    // idle timeout is closed, and there are never more than max_games at once.
+    // Times are passed in, so whoever drives the lobby decides what "now" is.
+    // Players' commands go through their game's CommandGuard, and players
+    // whose connections dropped rejoin by its session tokens; while they're
+    // gone, their snakes carry straight on or, with bots on, look for food.
+    use crate::bot::bot;
+    use crate::command::command::{CommandGuard, CommandLimits};
+    use crate::eventqueue::eventqueue::RetentionPolicy;
+    use crate::game::game::{BadConfig, Game, GameConfig};
+    use crate::interest::interest::InterestConfig;
+    use std::collections::BTreeMap;
+    use std::fmt;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    pub type Games = BTreeMap<usize, Arc<Mutex<Game>>>;
+
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    pub struct LobbyConfig {
+        // what games get unless they're created with a config of their own
+        pub game: GameConfig,
+        pub max_games: usize,
+        // players in any one game
+        pub max_players: usize,
+        // how long a game may stand empty before it's closed
+        pub idle_timeout: Duration,
+        // how many of the latest events each game keeps as its history
+        pub history_length: usize,
+        // what each game's players may ask of it
+        pub limits: CommandLimits,
+        // how much of their game players are sent, or None for all of it
+        pub interest: Option<InterestConfig>,
+        // whether bots steer the snakes of players who've dropped
+        pub bots: bool,
+    }
+    impl Default for LobbyConfig {
+        fn default() -> Self {
+            LobbyConfig {
+                game: GameConfig::default(),
+                max_games: 64,
+                max_players: 16,
+                idle_timeout: Duration::from_secs(60),
+                history_length: 10000,
+                limits: CommandLimits::default(),
+                interest: Some(InterestConfig::default()),
+                bots: false,
+            }
+        }
+    }
+
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    pub enum LobbyError {
+        NoSuchGame,
+        TooManyGames,
+        NoSuchSession,
+        BadConfig(BadConfig),
+    }
+    impl std::error::Error for LobbyError {}
+    impl fmt::Display for LobbyError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                LobbyError::NoSuchGame => write!(f, "No such game!"),
+                LobbyError::TooManyGames => write!(f, "Too many games!"),
+                LobbyError::NoSuchSession => write!(f, "No such session!"),
+                LobbyError::BadConfig(bad) => write!(f, "{}", bad),
+            }
+        }
+    }
+
+    // A game's time between ticks. The game's own clock only moves when it
+    // ticks; this one carries on from the last tick by the wall clock, which is
+    // right for a game ticking in real time, for players to set their clocks by.
+    pub struct GameClock {
+        // the game's time at its last tick, and when that was
+        last_tick: Mutex<(f64, Instant)>,
+    }
+    impl GameClock {
+        fn new(game_time: f64, now: Instant) -> GameClock {
+            GameClock { last_tick: Mutex::new((game_time, now)) }
+        }
+
+        fn set(&self, game_time: f64, now: Instant) {
+            *self.last_tick.lock().unwrap() = (game_time, now);
+        }
+
+        pub fn get_time(&self, now: Instant) -> f64 {
+            let (game_time, ticked) = *self.last_tick.lock().unwrap();
+            game_time + now.saturating_duration_since(ticked).as_secs_f64()
+        }
+    }
+
+    // A player's place in a game. open goes false when the game is ended, as a
+    // sign for the player to go.
+    pub struct Seat {
+        pub game_id: usize,
+        pub game: Arc<Mutex<Game>>,
+        // lock it before the game, when both are wanted
+        pub guard: Arc<Mutex<CommandGuard>>,
+        pub clock: Arc<GameClock>,
+        pub open: Arc<AtomicBool>,
+    }
+
+    struct Arena {
+        game: Arc<Mutex<Game>>,
+        guard: Arc<Mutex<CommandGuard>>,
+        clock: Arc<GameClock>,
+        open: Arc<AtomicBool>,
+        players: usize,
+        // since when nobody has been playing, if nobody is
+        empty_since: Option<Instant>,
+    }
+
+    pub struct GameManager {
+        config: LobbyConfig,
+        arenas: BTreeMap<usize, Arena>,
+        next_game_id: usize,
+    }
+
+    impl GameManager {
+        pub fn new(config: LobbyConfig) -> GameManager {
+            GameManager {
+                config,
+                arenas: BTreeMap::new(),
+                next_game_id: 0,
+            }
+        }
+
+        pub fn get_config(&self) -> LobbyConfig {
+            self.config
+        }
+
+        // Start a new game, empty as of now
+        pub fn create_game(&mut self, config: GameConfig, now: Instant) -> Result<usize, LobbyError> {
+            config.check().map_err(LobbyError::BadConfig)?;
+            if self.arenas.len() >= self.config.max_games {
+                return Err(LobbyError::TooManyGames);
+            }
+            let mut game = Game::new_with_config(config);
+            // Nobody reads for the primary subscriber, so the queue holds on to
+            // the latest events as the game's history.
+            game.get_event_queue_mut().set_retention_policy(RetentionPolicy {
+                max_backlog: Some(self.config.history_length),
+                ..RetentionPolicy::default()
+            });
+            game.start();
+            let clock = GameClock::new(game.get_time(), now);
+            let game_id = self.next_game_id;
+            self.next_game_id += 1;
+            self.arenas.insert(game_id, Arena {
+                game: Arc::new(Mutex::new(game)),
+                guard: Arc::new(Mutex::new(CommandGuard::new(self.config.limits))),
+                clock: Arc::new(clock),
+                open: Arc::new(AtomicBool::new(true)),
+                players: 0,
+                empty_since: Some(now),
+            });
+            Ok(game_id)
+        }
+
+        // Take the game out of the lobby and tell its players it's over
+        pub fn end_game(&mut self, game_id: usize) -> Result<(), LobbyError> {
+            let arena = self.arenas.remove(&game_id).ok_or(LobbyError::NoSuchGame)?;
+            arena.game.lock().unwrap().end();
+            arena.open.store(false, Ordering::SeqCst);
+            Ok(())
+        }
+
+        pub fn end_all_games(&mut self) {
+            let game_ids: Vec<usize> = self.arenas.keys().cloned().collect();
+            for game_id in game_ids {
+                let _ = self.end_game(game_id);
+            }
+        }
+
+        pub fn get_game(&self, game_id: usize) -> Result<Arc<Mutex<Game>>, LobbyError> {
+            self.arenas.get(&game_id).map(|arena| arena.game.clone()).ok_or(LobbyError::NoSuchGame)
+        }
+
+        pub fn get_guard(&self, game_id: usize) -> Result<Arc<Mutex<CommandGuard>>, LobbyError> {
+            self.arenas.get(&game_id).map(|arena| arena.guard.clone()).ok_or(LobbyError::NoSuchGame)
+        }
+
+        pub fn get_games(&self) -> Games {
+            self.arenas.iter().map(|(game_id, arena)| (*game_id, arena.game.clone())).collect()
+        }
+
+        pub fn get_game_count(&self) -> usize {
+            self.arenas.len()
+        }
+
+        pub fn get_player_count(&self, game_id: usize) -> Result<usize, LobbyError> {
+            self.arenas.get(&game_id).map(|arena| arena.players).ok_or(LobbyError::NoSuchGame)
+        }
+
+        // A seat in the fullest game with room, or in a new game if they're all full
+        pub fn join(&mut self, now: Instant) -> Result<Seat, LobbyError> {
+            let max_players = self.config.max_players;
+            // the first of the fullest, so games fill up in the order they started
+            let with_room = self.arenas.iter()
+                .filter(|(_, arena)| arena.players < max_players)
+                .max_by_key(|(game_id, arena)| (arena.players, std::cmp::Reverse(**game_id)))
+                .map(|(game_id, _)| *game_id);
+            let game_id = match with_room {
+                Some(game_id) => game_id,
+                None => self.create_game(self.config.game, now)?,
+            };
+            Ok(self.take_seat(game_id))
+        }
+
+        // Back to the game the session token is for, with the seat, the player
+        // and their new connection. Someone may have taken their seat in the
+        // meantime, so they can put the game over max_players.
+        pub fn rejoin(&mut self, token: &str) -> Result<(Seat, usize, usize), LobbyError> {
+            let found = self.arenas.iter()
+                .find_map(|(game_id, arena)| arena.guard.lock().unwrap().reconnect(token).ok().map(|reconnected| (*game_id, reconnected)));
+            let (game_id, (player_id, connection)) = found.ok_or(LobbyError::NoSuchSession)?;
+            Ok((self.take_seat(game_id), player_id, connection))
+        }
+
+        fn take_seat(&mut self, game_id: usize) -> Seat {
+            let arena = self.arenas.get_mut(&game_id).expect("The game was just found");
+            arena.players += 1;
+            arena.empty_since = None;
+            Seat {
+                game_id,
+                game: arena.game.clone(),
+                guard: arena.guard.clone(),
+                clock: arena.clock.clone(),
+                open: arena.open.clone(),
+            }
+        }
+
+        // A player giving up their seat. The game may have been ended already.
+        pub fn leave(&mut self, game_id: usize, now: Instant) {
+            if let Some(arena) = self.arenas.get_mut(&game_id) {
+                arena.players = arena.players.saturating_sub(1);
+                if arena.players == 0 {
+                    arena.empty_since = Some(now);
+                }
+            }
+        }
+
+        // End the games that have stood empty for the idle timeout, returning their ids
+        pub fn close_idle_games(&mut self, now: Instant) -> Vec<usize> {
+            let idle_timeout = self.config.idle_timeout;
+            let idle: Vec<usize> = self.arenas.iter()
+                .filter(|(_, arena)| match arena.empty_since {
+                    Some(empty_since) => now.saturating_duration_since(empty_since) >= idle_timeout,
+                    None => false,
+                })
+                .map(|(game_id, _)| *game_id)
+                .collect();
+            for game_id in idle.iter() {
+                let _ = self.end_game(*game_id);
+            }
+            idle
+        }
+
+        // Make each game's pending turns, let go of the players who didn't come
+        // back, then move it on, as of now
+        pub fn advance_clocks(&mut self, delta_t: f64, now: Instant) {
+            let limits = self.config.limits;
+            for arena in self.arenas.values() {
+                let mut guard = arena.guard.lock().unwrap();
+                let mut game = arena.game.lock().unwrap();
+                guard.flush(&mut game);
+                let game_time = game.get_time();
+                for snake_id in guard.expire_sessions(game_time) {
+                    // it may already have been killed
+                    let _ = game.remove_snake(snake_id, game_time);
+                }
+                if self.config.bots {
+                    // no better at turning than a player
+                    let max_turn = limits.max_turn.min(limits.max_turn_rate * delta_t);
+                    for snake_id in guard.get_dropped_snake_ids() {
+                        if let Some(turn) = bot::steer(&game, snake_id, max_turn) {
+                            // with the queue full, it carries straight on for now
+                            let _ = game.turn_snake(snake_id, turn, game_time);
+                        }
+                    }
+                }
+                game.advance_clock(delta_t);
+                arena.clock.set(game.get_time(), now);
+            }
+        }
+    }
+
+    #[test]
+    fn t_create_and_end() {
+        let now = Instant::now();
+        let mut lobby = GameManager::new(LobbyConfig { max_games: 2, ..LobbyConfig::default() });
+        let small = GameConfig { size: 10.0, ..GameConfig::default() };
+        let game1_id = lobby.create_game(small, now).unwrap();
+        let game2_id = lobby.create_game(GameConfig::default(), now).unwrap();
+        assert_eq!(Err(LobbyError::TooManyGames), lobby.create_game(GameConfig::default(), now));
+        lobby.end_game(game2_id).unwrap();
+        let nowhere = GameConfig { size: 0.0, ..GameConfig::default() };
+        assert_eq!(Err(LobbyError::BadConfig(BadConfig("size must be above 0"))), lobby.create_game(nowhere, now));
+        let game2_id = lobby.create_game(GameConfig::default(), now).unwrap();
+        assert_eq!(vec![game1_id, game2_id], lobby.get_games().keys().cloned().collect::<Vec<usize>>());
+        assert_eq!(10.0, lobby.get_game(game1_id).unwrap().lock().unwrap().get_size());
+        assert_eq!(100.0, lobby.get_game(game2_id).unwrap().lock().unwrap().get_size());
+
+        // each game keeps its own clock
+        lobby.get_game(game1_id).unwrap().lock().unwrap().advance_clock(1.0);
+        lobby.advance_clocks(0.5, now);
+        assert_eq!(1.5, lobby.get_game(game1_id).unwrap().lock().unwrap().get_time());
+        assert_eq!(0.5, lobby.get_game(game2_id).unwrap().lock().unwrap().get_time());
+        // and between ticks, the wall clock carries it on
+        let seat = lobby.join(now).unwrap();
+        assert_eq!(game1_id, seat.game_id);
+        assert_eq!(1.75, seat.clock.get_time(now + Duration::from_millis(250)));
+        lobby.leave(seat.game_id, now);
+
+        let game = lobby.get_game(game1_id).unwrap();
+        assert!(lobby.end_game(game1_id).is_ok());
+        assert_eq!(Err(LobbyError::NoSuchGame), lobby.end_game(game1_id));
+        assert!(lobby.get_game(game1_id).is_err());
+        assert_eq!(1, lobby.get_game_count());
+        // its players hear that it's over
+        let mut game = game.lock().unwrap();
+        let mut last = None;
+        while let Some(event) = game.get_event_queue_mut().get_event() {
+            last = Some(event.kind);
+        }
+        assert_eq!(Some(crate::eventqueue::eventqueue::EventKind::GameEnded { winner: None }), last);
+
+        // ids aren't reused
+        assert_eq!(Ok(game2_id + 1), lobby.create_game(GameConfig::default(), now));
+    }
+
+    #[test]
+    fn t_join() {
+        let now = Instant::now();
+        let mut lobby = GameManager::new(LobbyConfig { max_games: 2, max_players: 2, ..LobbyConfig::default() });
+        let seat1 = lobby.join(now).unwrap();
+        let seat2 = lobby.join(now).unwrap();
+        assert_eq!(seat1.game_id, seat2.game_id);
+        assert_eq!(Ok(2), lobby.get_player_count(seat1.game_id));
+
+        // the first game is full, so there's a second
+        let seat3 = lobby.join(now).unwrap();
+        assert_ne!(seat1.game_id, seat3.game_id);
+        lobby.join(now).unwrap();
+        assert!(matches!(lobby.join(now), Err(LobbyError::TooManyGames)));
+
+        // a seat comes free, and the next player gets it
+        lobby.leave(seat1.game_id, now);
+        assert_eq!(seat1.game_id, lobby.join(now).unwrap().game_id);
+
+        // the players in an ended game are told to go
+        assert!(seat2.open.load(Ordering::SeqCst));
+        lobby.end_game(seat2.game_id).unwrap();
+        assert!(!seat2.open.load(Ordering::SeqCst));
+        assert!(seat3.open.load(Ordering::SeqCst));
+        lobby.leave(seat2.game_id, now);
+    }
+
+    #[test]
+    fn t_close_idle_games() {
+        let start = Instant::now();
+        let idle_timeout = Duration::from_secs(60);
+        let mut lobby = GameManager::new(LobbyConfig { idle_timeout, ..LobbyConfig::default() });
+        let unplayed_id = lobby.create_game(GameConfig::default(), start).unwrap();
+        let seat = lobby.join(start).unwrap();
+        assert_eq!(unplayed_id, seat.game_id);
+        let other_id = lobby.create_game(GameConfig::default(), start).unwrap();
+
+        // nobody ever joined the other game
+        assert_eq!(Vec::<usize>::new(), lobby.close_idle_games(start + idle_timeout / 2));
+        assert_eq!(vec![other_id], lobby.close_idle_games(start + idle_timeout));
+
+        // a game with a player stays however long it goes on
+        assert_eq!(Vec::<usize>::new(), lobby.close_idle_games(start + idle_timeout * 10));
+        let left = start + idle_timeout * 10;
+        lobby.leave(seat.game_id, left);
+        assert_eq!(Vec::<usize>::new(), lobby.close_idle_games(left + idle_timeout / 2));
+        assert_eq!(vec![seat.game_id], lobby.close_idle_games(left + idle_timeout));
+        assert_eq!(0, lobby.get_game_count());
+    }
+
+    #[test]
+    fn t_rejoin() {
+        use crate::command::command::CommandLimits;
+        let now = Instant::now();
+        let limits = CommandLimits { reconnect_grace: 1.0, ..CommandLimits::default() };
+        let mut lobby = GameManager::new(LobbyConfig { max_players: 1, limits, bots: true, ..LobbyConfig::default() });
+        let seat = lobby.join(now).unwrap();
+        let (player_id, snake_id, token) = {
+            let mut guard = seat.guard.lock().unwrap();
+            let mut game = seat.game.lock().unwrap();
+            let player_id = guard.add_player();
+            let snake_id = guard.create_snake(&mut game, player_id).unwrap();
+            // something to the snake's right, if there was nothing nearer
+            game.add_food(1.0, 0.0, 0.0).unwrap();
+            (player_id, snake_id, guard.get_token(player_id).unwrap())
+        };
+
+        // the player drops, and a bot steers for them...
+        let velocity = seat.game.lock().unwrap().get_snake(snake_id).unwrap().get_velocity();
+        assert!(seat.guard.lock().unwrap().drop_connection(player_id, 0, 0.0).is_empty());
+        lobby.leave(seat.game_id, now);
+        lobby.advance_clocks(0.1, now);
+        assert_ne!(velocity, seat.game.lock().unwrap().get_snake(snake_id).unwrap().get_velocity());
+
+        // ...until they're back, even if someone's had their seat
+        let other_seat = lobby.join(now).unwrap();
+        assert_eq!(seat.game_id, other_seat.game_id);
+        assert!(matches!(lobby.rejoin("guess"), Err(LobbyError::NoSuchSession)));
+        let (seat, rejoined_id, connection) = lobby.rejoin(&token).unwrap();
+        assert_eq!((player_id, 1), (rejoined_id, connection));
+        assert_eq!(Ok(2), lobby.get_player_count(seat.game_id));
+
+        // and if they don't come back in time, their snake goes
+        assert!(seat.guard.lock().unwrap().drop_connection(player_id, connection, 0.1).is_empty());
+        lobby.leave(seat.game_id, now);
+        lobby.advance_clocks(0.5, now);
+        assert!(seat.game.lock().unwrap().get_snake(snake_id).is_ok());
+        lobby.advance_clocks(0.5, now);
+        lobby.advance_clocks(0.5, now);
+        assert!(seat.game.lock().unwrap().get_snake(snake_id).is_err());
+        assert!(matches!(lobby.rejoin(&token), Err(LobbyError::NoSuchSession)));
+    }
+}
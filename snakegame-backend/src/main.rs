@@ -1,10 +1,6 @@
-mod snake;
-mod food;
-mod game;
-mod eventqueue;
-use crate::game::game::Game;
-use crate::eventqueue::eventqueue::EventQueue;
-const PI:f64 = 3.14159;
+use snakegame_backend::game::game::Game;
+use snakegame_backend::eventqueue::eventqueue::EventQueue;
+use std::f64::consts::PI;
 
 fn print_event_log (event_queue: &mut EventQueue) {
     loop {
@@ -15,11 +11,49 @@ fn print_event_log (event_queue: &mut EventQueue) {
 
 }
 
+// `snakegame-backend serve [address] [site directory] [version file]` plays over
+// WebSockets and serves the website alongside, anything else runs the demo.
+// The REST API only changes games for whoever has SNAKEGAME_ADMIN_TOKEN.
+#[cfg(feature = "server")]
+fn serve(addr: &str, site_dir: &str, version_file: &str) {
+    use snakegame_backend::server::server::{Server, ServerConfig};
+    use snakegame_backend::site::site::Site;
+    let admin_token = std::env::var("SNAKEGAME_ADMIN_TOKEN").ok().filter(|token| !token.is_empty());
+    if admin_token.is_none() {
+        println!("No SNAKEGAME_ADMIN_TOKEN, so the games can only be looked at over the API");
+    }
+    let config = ServerConfig { admin_token, ..ServerConfig::default() };
+    let mut server = Server::bind(addr, config).expect("Can't listen!");
+    if std::path::Path::new(site_dir).is_dir() {
+        let version = std::fs::read_to_string(version_file).expect("Can't read the version!");
+        let site = Site::load(std::path::Path::new(site_dir), &version).expect("Can't load the site!");
+        server = server.with_site(site);
+        println!("Serving {} for {}", site_dir, version.trim());
+    } else {
+        println!("No site at {}, so only the game", site_dir);
+    }
+    println!("Serving on {}", server.local_addr().expect("Can't listen!"));
+    server.run().expect("Server stopped!");
+}
+
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("serve") {
+        #[cfg(feature = "server")]
+        serve(
+            args.get(2).map(String::as_str).unwrap_or("0.0.0.0:8080"),
+            args.get(3).map(String::as_str).unwrap_or("public-html"),
+            args.get(4).map(String::as_str).unwrap_or("VERSION"),
+        );
+        #[cfg(not(feature = "server"))]
+        eprintln!("Built without the server feature!");
+        return;
+    }
+
     let mut my_game = Game::new();
-    let snake_id = my_game.create_snake(0.0);
-    my_game.add_food(0.0, 1.0, 0.0);
-    my_game.add_food(-0.7, 1.7, 0.0);
+    let snake_id = my_game.create_snake(0.0).unwrap();
+    my_game.add_food(0.0, 1.0, 0.0).unwrap();
+    my_game.add_food(-0.7, 1.7, 0.0).unwrap();
 
     print_event_log(my_game.get_event_queue_mut());
 
@@ -0,0 +1,296 @@
+pub mod replay {
+    use crate::eventqueue::eventqueue::{Event, EventKind, PRIMARY_SUBSCRIBER};
+    use crate::game::game::{Game, GameConfig};
+    use std::fmt;
+
+    // The first place a replay didn't go the way the recording did
+    #[derive(Debug, PartialEq)]
+    pub struct ReplayMismatch {
+        // position of the offending event in the recording
+        pub index: usize,
+        // what the recording says happened next (None if the recording had ended)
+        pub recorded: Option<Event>,
+        // what happened next in the replay (None if nothing did)
+        pub replayed: Option<Event>,
+        // the last recorded event that did match, if any
+        pub last_match: Option<Event>,
+        pub game_time: f64,
+    }
+    impl std::error::Error for ReplayMismatch {}
+    impl fmt::Display for ReplayMismatch {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            fn describe(event: &Option<Event>) -> String {
+                match event {
+                    Some(event) => format!("'{}'", event),
+                    None => "nothing".to_string(),
+                }
+            }
+            write!(
+                f,
+                "Replay diverged at event {} (game time {:.3}): recorded {}, replay produced {}, after {}",
+                self.index,
+                self.game_time,
+                describe(&self.recorded),
+                describe(&self.replayed),
+                describe(&self.last_match)
+            )
+        }
+    }
+
+    // the replay's own queue has no reorder window or backlog limit
+    const REPLAY_QUEUE: &str = "A replay's event queue takes everything";
+
+    impl Game {
+        // Play a recorded game again from its seed and config, checking that it
+        // turns out the same. Births, turns, boosts, renames, food, removals,
+        // clock advances and the start in the recording drive the replay;
+        // everything the game works out for itself (starting food, meals, expired
+        // food, collisions, deaths, the result) has to come out exactly as recorded.
+        // The recording must be the game's complete event log, at full precision.
+        pub fn replay(config: GameConfig, seed: u64, events: &[Event]) -> Result<Game, Box<ReplayMismatch>> {
+            let mut game = Game::new_seeded(config, seed);
+            let mut last_match = None;
+            for (index, recorded) in events.iter().enumerate() {
+                // anything the replay already has queued up must be what's recorded next,
+                // so only drive the game with inputs once it has caught up
+                if game.get_event_queue_mut().get_unread_count(PRIMARY_SUBSCRIBER) == 0 {
+                    match recorded.kind {
+                        EventKind::SnakeBorn { team: Some(team_id), .. } => {
+                            game.create_team_snake(team_id, recorded.event_time).expect(REPLAY_QUEUE);
+                        }
+                        EventKind::SnakeBorn { team: None, .. } => {
+                            game.create_snake(recorded.event_time).expect(REPLAY_QUEUE);
+                        }
+                        EventKind::SnakeTurned { snake_id, rad_relative } => {
+                            // a turn for a snake the replay doesn't have shows up as a
+                            // missing SnakeTurned below
+                            let _ = game.turn_snake(snake_id, rad_relative, recorded.event_time);
+                        }
+                        EventKind::SnakeBoosted { snake_id, speed } => {
+                            let _ = game.boost_snake(snake_id, speed, recorded.event_time);
+                        }
+                        EventKind::SnakeRenamed { snake_id, ref name } => {
+                            let _ = game.rename_snake(snake_id, name, recorded.event_time);
+                        }
+                        EventKind::FoodAdded { loc, .. } => {
+                            game.add_food(loc.x, loc.y, recorded.event_time).expect(REPLAY_QUEUE);
+                        }
+                        EventKind::ClockAdvanced { delta_t } => {
+                            game.advance_clock(delta_t);
+                        }
+                        EventKind::GameStarted => {
+                            game.start();
+                        }
+                        EventKind::SnakeRemoved { snake_id } => {
+                            // like a turn, a snake the replay doesn't have shows up
+                            // as a missing SnakeRemoved
+                            let _ = game.remove_snake(snake_id, recorded.event_time);
+                        }
+                        // the game decides these for itself
+                        EventKind::SnakeAte { .. } | EventKind::SnakeKilled { .. } | EventKind::StateSnapshot { .. } |
+                        EventKind::FoodRemoved { .. } | EventKind::SnakeGrew { .. } | EventKind::SnakeCollided { .. } |
+                        EventKind::ScoreChanged { .. } | EventKind::GameEnded { .. } => (),
+                    }
+                }
+
+                let replayed = game.get_event_queue_mut().get_event();
+                if replayed.as_ref() != Some(recorded) {
+                    return Err(Box::new(ReplayMismatch {
+                        index,
+                        recorded: Some(recorded.clone()),
+                        replayed,
+                        last_match,
+                        game_time: game.get_time(),
+                    }));
+                }
+                last_match = Some(recorded.clone());
+            }
+
+            // the replay mustn't have anything left over that the recording lacks
+            if let Some(replayed) = game.get_event_queue_mut().get_event() {
+                return Err(Box::new(ReplayMismatch {
+                    index: events.len(),
+                    recorded: None,
+                    replayed: Some(replayed),
+                    last_match,
+                    game_time: game.get_time(),
+                }));
+            }
+            Ok(game)
+        }
+    }
+
+    fn _play_recorded_game(config: GameConfig) -> (Game, Vec<Event>) {
+        use std::f64::consts::PI;
+        let mut game = Game::new_seeded(config, 42);
+        let snake1_id = game.create_snake(0.0).unwrap();
+        game.add_food(0.0, 2.0, 0.0).unwrap();
+        game.add_food(-3.0, 2.0, 0.0).unwrap();
+        for _i in 0..10 {
+            game.advance_clock(0.25);
+        }
+        game.turn_snake(snake1_id, PI / 2.0, 2.5).unwrap();
+        let snake2_id = game.create_team_snake(1, 2.5).unwrap();
+        game.turn_snake(snake2_id, PI, 2.5).unwrap();
+        for _i in 0..40 {
+            game.advance_clock(0.1);
+        }
+
+        let mut events = Vec::new();
+        while let Some(event) = game.get_event_queue_mut().get_event() {
+            events.push(event);
+        }
+        (game, events)
+    }
+
+    #[test]
+    fn t_replay() {
+        let (original, events) = _play_recorded_game(GameConfig::default());
+        // make sure there was something to check
+        assert!(events.iter().any(|event| matches!(event.kind, EventKind::SnakeAte { .. })));
+
+        let replayed = Game::replay(GameConfig::default(), original.get_seed(), &events).expect("Replay should match");
+        assert_eq!(original.get_time(), replayed.get_time());
+        assert_eq!(original._get_food_count(), replayed._get_food_count());
+        for snake_id in 0..2 {
+            let original_snake = original.get_snake(snake_id).unwrap();
+            let replayed_snake = replayed.get_snake(snake_id).unwrap();
+            assert_eq!(original_snake.get_location(), replayed_snake.get_location());
+            assert_eq!(original_snake.get_size(), replayed_snake.get_size());
+        }
+    }
+
+    #[test]
+    fn t_replay_removal() {
+        // a player leaving takes their snake out, and so does the replay
+        let (mut original, mut events) = _play_recorded_game(GameConfig::default());
+        let snake_id = original.get_snake_ids()[0];
+        let time = original.get_time();
+        original.remove_snake(snake_id, time).unwrap();
+        for _i in 0..5 {
+            original.advance_clock(0.1);
+        }
+        while let Some(event) = original.get_event_queue_mut().get_event() {
+            events.push(event);
+        }
+        assert!(events.iter().any(|event| event.kind == EventKind::SnakeRemoved { snake_id }));
+
+        let replayed = Game::replay(GameConfig::default(), original.get_seed(), &events).expect("Replay should match");
+        assert!(replayed.get_snake(snake_id).is_err());
+        assert_eq!(original.get_snake_ids(), replayed.get_snake_ids());
+    }
+
+    #[test]
+    fn t_replay_boosts_and_names() {
+        use crate::eventqueue::eventqueue::FoodRemovalReason;
+        // food that lies too long goes, a snake speeds up and gets a name
+        let config = GameConfig { food_lifetime: Some(3.0), ..GameConfig::default() };
+        let (mut original, mut events) = _play_recorded_game(config);
+        let snake_id = original.get_snake_ids()[0];
+        let time = original.get_time();
+        original.boost_snake(snake_id, 2.0, time).unwrap();
+        original.rename_snake(snake_id, "Kaa", time).unwrap();
+        for _i in 0..5 {
+            original.advance_clock(0.1);
+        }
+        while let Some(event) = original.get_event_queue_mut().get_event() {
+            events.push(event);
+        }
+        assert!(events.iter().any(|event| matches!(event.kind, EventKind::FoodRemoved { reason: FoodRemovalReason::Expired, .. })));
+        assert!(events.iter().any(|event| matches!(event.kind, EventKind::SnakeBoosted { .. })));
+
+        let replayed = Game::replay(config, original.get_seed(), &events).expect("Replay should match");
+        assert_eq!(Some("Kaa"), replayed.get_snake_name(snake_id).unwrap());
+        assert_eq!(2.0, replayed.get_snake(snake_id).unwrap().get_speed());
+        assert_eq!(original.snapshot(), replayed.snapshot());
+    }
+
+    #[test]
+    fn t_replay_reorder_window() {
+        use std::f64::consts::FRAC_PI_2;
+        // a turn from earlier in the window comes in after the clock has moved
+        // past it, with the snake already at the food
+        let mut original = Game::new_seeded(GameConfig::default(), 7);
+        original.get_event_queue_mut().set_reorder_window(Some(1.0));
+        let snake_id = original.create_snake(0.0).unwrap();
+        original.add_food(0.0, 0.4, 0.0).unwrap();
+        original.advance_clock(0.5);
+        original.turn_snake(snake_id, FRAC_PI_2, 0.2).unwrap();
+        for _i in 0..5 {
+            original.advance_clock(0.5);
+        }
+        original.get_event_queue_mut().flush();
+        let mut events = Vec::new();
+        while let Some(event) = original.get_event_queue_mut().get_event() {
+            events.push(event);
+        }
+        assert!(events.iter().any(|event| matches!(event.kind, EventKind::SnakeAte { .. })));
+
+        let replayed = Game::replay(GameConfig::default(), original.get_seed(), &events).expect("Replay should match");
+        assert_eq!(original.snapshot().snakes, replayed.snapshot().snakes);
+    }
+
+    #[test]
+    fn t_replay_grid() {
+        use crate::rules::rules::GameMode;
+        let config = GameConfig { mode: GameMode::classic_grid(), ..GameConfig::default() };
+        let (original, events) = _play_recorded_game(config);
+        assert!(Game::replay(config, original.get_seed(), &events).is_ok());
+    }
+
+    #[test]
+    fn t_replay_wrong_seed() {
+        let (original, events) = _play_recorded_game(GameConfig::default());
+        let mismatch = Game::replay(GameConfig::default(), original.get_seed() + 1, &events).err().expect("Replay should have diverged");
+        // the very first piece of starting food lands somewhere else
+        assert_eq!(0, mismatch.index);
+        assert_eq!(None, mismatch.last_match);
+        assert!(matches!(mismatch.replayed.unwrap().kind, EventKind::FoodAdded { .. }));
+    }
+
+    #[test]
+    fn t_replay_mismatch() {
+        let (original, mut events) = _play_recorded_game(GameConfig::default());
+
+        // pretend the snake ate something else
+        let meal_idx = events.iter().position(|event| matches!(event.kind, EventKind::SnakeAte { .. })).unwrap();
+        let real_meal = events[meal_idx].clone();
+        if let EventKind::SnakeAte { ref mut food_id, .. } = events[meal_idx].kind {
+            *food_id += 100;
+        }
+        let mismatch = Game::replay(GameConfig::default(), original.get_seed(), &events).err().expect("Replay should have diverged");
+        assert_eq!(meal_idx, mismatch.index);
+        assert_eq!(Some(events[meal_idx].clone()), mismatch.recorded);
+        assert_eq!(Some(real_meal.clone()), mismatch.replayed);
+        assert_eq!(Some(events[meal_idx - 1].clone()), mismatch.last_match);
+        assert_eq!(real_meal.event_time, mismatch.game_time);
+        assert!(format!("{}", mismatch).starts_with(&format!("Replay diverged at event {}", meal_idx)));
+
+        // or that it didn't eat at all - the replay eats anyway
+        events.remove(meal_idx);
+        let mismatch = Game::replay(GameConfig::default(), original.get_seed(), &events).err().expect("Replay should have diverged");
+        assert_eq!(meal_idx, mismatch.index);
+        assert_eq!(Some(real_meal), mismatch.replayed);
+
+        // a recording cut short of what the game went on to do
+        let (original, mut events) = _play_recorded_game(GameConfig::default());
+        let last_meal_idx = events.iter().rposition(|event| matches!(event.kind, EventKind::SnakeAte { .. })).unwrap();
+        events.truncate(last_meal_idx);
+        let mismatch = Game::replay(GameConfig::default(), original.get_seed(), &events).err().expect("Replay should have diverged");
+        assert_eq!(last_meal_idx, mismatch.index);
+        assert_eq!(None, mismatch.recorded);
+        assert!(matches!(mismatch.replayed.unwrap().kind, EventKind::SnakeAte { .. }));
+    }
+
+    #[test]
+    fn t_replay_physics_change() {
+        // a recording where the snake turned the other way doesn't add up
+        let (original, mut events) = _play_recorded_game(GameConfig::default());
+        let turn_idx = events.iter().position(|event| matches!(event.kind, EventKind::SnakeTurned { .. })).unwrap();
+        if let EventKind::SnakeTurned { ref mut rad_relative, .. } = events[turn_idx].kind {
+            *rad_relative = -*rad_relative;
+        }
+        let mismatch = Game::replay(GameConfig::default(), original.get_seed(), &events).err().expect("Replay should have diverged");
+        assert!(mismatch.index > turn_idx);
+    }
+}
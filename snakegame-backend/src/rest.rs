@@ -0,0 +1,436 @@
+pub mod rest {
+    // JSON over plain HTTP, for looking into games and looking after them.
+    //   GET    /api/games                             every game, in brief
+    //   GET    /api/games/{game_id}                   a GameSnapshot
+    //   GET    /api/games/{game_id}/snakes            the live snakes
+    //   GET    /api/games/{game_id}/snakes/{snake_id}
+    //   DELETE /api/games/{game_id}/snakes/{snake_id} take the snake out of the game
+    //   GET    /api/games/{game_id}/food
+    //   GET    /api/games/{game_id}/food/{food_id}
+    //   POST   /api/games/{game_id}/food              {"x":..,"y":..} adds food there
+    //   GET    /api/games/{game_id}/events?after=..&limit=..
+    // The event history is as much of the game's log as its queue still holds:
+    // {"oldest":..,"events":[..]}, where oldest is the sequence number of the
+    // oldest event held. Page through by passing the last sequence seen as after.
+    // Errors come back as {"error":".."} with a 4xx status.
+    //
+    // Anything but a GET changes a game, so a server only takes it with its
+    // admin token, sent as "Authorization: Bearer <token>". Without the right
+    // one it's a 401, and on a server that hasn't got a token, a 403.
+    //
+    // A server with a lobby also has
+    //   POST   /api/games                             a GameConfig, or nothing for the lobby's, starts a game
+    //   DELETE /api/games/{game_id}                   ends the game
+    //   GET    /api/games/{game_id}/security          the commands its CommandGuard turned away
+    use crate::game::game::{Game, GameConfig};
+    use crate::http::http::{HttpRequest, HttpResponse};
+    use crate::lobby::lobby::{GameManager, LobbyError};
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+    use std::sync::Mutex;
+    use std::time::Instant;
+
+    pub use crate::lobby::lobby::Games;
+
+    pub const DEFAULT_PAGE: usize = 100;
+    pub const MAX_PAGE: usize = 1000;
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    pub struct GameSummary {
+        pub game_id: usize,
+        pub time: f64,
+        pub tick: u64,
+        pub snakes: usize,
+        pub food: usize,
+    }
+
+    #[derive(Deserialize)]
+    struct NewFood {
+        x: f64,
+        y: f64,
+    }
+
+    // The response to a request for something under /api
+    pub fn respond(games: &Games, request: &HttpRequest) -> HttpResponse {
+        let segments = request.get_segments();
+        let method = request.method.as_str();
+        match segments.as_slice() {
+            ["api", "games"] => match method {
+                "GET" => list_games(games),
+                _ => not_allowed("GET"),
+            },
+            ["api", "games", game_id, rest @ ..] => {
+                let game = match parse_id(game_id).and_then(|game_id| games.get(&game_id)) {
+                    Some(game) => game,
+                    None => return HttpResponse::error(404, &format!("No game {}", game_id)),
+                };
+                let mut game = game.lock().unwrap();
+                respond_for_game(&mut game, method, rest, request)
+            }
+            _ => HttpResponse::error(404, &format!("Nothing at {}", request.path)),
+        }
+    }
+
+    // The response to a request for something under /api, on a server with a
+    // lobby and, if it takes changes to its games, an admin token
+    pub fn respond_for_lobby(lobby: &Mutex<GameManager>, request: &HttpRequest, admin_token: Option<&str>) -> HttpResponse {
+        if request.method != "GET" {
+            if let Err(refusal) = check_admin(request, admin_token) {
+                return refusal;
+            }
+        }
+        let segments = request.get_segments();
+        match (request.method.as_str(), segments.as_slice()) {
+            ("POST", ["api", "games"]) => {
+                let config = if request.body.is_empty() {
+                    lobby.lock().unwrap().get_config().game
+                } else {
+                    match serde_json::from_slice::<GameConfig>(&request.body) {
+                        Ok(config) => config,
+                        Err(error) => return HttpResponse::error(400, &format!("Expected a game config: {}", error)),
+                    }
+                };
+                match lobby.lock().unwrap().create_game(config, Instant::now()) {
+                    Ok(game_id) => HttpResponse::json(201, &json!({ "game_id": game_id }))
+                        .with_header("Location", &format!("{}/{}", request.path.trim_end_matches('/'), game_id)),
+                    Err(error @ LobbyError::BadConfig(_)) => HttpResponse::error(400, &error.to_string()),
+                    Err(error) => HttpResponse::error(503, &error.to_string()),
+                }
+            }
+            ("DELETE", ["api", "games", game_id]) => {
+                match parse_id(game_id).map(|game_id| lobby.lock().unwrap().end_game(game_id)) {
+                    Some(Ok(())) => HttpResponse::empty(204),
+                    _ => HttpResponse::error(404, &format!("No game {}", game_id)),
+                }
+            }
+            ("GET", ["api", "games", game_id, "security"]) => {
+                match parse_id(game_id).and_then(|game_id| lobby.lock().unwrap().get_guard(game_id).ok()) {
+                    Some(guard) => HttpResponse::json(200, &guard.lock().unwrap().get_security_log()),
+                    None => HttpResponse::error(404, &format!("No game {}", game_id)),
+                }
+            }
+            (_, ["api", "games", _, "security"]) => not_allowed("GET"),
+            (method, ["api", "games"]) if method != "GET" => not_allowed("GET, POST"),
+            (method, ["api", "games", _]) if method != "GET" => not_allowed("GET, DELETE"),
+            _ => {
+                // the games as they are now; the lobby isn't held up while they answer
+                let games = lobby.lock().unwrap().get_games();
+                respond(&games, request)
+            }
+        }
+    }
+
+    fn respond_for_game(game: &mut Game, method: &str, segments: &[&str], request: &HttpRequest) -> HttpResponse {
+        match (method, segments) {
+            ("GET", []) => HttpResponse::json(200, &game.snapshot()),
+            ("GET", ["snakes"]) => HttpResponse::json(200, &game.snapshot().snakes),
+            ("GET", ["snakes", snake_id]) => match parse_id(snake_id) {
+                Some(snake_id) => match game.get_snake(snake_id) {
+                    Ok(the_snake) => HttpResponse::json(200, &the_snake.snapshot(snake_id)),
+                    Err(_) => HttpResponse::error(404, &format!("No snake {}", snake_id)),
+                },
+                None => HttpResponse::error(404, &format!("No snake {}", snake_id)),
+            },
+            ("DELETE", ["snakes", snake_id]) => {
+                let event_time = game.get_time();
+                match parse_id(snake_id).map(|snake_id| game.remove_snake(snake_id, event_time)) {
+                    Some(Ok(())) => HttpResponse::empty(204),
+                    _ => HttpResponse::error(404, &format!("No snake {}", snake_id)),
+                }
+            }
+            ("GET", ["food"]) => HttpResponse::json(200, &game.snapshot().food),
+            ("GET", ["food", food_id]) => match parse_id(food_id) {
+                Some(food_id) => match game._get_food(food_id) {
+                    Ok(the_food) => HttpResponse::json(200, &the_food.snapshot(food_id)),
+                    Err(_) => HttpResponse::error(404, &format!("No food {}", food_id)),
+                },
+                None => HttpResponse::error(404, &format!("No food {}", food_id)),
+            },
+            ("POST", ["food"]) => match serde_json::from_slice::<NewFood>(&request.body) {
+                Ok(new_food) => {
+                    let event_time = game.get_time();
+                    match game.add_food(new_food.x, new_food.y, event_time) {
+                        Ok(food_id) => HttpResponse::json(201, &json!({ "food_id": food_id }))
+                            .with_header("Location", &format!("{}/{}", request.path.trim_end_matches('/'), food_id)),
+                        // the game's event queue is full
+                        Err(error) => HttpResponse::error(503, &error.to_string()),
+                    }
+                }
+                Err(error) => HttpResponse::error(400, &format!("Expected {{\"x\":..,\"y\":..}}: {}", error)),
+            },
+            ("GET", ["events"]) => event_history(game, request),
+            (_, []) | (_, ["snakes"]) | (_, ["food", _]) | (_, ["events"]) => not_allowed("GET"),
+            (_, ["snakes", _]) => not_allowed("GET, DELETE"),
+            (_, ["food"]) => not_allowed("GET, POST"),
+            _ => HttpResponse::error(404, &format!("Nothing at {}", request.path)),
+        }
+    }
+
+    fn list_games(games: &Games) -> HttpResponse {
+        let summaries: Vec<GameSummary> = games.iter()
+            .map(|(game_id, game)| {
+                let snapshot = game.lock().unwrap().snapshot();
+                GameSummary {
+                    game_id: *game_id,
+                    time: snapshot.time,
+                    tick: snapshot.tick,
+                    snakes: snapshot.snakes.len(),
+                    food: snapshot.food.len(),
+                }
+            })
+            .collect();
+        HttpResponse::json(200, &summaries)
+    }
+
+    fn event_history(game: &mut Game, request: &HttpRequest) -> HttpResponse {
+        let after = match request.get_query("after") {
+            Some(after) => match after.parse::<u64>() {
+                Ok(after) => Some(after),
+                Err(_) => return HttpResponse::error(400, "after must be a sequence number"),
+            },
+            None => None,
+        };
+        let limit = match request.get_query("limit") {
+            Some(limit) => match limit.parse::<usize>() {
+                Ok(limit) => limit.min(MAX_PAGE),
+                Err(_) => return HttpResponse::error(400, "limit must be a number"),
+            },
+            None => DEFAULT_PAGE,
+        };
+        let eventqueue = game.get_event_queue_mut();
+        HttpResponse::json(200, &json!({
+            "oldest": eventqueue.get_oldest_held_sequence(),
+            "events": eventqueue.get_held_events(after, limit),
+        }))
+    }
+
+    fn check_admin(request: &HttpRequest, admin_token: Option<&str>) -> Result<(), HttpResponse> {
+        let admin_token = match admin_token {
+            Some(admin_token) => admin_token,
+            None => return Err(HttpResponse::error(403, "This server's games can't be changed over the API")),
+        };
+        let sent = request.get_header("Authorization").and_then(|value| value.strip_prefix("Bearer "));
+        match sent {
+            Some(sent) if same_token(sent, admin_token) => Ok(()),
+            _ => Err(HttpResponse::error(401, "Expected the admin token").with_header("WWW-Authenticate", "Bearer")),
+        }
+    }
+
+    // Compared all the way through whatever the first difference, so the time
+    // it takes doesn't give away how much of a guess was right
+    fn same_token(sent: &str, admin_token: &str) -> bool {
+        sent.len() == admin_token.len()
+            && sent.bytes().zip(admin_token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+    }
+
+    fn parse_id(id: &str) -> Option<usize> {
+        id.parse().ok()
+    }
+
+    fn not_allowed(allow: &str) -> HttpResponse {
+        HttpResponse::error(405, "Method not allowed").with_header("Allow", allow)
+    }
+
+    fn _request(method: &str, path: &str, body: &str) -> HttpRequest {
+        let (path, query) = match path.find('?') {
+            Some(idx) => (&path[..idx], &path[idx + 1..]),
+            None => (path, ""),
+        };
+        HttpRequest {
+            method: method.to_string(),
+            path: path.to_string(),
+            query: query.split('&')
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| {
+                    let mut parts = pair.splitn(2, '=');
+                    (parts.next().unwrap().to_string(), parts.next().unwrap_or("").to_string())
+                })
+                .collect(),
+            headers: Vec::new(),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    const _ADMIN_TOKEN: &str = "let-me-in";
+
+    fn _as_admin(mut request: HttpRequest) -> HttpRequest {
+        request.headers.push(("Authorization".to_string(), format!("Bearer {}", _ADMIN_TOKEN)));
+        request
+    }
+
+    fn _body(response: &HttpResponse) -> serde_json::Value {
+        serde_json::from_slice(&response.body).unwrap()
+    }
+
+    #[test]
+    fn t_inspect() {
+        use std::sync::Arc;
+        let mut game = Game::new_blank_game();
+        let snake_id = game.create_snake(0.0).unwrap();
+        let food_id = game.add_food(3.0, 4.0, 0.0).unwrap();
+        let mut games = Games::new();
+        games.insert(0, Arc::new(Mutex::new(game)));
+
+        let response = respond(&games, &_request("GET", "/api/games", ""));
+        assert_eq!(200, response.status);
+        assert_eq!(Some("application/json"), response.get_header("content-type"));
+        let summaries: Vec<GameSummary> = serde_json::from_slice(&response.body).unwrap();
+        assert_eq!(vec![GameSummary { game_id: 0, time: 0.0, tick: 0, snakes: 1, food: 1 }], summaries);
+
+        let response = respond(&games, &_request("GET", "/api/games/0", ""));
+        assert_eq!(200, response.status);
+        assert_eq!(json!(0.0), _body(&response)["time"]);
+
+        let response = respond(&games, &_request("GET", "/api/games/0/snakes", ""));
+        assert_eq!(json!(snake_id), _body(&response)[0]["snake_id"]);
+        let response = respond(&games, &_request("GET", &format!("/api/games/0/snakes/{}", snake_id), ""));
+        assert_eq!(json!(1.0), _body(&response)["size"]);
+
+        let response = respond(&games, &_request("GET", "/api/games/0/food", ""));
+        assert_eq!(json!(food_id), _body(&response)[0]["food_id"]);
+        let response = respond(&games, &_request("GET", &format!("/api/games/0/food/{}", food_id), ""));
+        assert_eq!(json!({"x": 3.0, "y": 4.0}), _body(&response)["location"]);
+    }
+
+    #[test]
+    fn t_not_found() {
+        use std::sync::Arc;
+        let mut games = Games::new();
+        games.insert(0, Arc::new(Mutex::new(Game::new_blank_game())));
+        for path in ["/api/games/1", "/api/games/zero/snakes", "/api/games/0/snakes/7", "/api/games/0/food/7", "/api/games/0/hats", "/elsewhere"].iter() {
+            let response = respond(&games, &_request("GET", path, ""));
+            assert_eq!(404, response.status, "{}", path);
+            assert!(_body(&response)["error"].is_string());
+        }
+        assert_eq!(json!("No snake 7"), _body(&respond(&games, &_request("GET", "/api/games/0/snakes/7", "")))["error"]);
+        assert_eq!(404, respond(&games, &_request("DELETE", "/api/games/0/snakes/7", "")).status);
+
+        let response = respond(&games, &_request("PUT", "/api/games/0/food", ""));
+        assert_eq!(405, response.status);
+        assert_eq!(Some("GET, POST"), response.get_header("Allow"));
+    }
+
+    #[test]
+    fn t_admin() {
+        use std::sync::Arc;
+        let mut game = Game::new_blank_game();
+        let snake_id = game.create_snake(0.0).unwrap();
+        let mut games = Games::new();
+        games.insert(0, Arc::new(Mutex::new(game)));
+
+        let response = respond(&games, &_request("POST", "/api/games/0/food", r#"{"x":5.0,"y":-5.0}"#));
+        assert_eq!(201, response.status);
+        let food_id = _body(&response)["food_id"].as_u64().unwrap() as usize;
+        assert_eq!(Some(format!("/api/games/0/food/{}", food_id).as_str()), response.get_header("Location"));
+        assert!(games[&0].lock().unwrap()._get_food(food_id).is_ok());
+        assert_eq!(400, respond(&games, &_request("POST", "/api/games/0/food", r#"{"x":5.0}"#)).status);
+
+        let response = respond(&games, &_request("DELETE", &format!("/api/games/0/snakes/{}", snake_id), ""));
+        assert_eq!(204, response.status);
+        assert!(games[&0].lock().unwrap().get_snake(snake_id).is_err());
+        assert_eq!(404, respond(&games, &_request("DELETE", &format!("/api/games/0/snakes/{}", snake_id), "")).status);
+    }
+
+    #[test]
+    fn t_event_history() {
+        use std::sync::Arc;
+        let mut game = Game::new_blank_game();
+        for _i in 0..5 {
+            game.create_snake(0.0).unwrap();
+        }
+        let mut games = Games::new();
+        games.insert(0, Arc::new(Mutex::new(game)));
+
+        let page = _body(&respond(&games, &_request("GET", "/api/games/0/events?limit=2", "")));
+        assert_eq!(json!(0), page["oldest"]);
+        assert_eq!(json!([0, 1]), json!([page["events"][0]["sequence"], page["events"][1]["sequence"]]));
+        let page = _body(&respond(&games, &_request("GET", "/api/games/0/events?after=1&limit=2", "")));
+        assert_eq!(json!("snake_born"), page["events"][0]["type"]);
+        assert_eq!(json!(2), page["events"][0]["sequence"]);
+        let page = _body(&respond(&games, &_request("GET", "/api/games/0/events?after=4", "")));
+        assert_eq!(json!([]), page["events"]);
+
+        // looking at the history leaves it there
+        assert_eq!(5, games[&0].lock().unwrap().get_event_queue_mut().get_unread_count(0));
+        assert_eq!(400, respond(&games, &_request("GET", "/api/games/0/events?after=soon", "")).status);
+    }
+
+    #[test]
+    fn t_admin_token() {
+        use crate::lobby::lobby::LobbyConfig;
+        let lobby = Mutex::new(GameManager::new(LobbyConfig::default()));
+        lobby.lock().unwrap().create_game(GameConfig::default(), Instant::now()).unwrap();
+        let food_count = lobby.lock().unwrap().get_game(0).unwrap().lock().unwrap()._get_food_count();
+
+        // looking is for anyone
+        assert_eq!(200, respond_for_lobby(&lobby, &_request("GET", "/api/games/0", ""), Some(_ADMIN_TOKEN)).status);
+        assert_eq!(200, respond_for_lobby(&lobby, &_request("GET", "/api/games/0/security", ""), None).status);
+
+        // changing things isn't, without the token
+        let changes = [("POST", "/api/games", ""), ("DELETE", "/api/games/0", ""), ("POST", "/api/games/0/food", r#"{"x":1.0,"y":1.0}"#), ("DELETE", "/api/games/0/snakes/0", "")];
+        for (method, path, body) in changes.iter() {
+            let response = respond_for_lobby(&lobby, &_request(method, path, body), Some(_ADMIN_TOKEN));
+            assert_eq!(401, response.status, "{} {}", method, path);
+            assert_eq!(Some("Bearer"), response.get_header("WWW-Authenticate"));
+            let mut guess = _request(method, path, body);
+            guess.headers.push(("Authorization".to_string(), "Bearer let-me-im".to_string()));
+            assert_eq!(401, respond_for_lobby(&lobby, &guess, Some(_ADMIN_TOKEN)).status, "{} {}", method, path);
+            // a server with no token takes no changes at all
+            assert_eq!(403, respond_for_lobby(&lobby, &_as_admin(_request(method, path, body)), None).status, "{} {}", method, path);
+        }
+        assert_eq!(1, lobby.lock().unwrap().get_game_count());
+        assert_eq!(food_count, lobby.lock().unwrap().get_game(0).unwrap().lock().unwrap()._get_food_count());
+
+        // and with it, they're made
+        let response = respond_for_lobby(&lobby, &_as_admin(_request("POST", "/api/games/0/food", r#"{"x":1.0,"y":1.0}"#)), Some(_ADMIN_TOKEN));
+        assert_eq!(201, response.status);
+        assert_eq!(204, respond_for_lobby(&lobby, &_as_admin(_request("DELETE", "/api/games/0", "")), Some(_ADMIN_TOKEN)).status);
+    }
+
+    #[test]
+    fn t_lobby() {
+        use crate::lobby::lobby::LobbyConfig;
+        let lobby = Mutex::new(GameManager::new(LobbyConfig { max_games: 2, ..LobbyConfig::default() }));
+
+        let response = respond_for_lobby(&lobby, &_as_admin(_request("POST", "/api/games", "")), Some(_ADMIN_TOKEN));
+        assert_eq!(201, response.status);
+        assert_eq!(Some("/api/games/0"), response.get_header("Location"));
+        let small = serde_json::to_string(&GameConfig { size: 10.0, ..GameConfig::default() }).unwrap();
+        let response = respond_for_lobby(&lobby, &_as_admin(_request("POST", "/api/games", &small)), Some(_ADMIN_TOKEN));
+        assert_eq!(json!({"game_id": 1}), _body(&response));
+        assert_eq!(10.0, lobby.lock().unwrap().get_game(1).unwrap().lock().unwrap().get_size());
+        assert_eq!(503, respond_for_lobby(&lobby, &_as_admin(_request("POST", "/api/games", "")), Some(_ADMIN_TOKEN)).status);
+        assert_eq!(400, respond_for_lobby(&lobby, &_as_admin(_request("POST", "/api/games", "{}")), Some(_ADMIN_TOKEN)).status);
+        // a config that reads fine but couldn't be played is turned away too
+        let nowhere = serde_json::to_string(&GameConfig { size: 0.0, ..GameConfig::default() }).unwrap();
+        let response = respond_for_lobby(&lobby, &_as_admin(_request("POST", "/api/games", &nowhere)), Some(_ADMIN_TOKEN));
+        assert_eq!(400, response.status);
+        assert_eq!(json!("Bad game config, size must be above 0!"), _body(&response)["error"]);
+
+        // the rest of the API sees the lobby's games
+        let response = respond_for_lobby(&lobby, &_request("GET", "/api/games", ""), Some(_ADMIN_TOKEN));
+        assert_eq!(json!([0, 1]), json!([_body(&response)[0]["game_id"], _body(&response)[1]["game_id"]]));
+        assert_eq!(200, respond_for_lobby(&lobby, &_request("GET", "/api/games/1/snakes", ""), Some(_ADMIN_TOKEN)).status);
+
+        // and the guard's security log
+        {
+            use crate::command::command::Command;
+            let guard = lobby.lock().unwrap().get_guard(1).unwrap();
+            let game = lobby.lock().unwrap().get_game(1).unwrap();
+            let mut guard = guard.lock().unwrap();
+            let player_id = guard.add_player();
+            let command = Command::Turn { snake_id: 7, rad_relative: 0.5, event_time: 0.0 };
+            assert!(guard.apply(&mut game.lock().unwrap(), player_id, command).is_err());
+        }
+        let response = respond_for_lobby(&lobby, &_request("GET", "/api/games/1/security", ""), Some(_ADMIN_TOKEN));
+        assert_eq!(json!({"type": "not_your_snake", "snake_id": 7}), _body(&response)[0]["rejection"]);
+
+        assert_eq!(204, respond_for_lobby(&lobby, &_as_admin(_request("DELETE", "/api/games/0", "")), Some(_ADMIN_TOKEN)).status);
+        assert_eq!(404, respond_for_lobby(&lobby, &_as_admin(_request("DELETE", "/api/games/0", "")), Some(_ADMIN_TOKEN)).status);
+        assert_eq!(404, respond_for_lobby(&lobby, &_request("GET", "/api/games/0", ""), Some(_ADMIN_TOKEN)).status);
+        assert_eq!(1, lobby.lock().unwrap().get_game_count());
+
+        let response = respond_for_lobby(&lobby, &_as_admin(_request("PUT", "/api/games", "")), Some(_ADMIN_TOKEN));
+        assert_eq!(Some("GET, POST"), response.get_header("Allow"));
+    }
+}
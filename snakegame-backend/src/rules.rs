@@ -0,0 +1,258 @@
+pub mod rules {
+    use crate::game::common::Coordinates;
+    use crate::snake::snake::{Snake, SweepArea};
+    use std::f64::consts::FRAC_PI_2;
+    #[cfg(feature = "serde")]
+    use serde::{Deserialize, Serialize};
+
+    // Everything that differs between the ways the game can be played. The Game
+    // owns the snakes, food and events; the rules decide how they move and grow.
+    pub trait Rules: Send + Sync {
+        // The turn a snake actually makes when asked to turn by rad_relative
+        fn effective_turn(&self, rad_relative: f64) -> f64;
+        // Bring the snake up to game_time, returning the area it swept on the way
+        fn move_snake(&self, snake: &mut Snake, game_time: f64) -> SweepArea;
+        // Turn the snake by an effective_turn() at event_time
+        fn turn_snake(&self, snake: &mut Snake, rad_relative: f64, event_time: f64) -> SweepArea;
+        // The speed a snake actually goes when boosted to speed
+        fn effective_speed(&self, speed: f64) -> f64;
+        // Set the snake going at an effective_speed() from event_time
+        fn boost_snake(&self, snake: &mut Snake, speed: f64, event_time: f64) -> SweepArea;
+        fn feed_snake(&self, snake: &mut Snake, nutrition: f64);
+        // How far out from its head a snake can be hit by another snake
+        fn body_radius(&self, snake: &Snake) -> f64;
+        fn place_food(&self, x: f64, y: f64) -> Coordinates;
+    }
+
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    #[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+    pub enum GameMode {
+        // snakes glide anywhere and can turn by any angle
+        Continuous,
+        // classic snake - whole cells, four headings, one cell per tick
+        Grid { tick_length: f64 },
+    }
+    impl GameMode {
+        pub fn classic_grid() -> GameMode {
+            GameMode::Grid { tick_length: 1.0 }
+        }
+
+        pub fn rules(&self) -> Box<dyn Rules> {
+            match *self {
+                GameMode::Continuous => Box::new(ContinuousRules),
+                GameMode::Grid { tick_length } => Box::new(GridRules { tick_length }),
+            }
+        }
+    }
+
+    pub struct ContinuousRules;
+    impl Rules for ContinuousRules {
+        fn effective_turn(&self, rad_relative: f64) -> f64 {
+            rad_relative
+        }
+
+        fn move_snake(&self, snake: &mut Snake, game_time: f64) -> SweepArea {
+            snake.update(game_time)
+        }
+
+        fn turn_snake(&self, snake: &mut Snake, rad_relative: f64, event_time: f64) -> SweepArea {
+            snake.turn(rad_relative, event_time)
+        }
+
+        fn effective_speed(&self, speed: f64) -> f64 {
+            speed
+        }
+
+        fn boost_snake(&self, snake: &mut Snake, speed: f64, event_time: f64) -> SweepArea {
+            snake.boost(speed, event_time)
+        }
+
+        fn feed_snake(&self, snake: &mut Snake, nutrition: f64) {
+            snake.eat(nutrition);
+        }
+
+        fn body_radius(&self, snake: &Snake) -> f64 {
+            snake.get_size() / 2.0
+        }
+
+        fn place_food(&self, x: f64, y: f64) -> Coordinates {
+            Coordinates { x, y }
+        }
+    }
+
+    pub struct GridRules {
+        // game seconds it takes a snake to move one cell
+        pub tick_length: f64,
+    }
+    impl GridRules {
+        // Number of whole ticks that have started by game_time. The nudge keeps
+        // times like 0.1 * 30 from landing just short of a tick.
+        fn ticks_at(&self, game_time: f64) -> f64 {
+            (game_time / self.tick_length + 1e-9).floor()
+        }
+
+        // Snakes only ever cover one cell's width, however long they have grown
+        fn one_cell_wide(mut coverage: SweepArea) -> SweepArea {
+            coverage.width = 1.0;
+            coverage
+        }
+    }
+    impl Rules for GridRules {
+        fn effective_turn(&self, rad_relative: f64) -> f64 {
+            // whatever the angle asked for, it's a quarter turn that way
+            if rad_relative > 0.0 {
+                FRAC_PI_2
+            } else if rad_relative < 0.0 {
+                -FRAC_PI_2
+            } else {
+                0.0
+            }
+        }
+
+        fn move_snake(&self, snake: &mut Snake, game_time: f64) -> SweepArea {
+            let steps = self.ticks_at(game_time) - self.ticks_at(snake.get_last_updated());
+            let coverage = snake.travel(steps, game_time);
+            snake.snap_to_grid();
+            GridRules::one_cell_wide(coverage)
+        }
+
+        fn turn_snake(&self, snake: &mut Snake, rad_relative: f64, event_time: f64) -> SweepArea {
+            let coverage = self.move_snake(snake, event_time);
+            snake.rotate(self.effective_turn(rad_relative));
+            snake.snap_to_grid();
+            coverage
+        }
+
+        fn effective_speed(&self, speed: f64) -> f64 {
+            // whole cells per tick, and never stopped dead
+            speed.round().max(1.0)
+        }
+
+        fn boost_snake(&self, snake: &mut Snake, speed: f64, event_time: f64) -> SweepArea {
+            let coverage = self.move_snake(snake, event_time);
+            snake.set_speed(self.effective_speed(speed));
+            snake.snap_to_grid();
+            coverage
+        }
+
+        fn feed_snake(&self, snake: &mut Snake, nutrition: f64) {
+            // one more segment per food, however nutritious
+            snake.feed(nutrition, 1.0);
+        }
+
+        fn body_radius(&self, _snake: &Snake) -> f64 {
+            0.0
+        }
+
+        fn place_food(&self, x: f64, y: f64) -> Coordinates {
+            Coordinates { x: x.round(), y: y.round() }
+        }
+    }
+
+    #[test]
+    fn t_continuous_rules() {
+        let rules = GameMode::Continuous.rules();
+        assert_eq!(0.3, rules.effective_turn(0.3));
+        assert_eq!(1.5, rules.effective_speed(1.5));
+
+        let mut test_snake = Snake::new(0.0);
+        let coverage = rules.move_snake(&mut test_snake, 0.5);
+        assert_eq!(0.5, coverage.end_loc.y);
+        assert_eq!(1.0, coverage.width);
+
+        rules.feed_snake(&mut test_snake, 10.0);
+        assert_eq!(2.0, test_snake.get_size());
+        assert_eq!(1.0, rules.body_radius(&test_snake));
+
+        let loc = rules.place_food(0.3, -0.7);
+        assert_eq!(0.3, loc.x);
+        assert_eq!(-0.7, loc.y);
+    }
+
+    #[test]
+    fn t_grid_effective_turn() {
+        let rules = GameMode::classic_grid().rules();
+        assert_eq!(FRAC_PI_2, rules.effective_turn(0.1));
+        assert_eq!(FRAC_PI_2, rules.effective_turn(3.0));
+        assert_eq!(-FRAC_PI_2, rules.effective_turn(-0.1));
+        assert_eq!(0.0, rules.effective_turn(0.0));
+    }
+
+    #[test]
+    fn t_grid_move_snake() {
+        let rules = GameMode::classic_grid().rules();
+        let mut test_snake = Snake::new(0.0);
+
+        // nothing happens until a whole tick has gone by
+        let coverage = rules.move_snake(&mut test_snake, 0.5);
+        assert_eq!(0.0, coverage.end_loc.y);
+        assert_eq!(0.0, test_snake.get_location().y);
+
+        // then the snake moves one cell per tick
+        let coverage = rules.move_snake(&mut test_snake, 1.0);
+        assert_eq!(0.0, coverage.start_loc.y);
+        assert_eq!(1.0, coverage.end_loc.y);
+        let coverage = rules.move_snake(&mut test_snake, 3.2);
+        assert_eq!(1.0, coverage.start_loc.y);
+        assert_eq!(3.0, coverage.end_loc.y);
+
+        // a longer snake still only sweeps one cell wide
+        rules.feed_snake(&mut test_snake, 1.0);
+        assert_eq!(2.0, test_snake.get_size());
+        let coverage = rules.move_snake(&mut test_snake, 4.0);
+        assert_eq!(1.0, coverage.width);
+
+        // faster ticks mean faster snakes
+        let rules = GameMode::Grid { tick_length: 0.1 }.rules();
+        let mut test_snake = Snake::new(0.0);
+        rules.move_snake(&mut test_snake, 3.0);
+        assert_eq!(30.0, test_snake.get_location().y);
+    }
+
+    #[test]
+    fn t_grid_turn_snake() {
+        let rules = GameMode::classic_grid().rules();
+        let mut test_snake = Snake::new(0.0);
+
+        // any left turn is a quarter turn left, with no drift off the grid
+        rules.turn_snake(&mut test_snake, 0.2, 2.0);
+        let vel = test_snake.get_velocity();
+        assert_eq!(-1.0, vel.delta_x);
+        assert_eq!(0.0, vel.delta_y);
+        let loc = test_snake.get_location();
+        assert_eq!(0.0, loc.x);
+        assert_eq!(2.0, loc.y);
+
+        rules.move_snake(&mut test_snake, 5.0);
+        let loc = test_snake.get_location();
+        assert_eq!(-3.0, loc.x);
+        assert_eq!(2.0, loc.y);
+    }
+
+    #[test]
+    fn t_grid_boost_snake() {
+        let rules = GameMode::classic_grid().rules();
+        assert_eq!(2.0, rules.effective_speed(2.4));
+        assert_eq!(1.0, rules.effective_speed(0.2));
+
+        // a boosted snake moves more whole cells each tick
+        let mut test_snake = Snake::new(0.0);
+        rules.boost_snake(&mut test_snake, 2.4, 1.0);
+        let vel = test_snake.get_velocity();
+        assert_eq!(0.0, vel.delta_x);
+        assert_eq!(2.0, vel.delta_y);
+        assert_eq!(1.0, test_snake.get_location().y);
+
+        rules.move_snake(&mut test_snake, 3.0);
+        assert_eq!(5.0, test_snake.get_location().y);
+    }
+
+    #[test]
+    fn t_grid_place_food() {
+        let rules = GameMode::classic_grid().rules();
+        let loc = rules.place_food(0.3, -0.7);
+        assert_eq!(0.0, loc.x);
+        assert_eq!(-1.0, loc.y);
+    }
+}
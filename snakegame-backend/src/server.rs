@@ -0,0 +1,879 @@
+pub mod server {
+    // Plays games in real time over WebSockets.
+    //
+    // The server hosts a lobby of games, and seats every connection in one of
+    // them with a snake of its own. The server opens with
+    //   {"type":"welcome","game_id":..,"snake_id":..,"session":"..","snapshot":{..}}
+    // - the player's game and snake, their session token, and the game as it
+    // stands - then sends whatever has been logged since as
+    // {"type":"events","events":[..]}, in the Event JSON. Players only hear
+    // about what's near their snake: see the interest module. Events messages
+    // carry its notices as "notices":[..], when there are any.
+    // Players steer with {"type":"turn","rad_relative":..,"event_time":..}, where
+    // event_time is optional and defaults to now. Turns go through the game's
+    // CommandGuard; anything the server can't act on gets
+    // {"type":"error","message":".."} back, and players the guard kicks are
+    // disconnected.
+    //
+    // To keep time with the players, the server sends {"type":"ping","server_time":..}
+    // every so often, in game seconds, and players answer with
+    // {"type":"pong","server_time":..,"client_time":..}, the time it came by
+    // their own clock. From those the server keeps an estimate of each
+    // player's clock, and a turn can be stamped "client_time" instead of
+    // "event_time" to be made at the game time it stands for. Players can set
+    // their own clocks the same way: {"type":"ping","client_time":..} gets
+    // {"type":"pong","client_time":..,"server_time":..} back.
+    //
+    // A player whose connection drops can connect again with ?session=<token>
+    // for a while after, and &after=<sequence> of the last event they had, to
+    // get their snake back. The welcome then has the events they missed as
+    // "missed":[..], which the snapshot already takes in.
+    //
+    // Clients asking for the wamp.2.json subprotocol get a WAMP session on the
+    // same terms instead - see the wamp module. Players are sent away when
+    // their game ends. Plain HTTP requests on the same port go to the REST API
+    // if they're for something under /api, and to the website if there is one.
+    use crate::clocksync::clocksync::{ClockSync, SyncSample};
+    use crate::command::command::{Command, Rejection};
+    use crate::eventfilter::eventfilter::EventFilter;
+    use crate::eventqueue::eventqueue::{Event, SubscriberId};
+    use crate::game::game::Game;
+    use crate::http::http::{self, HttpError, HttpResponse};
+    use crate::interest::interest::{Interest, InterestConfig, Notice};
+    use crate::lobby::lobby::{GameManager, LobbyConfig, LobbyError, Seat};
+    use crate::rest::rest;
+    use crate::site::site::Site;
+    use crate::snapshot::snapshot::GameSnapshot;
+    use crate::wamp::wamp;
+    use serde::{Deserialize, Serialize};
+    use std::io;
+    use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use tungstenite::{Message, WebSocket};
+
+    // How often idle threads look up to see whether there's anything to do
+    pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(5);
+    // how long a connection gets to send its request
+    const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+    // how often players' clocks are checked
+    const SYNC_INTERVAL: Duration = Duration::from_secs(2);
+    const SYNC_SMOOTHING: f64 = 0.25;
+
+    #[derive(Clone, PartialEq, Debug)]
+    pub struct ServerConfig {
+        pub lobby: LobbyConfig,
+        // real time between clock advances. The game clocks advance by as much,
+        // so the games run at the speed of the wall clock.
+        pub tick_length: Duration,
+        // what the REST API wants as a Bearer token before it changes any game,
+        // or None for it to only be looked at
+        pub admin_token: Option<String>,
+    }
+    impl Default for ServerConfig {
+        fn default() -> Self {
+            ServerConfig {
+                lobby: LobbyConfig::default(),
+                tick_length: Duration::from_millis(100),
+                admin_token: None,
+            }
+        }
+    }
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    #[serde(tag = "type", rename_all = "snake_case")]
+    pub enum ClientMessage {
+        Turn {
+            rad_relative: f64,
+            #[serde(default, skip_serializing_if = "Option::is_none")]
+            event_time: Option<f64>,
+            #[serde(default, skip_serializing_if = "Option::is_none")]
+            client_time: Option<f64>,
+        },
+        Ping { client_time: f64 },
+        Pong { server_time: f64, client_time: f64 },
+    }
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    #[serde(tag = "type", rename_all = "snake_case")]
+    pub enum ServerMessage {
+        Welcome {
+            game_id: usize,
+            snake_id: usize,
+            session: String,
+            // boxed, being by far the biggest
+            snapshot: Box<GameSnapshot>,
+            // what a returning player missed, up to the snapshot
+            #[serde(default, skip_serializing_if = "Vec::is_empty")]
+            missed: Vec<Event>,
+        },
+        Events {
+            events: Vec<Event>,
+            #[serde(default, skip_serializing_if = "Vec::is_empty")]
+            notices: Vec<Notice>,
+        },
+        Error { message: String },
+        Ping { server_time: f64 },
+        Pong { client_time: f64, server_time: f64 },
+    }
+
+    // A JSON player, as the server knows them
+    struct Player {
+        player_id: usize,
+        // which of the player's connections this is
+        connection: usize,
+        snake_id: usize,
+        subscriber_id: SubscriberId,
+        clock: ClockSync,
+        // the server_time of the ping waiting on a pong, if one is
+        ping: Option<f64>,
+        // what they're told about, if not everything
+        interest: Option<Interest>,
+    }
+
+    // A player coming back, by their session token
+    #[derive(Copy, Clone)]
+    struct Resumed {
+        player_id: usize,
+        connection: usize,
+        // the sequence of the last event they had, if they said
+        after: Option<u64>,
+    }
+
+    pub struct Server {
+        listener: TcpListener,
+        config: ServerConfig,
+        lobby: Arc<Mutex<GameManager>>,
+        site: Option<Arc<Site>>,
+        running: Arc<AtomicBool>,
+    }
+
+    // A server running on background threads. Dropping it stops the server.
+    pub struct ServerHandle {
+        local_addr: SocketAddr,
+        running: Arc<AtomicBool>,
+        threads: Vec<thread::JoinHandle<()>>,
+    }
+
+    impl Server {
+        pub fn bind<A: ToSocketAddrs>(addr: A, config: ServerConfig) -> io::Result<Server> {
+            let listener = TcpListener::bind(addr)?;
+            // non-blocking, so the accept loop notices when it's time to stop
+            listener.set_nonblocking(true)?;
+            let mut lobby = GameManager::new(config.lobby);
+            // a game to be going on with. Like any other, it's closed if
+            // nobody plays.
+            lobby.create_game(config.lobby.game, Instant::now())
+                .map_err(io::Error::other)?;
+            Ok(Server {
+                listener,
+                config,
+                lobby: Arc::new(Mutex::new(lobby)),
+                site: None,
+                running: Arc::new(AtomicBool::new(true)),
+            })
+        }
+
+        // Serve the website too
+        pub fn with_site(mut self, site: Site) -> Server {
+            self.site = Some(Arc::new(site));
+            self
+        }
+
+        pub fn local_addr(&self) -> io::Result<SocketAddr> {
+            self.listener.local_addr()
+        }
+
+        // Serve until the process ends
+        pub fn run(self) -> io::Result<()> {
+            let ticker = self.spawn_ticker();
+            let result = self.accept_players();
+            self.running.store(false, Ordering::SeqCst);
+            let _ = ticker.join();
+            result
+        }
+
+        pub fn spawn(self) -> io::Result<ServerHandle> {
+            let local_addr = self.local_addr()?;
+            let running = self.running.clone();
+            let ticker = self.spawn_ticker();
+            let acceptor = thread::spawn(move || {
+                if let Err(error) = self.accept_players() {
+                    eprintln!("Server stopped accepting players: {}", error);
+                }
+            });
+            Ok(ServerHandle {
+                local_addr,
+                running,
+                threads: vec![ticker, acceptor],
+            })
+        }
+
+        fn spawn_ticker(&self) -> thread::JoinHandle<()> {
+            let lobby = self.lobby.clone();
+            let running = self.running.clone();
+            let tick_length = self.config.tick_length;
+            thread::spawn(move || {
+                let mut next_tick = Instant::now() + tick_length;
+                while running.load(Ordering::SeqCst) {
+                    let now = Instant::now();
+                    if now < next_tick {
+                        thread::sleep((next_tick - now).min(POLL_INTERVAL));
+                        continue;
+                    }
+                    let mut lobby = lobby.lock().unwrap();
+                    lobby.advance_clocks(tick_length.as_secs_f64(), now);
+                    lobby.close_idle_games(now);
+                    next_tick += tick_length;
+                }
+                // which sends the players away
+                lobby.lock().unwrap().end_all_games();
+            })
+        }
+
+        fn accept_players(&self) -> io::Result<()> {
+            while self.running.load(Ordering::SeqCst) {
+                match self.listener.accept() {
+                    Ok((stream, peer)) => {
+                        let lobby = self.lobby.clone();
+                        let site = self.site.clone();
+                        let admin_token = self.config.admin_token.clone();
+                        thread::spawn(move || {
+                            if let Err(error) = serve_connection(stream, &lobby, site.as_deref(), admin_token.as_deref()) {
+                                eprintln!("Dropped connection from {}: {}", peer, error);
+                            }
+                        });
+                    }
+                    Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+                    Err(error) => return Err(error),
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl ServerHandle {
+        pub fn local_addr(&self) -> SocketAddr {
+            self.local_addr
+        }
+
+        pub fn stop(&mut self) {
+            self.running.store(false, Ordering::SeqCst);
+            for thread in self.threads.drain(..) {
+                let _ = thread.join();
+            }
+        }
+    }
+    impl Drop for ServerHandle {
+        fn drop(&mut self) {
+            self.stop();
+        }
+    }
+
+    // One connection, until it goes
+    fn serve_connection(stream: TcpStream, lobby: &Mutex<GameManager>, site: Option<&Site>, admin_token: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        if http::is_websocket_upgrade(&http::peek_head(&stream)?) {
+            serve_player(stream, lobby)?;
+        } else {
+            serve_http(stream, lobby, site, admin_token)?;
+        }
+        Ok(())
+    }
+
+    fn serve_http(mut stream: TcpStream, lobby: &Mutex<GameManager>, site: Option<&Site>, admin_token: Option<&str>) -> io::Result<()> {
+        let mut head_only = false;
+        let response = match http::read_request(&mut stream) {
+            Ok(mut request) => {
+                // HEAD is answered as GET would be, without the body
+                if request.method == "HEAD" {
+                    head_only = true;
+                    request.method = "GET".to_string();
+                }
+                match (request.get_segments().first(), site) {
+                    (Some(&"api"), _) => rest::respond_for_lobby(lobby, &request, admin_token),
+                    (_, Some(site)) => site.respond(&request),
+                    (_, None) => HttpResponse::error(404, &format!("Nothing at {}", request.path)),
+                }
+            }
+            Err(HttpError::Io(error)) => return Err(error),
+            Err(HttpError::TooLarge) => HttpResponse::error(413, "Request too large"),
+            Err(HttpError::Malformed) => HttpResponse::error(400, "Malformed request"),
+        };
+        if head_only {
+            http::write_head(&mut stream, &response)
+        } else {
+            http::write_response(&mut stream, &response)
+        }
+    }
+
+    // A WebSocket connection, from the handshake until it goes
+    fn serve_player(stream: TcpStream, lobby: &Mutex<GameManager>) -> tungstenite::Result<()> {
+        use tungstenite::handshake::server::{Request, Response};
+        let mut speaks_wamp = false;
+        let mut query = Vec::new();
+        let mut socket = tungstenite::accept_hdr(stream, |request: &Request, mut response: Response| {
+            query = request.uri().query().and_then(http::parse_query).unwrap_or_default();
+            if wamp::offers_subprotocol(request) {
+                speaks_wamp = true;
+                response.headers_mut().insert(
+                    tungstenite::http::header::SEC_WEBSOCKET_PROTOCOL,
+                    tungstenite::http::HeaderValue::from_static(wamp::SUBPROTOCOL),
+                );
+            }
+            Ok(response)
+        })
+        .map_err(|error| match error {
+            tungstenite::HandshakeError::Failure(error) => error,
+            tungstenite::HandshakeError::Interrupted(_) => io::Error::from(io::ErrorKind::WouldBlock).into(),
+        })?;
+        // reads give up after a moment, so events can go out in between
+        socket.get_ref().set_read_timeout(Some(POLL_INTERVAL))?;
+
+        let get_query = |name: &str| query.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str());
+        let joined = {
+            let mut lobby = lobby.lock().unwrap();
+            let interest = lobby.get_config().interest;
+            match get_query("session").filter(|_| !speaks_wamp) {
+                Some(token) => lobby.rejoin(token).map(|(seat, player_id, connection)| {
+                    let after = get_query("after").and_then(|after| after.parse().ok());
+                    (seat, interest, Some(Resumed { player_id, connection, after }))
+                }),
+                None => lobby.join(Instant::now()).map(|seat| (seat, interest, None)),
+            }
+        };
+        let (seat, interest, resumed) = match joined {
+            Ok(joined) => joined,
+            Err(LobbyError::NoSuchSession) => {
+                send(&mut socket, &ServerMessage::Error { message: "No such session!".to_string() })?;
+                socket.close(None)?;
+                while socket.read().is_ok() {}
+                return Ok(());
+            }
+            Err(error) => {
+                let message = format!("No room for another player: {}", error);
+                send(&mut socket, &ServerMessage::Error { message })?;
+                socket.close(None)?;
+                // let the close go out before the connection does
+                while socket.read().is_ok() {}
+                return Ok(());
+            }
+        };
+        let result = if speaks_wamp {
+            wamp::serve_session(&mut socket, &seat)
+        } else {
+            serve_json_player(&mut socket, &seat, interest, resumed)
+        };
+        lobby.lock().unwrap().leave(seat.game_id, Instant::now());
+        match result {
+            Err(tungstenite::Error::ConnectionClosed) => Ok(()),
+            _ => result,
+        }
+    }
+
+    fn serve_json_player(
+        socket: &mut WebSocket<TcpStream>,
+        seat: &Seat,
+        interest: Option<InterestConfig>,
+        resumed: Option<Resumed>,
+    ) -> tungstenite::Result<()> {
+        let (mut player, welcome) = match welcome(seat, interest, resumed) {
+            Ok(welcomed) => welcomed,
+            Err(rejection) => {
+                send(socket, &ServerMessage::Error { message: rejection.to_string() })?;
+                socket.close(None)?;
+                while socket.read().is_ok() {}
+                return Ok(());
+            }
+        };
+        let result = send(socket, &welcome).and_then(|()| play(socket, seat, &mut player));
+
+        // their snake waits a while for them to come back
+        let mut guard = seat.guard.lock().unwrap();
+        let mut game = seat.game.lock().unwrap();
+        game.get_event_queue_mut().unsubscribe(player.subscriber_id);
+        let event_time = game.get_time();
+        for snake_id in guard.drop_connection(player.player_id, player.connection, event_time) {
+            // it may already have been killed
+            let _ = game.remove_snake(snake_id, event_time);
+        }
+        result
+    }
+
+    // The player and their welcome, with a snake to play - or why not, in
+    // which case they're let go as if they'd dropped
+    fn welcome(seat: &Seat, interest: Option<InterestConfig>, resumed: Option<Resumed>) -> Result<(Player, ServerMessage), Rejection> {
+        let mut guard = seat.guard.lock().unwrap();
+        let mut game = seat.game.lock().unwrap();
+        let (player_id, connection) = match resumed {
+            Some(Resumed { player_id, connection, .. }) => (player_id, connection),
+            None => (guard.add_player(), 0),
+        };
+        let snake_id = match guard.get_snake_ids(player_id).first() {
+            Some(snake_id) => *snake_id,
+            None => match guard.create_snake(&mut game, player_id) {
+                Ok(snake_id) => snake_id,
+                Err(rejection) => {
+                    let event_time = game.get_time();
+                    guard.drop_connection(player_id, connection, event_time);
+                    return Err(rejection);
+                }
+            },
+        };
+        // what a returning player missed is before the snapshot, as far back as
+        // the game remembers
+        let subscriber_id = match resumed.and_then(|resumed| resumed.after) {
+            Some(sequence) => game.get_event_queue_mut().subscribe_after(sequence, EventFilter::Everything),
+            None => game.get_event_queue_mut().subscribe(),
+        };
+        let missed = if resumed.is_some() {
+            take_events(&mut game, subscriber_id)
+        } else {
+            Vec::new()
+        };
+        // the snapshot covers everything up to now, the events everything after
+        let mut interest = interest.map(|config| Interest::new(config, snake_id));
+        let (snapshot, missed) = match interest.as_mut() {
+            Some(interest) => {
+                let snapshot = interest.view_snapshot(game.snapshot());
+                (snapshot, interest.view_history(missed))
+            }
+            None => (game.snapshot(), missed),
+        };
+        let welcome = ServerMessage::Welcome {
+            game_id: seat.game_id,
+            snake_id,
+            session: guard.get_token(player_id).expect("Players with snakes have sessions"),
+            snapshot: Box::new(snapshot),
+            missed,
+        };
+        let player = Player {
+            player_id,
+            connection,
+            snake_id,
+            subscriber_id,
+            clock: ClockSync::new(SYNC_SMOOTHING),
+            ping: None,
+            interest,
+        };
+        Ok((player, welcome))
+    }
+
+    // Whether a read only gave up waiting, rather than failed
+    pub(crate) fn timed_out(error: &tungstenite::Error) -> bool {
+        match error {
+            tungstenite::Error::Io(error) => error.kind() == io::ErrorKind::WouldBlock || error.kind() == io::ErrorKind::TimedOut,
+            _ => false,
+        }
+    }
+
+    // Everything logged for the subscriber since it last looked
+    pub(crate) fn read_events(game: &Mutex<Game>, subscriber_id: SubscriberId) -> Vec<Event> {
+        take_events(&mut game.lock().unwrap(), subscriber_id)
+    }
+
+    fn take_events(game: &mut Game, subscriber_id: SubscriberId) -> Vec<Event> {
+        let eventqueue = game.get_event_queue_mut();
+        let mut events = Vec::new();
+        while let Some(event) = eventqueue.get_event_for(subscriber_id) {
+            events.push(event);
+        }
+        events
+    }
+
+    // What the player is to hear of what's happened since they last looked,
+    // if there's anything. The game stays locked throughout, so what's in
+    // range goes by the game as of the last event.
+    fn read_update(seat: &Seat, player: &mut Player) -> Option<ServerMessage> {
+        let mut game = seat.game.lock().unwrap();
+        let events = take_events(&mut game, player.subscriber_id);
+        if events.is_empty() {
+            return None;
+        }
+        let (events, notices) = match player.interest.as_mut() {
+            Some(interest) => interest.filter(&game, events),
+            None => (events, Vec::new()),
+        };
+        if events.is_empty() && notices.is_empty() {
+            return None;
+        }
+        Some(ServerMessage::Events { events, notices })
+    }
+
+    fn play(socket: &mut WebSocket<TcpStream>, seat: &Seat, player: &mut Player) -> tungstenite::Result<()> {
+        let mut next_ping = Instant::now();
+        while seat.open.load(Ordering::SeqCst) {
+            if !seat.guard.lock().unwrap().is_connected(player.player_id, player.connection) {
+                let message = "Reconnected from somewhere else".to_string();
+                send(socket, &ServerMessage::Error { message })?;
+                socket.close(None)?;
+                return Ok(());
+            }
+            let now = Instant::now();
+            if now >= next_ping {
+                let server_time = seat.clock.get_time(now);
+                player.ping = Some(server_time);
+                send(socket, &ServerMessage::Ping { server_time })?;
+                next_ping = now + SYNC_INTERVAL;
+            }
+
+            match socket.read() {
+                Ok(Message::Text(text)) => match handle_message(seat, player, &text, Instant::now()) {
+                    Ok(Some(reply)) => send(socket, &reply)?,
+                    Ok(None) => (),
+                    Err(message) => {
+                        send(socket, &ServerMessage::Error { message })?;
+                        if seat.guard.lock().unwrap().is_kicked(player.player_id) {
+                            break;
+                        }
+                    }
+                },
+                Ok(Message::Close(_)) => return Ok(()),
+                // tungstenite answers pings itself
+                Ok(_) => (),
+                Err(ref error) if timed_out(error) => (),
+                Err(error) => return Err(error),
+            }
+
+            if let Some(update) = read_update(seat, player) {
+                send(socket, &update)?;
+            }
+        }
+        // the last word from a game that's ending
+        if let Some(update) = read_update(seat, player) {
+            send(socket, &update)?;
+        }
+        socket.close(None)?;
+        Ok(())
+    }
+
+    // What to send back, if anything
+    fn handle_message(seat: &Seat, player: &mut Player, text: &str, now: Instant) -> Result<Option<ServerMessage>, String> {
+        let message: ClientMessage = serde_json::from_str(text).map_err(|error| format!("Bad message: {}", error))?;
+        match message {
+            ClientMessage::Turn { rad_relative, event_time, client_time } => {
+                let mut guard = seat.guard.lock().unwrap();
+                let mut game = seat.game.lock().unwrap();
+                let event_time = match (event_time, client_time) {
+                    (Some(event_time), _) => event_time,
+                    (None, Some(client_time)) => player.clock.to_local_time(client_time)
+                        .ok_or_else(|| "Answer a ping before sending client times".to_string())?,
+                    (None, None) => game.get_time(),
+                };
+                let command = Command::Turn { snake_id: player.snake_id, rad_relative, event_time };
+                guard.apply(&mut game, player.player_id, command).map_err(|rejection| rejection.to_string())?;
+                Ok(None)
+            }
+            ClientMessage::Ping { client_time } => Ok(Some(ServerMessage::Pong { client_time, server_time: seat.clock.get_time(now) })),
+            ClientMessage::Pong { server_time, client_time } => {
+                // only an answer to the ping that was sent counts
+                if player.ping != Some(server_time) {
+                    return Err("That's not the ping that was sent".to_string());
+                }
+                player.ping = None;
+                // the client received and answered at the one time
+                let sample = SyncSample::from_times(server_time, client_time, client_time, seat.clock.get_time(now));
+                player.clock.add_sample(sample);
+                Ok(None)
+            }
+        }
+    }
+
+    fn send(socket: &mut WebSocket<TcpStream>, message: &ServerMessage) -> tungstenite::Result<()> {
+        let json = serde_json::to_string(message).expect("Server messages always serialize");
+        socket.send(Message::Text(json))
+    }
+
+    fn _connect(addr: SocketAddr) -> WebSocket<tungstenite::stream::MaybeTlsStream<TcpStream>> {
+        _connect_with(addr, "")
+    }
+
+    fn _connect_with(addr: SocketAddr, query: &str) -> WebSocket<tungstenite::stream::MaybeTlsStream<TcpStream>> {
+        let (socket, _response) = tungstenite::connect(format!("ws://{}/{}", addr, query)).unwrap();
+        // fail rather than hang if the server goes quiet
+        if let tungstenite::stream::MaybeTlsStream::Plain(stream) = socket.get_ref() {
+            stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        }
+        socket
+    }
+
+    fn _receive(socket: &mut WebSocket<tungstenite::stream::MaybeTlsStream<TcpStream>>) -> ServerMessage {
+        match socket.read().unwrap() {
+            Message::Text(text) => serde_json::from_str(&text).unwrap(),
+            other => panic!("Expected a text message, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn t_client_messages() {
+        use crate::lobby::lobby::LobbyConfig;
+        let turn: ClientMessage = serde_json::from_str(r#"{"type":"turn","rad_relative":0.5}"#).unwrap();
+        assert_eq!(ClientMessage::Turn { rad_relative: 0.5, event_time: None, client_time: None }, turn);
+        let turn: ClientMessage = serde_json::from_str(r#"{"type":"turn","rad_relative":0.5,"event_time":1.5}"#).unwrap();
+        assert_eq!(ClientMessage::Turn { rad_relative: 0.5, event_time: Some(1.5), client_time: None }, turn);
+        assert!(serde_json::from_str::<ClientMessage>(r#"{"type":"jump"}"#).is_err());
+
+        let now = Instant::now();
+        let seat = GameManager::new(LobbyConfig::default()).join(now).unwrap();
+        let mut player = {
+            let mut guard = seat.guard.lock().unwrap();
+            let mut game = seat.game.lock().unwrap();
+            let player_id = guard.add_player();
+            let snake_id = guard.create_snake(&mut game, player_id).unwrap();
+            let subscriber_id = game.get_event_queue_mut().subscribe();
+            Player { player_id, connection: 0, snake_id, subscriber_id, clock: ClockSync::new(SYNC_SMOOTHING), ping: None, interest: None }
+        };
+        let handle = |player: &mut Player, text: &str| handle_message(&seat, player, text, now);
+        assert_eq!(Ok(None), handle(&mut player, r#"{"type":"turn","rad_relative":0.5}"#));
+        assert!(handle(&mut player, "not json").is_err());
+        assert!(handle(&mut player, r#"{"type":"turn","rad_relative":0.5,"event_time":100.0}"#).is_err());
+        player.snake_id += 1;
+        assert_eq!(Err(format!("Snake {} isn't yours!", player.snake_id)), handle(&mut player, r#"{"type":"turn","rad_relative":0.5}"#));
+        assert_eq!(2, seat.guard.lock().unwrap().get_security_log().len());
+    }
+
+    #[test]
+    fn t_clock_sync() {
+        use crate::lobby::lobby::LobbyConfig;
+        let now = Instant::now();
+        let seat = GameManager::new(LobbyConfig::default()).join(now).unwrap();
+        let mut player = {
+            let mut guard = seat.guard.lock().unwrap();
+            let mut game = seat.game.lock().unwrap();
+            let player_id = guard.add_player();
+            let snake_id = guard.create_snake(&mut game, player_id).unwrap();
+            let subscriber_id = game.get_event_queue_mut().subscribe();
+            Player { player_id, connection: 0, snake_id, subscriber_id, clock: ClockSync::new(SYNC_SMOOTHING), ping: None, interest: None }
+        };
+        let later = |millis: u64| now + Duration::from_millis(millis);
+
+        // the player's clock is 50s ahead of the game's, and it's 50ms each way
+        assert!(handle_message(&seat, &mut player, r#"{"type":"turn","rad_relative":0.5,"client_time":50.0}"#, now).is_err());
+        player.ping = Some(0.0);
+        assert!(handle_message(&seat, &mut player, r#"{"type":"pong","server_time":1.0,"client_time":50.05}"#, later(100)).is_err());
+        assert_eq!(Ok(None), handle_message(&seat, &mut player, r#"{"type":"pong","server_time":0.0,"client_time":50.05}"#, later(100)));
+        assert!((player.clock.get_offset().unwrap() - 50.0).abs() < 1e-9);
+        assert!((player.clock.get_round_trip().unwrap() - 0.1).abs() < 1e-9);
+        // the pong's been had
+        assert!(handle_message(&seat, &mut player, r#"{"type":"pong","server_time":0.0,"client_time":50.05}"#, later(100)).is_err());
+
+        // so a turn by the player's clock is made at the game time it means
+        assert_eq!(Ok(None), handle_message(&seat, &mut player, r#"{"type":"turn","rad_relative":0.5,"client_time":50.0}"#, later(100)));
+        assert_eq!(
+            Err("Time 10.000 is too far ahead of the game at 0.000!".to_string()),
+            handle_message(&seat, &mut player, r#"{"type":"turn","rad_relative":0.5,"client_time":60.0}"#, later(100))
+        );
+
+        // and the player can set their own clock by the game's
+        let reply = handle_message(&seat, &mut player, r#"{"type":"ping","client_time":7.0}"#, later(250)).unwrap();
+        assert_eq!(Some(ServerMessage::Pong { client_time: 7.0, server_time: 0.25 }), reply);
+    }
+
+    #[test]
+    fn t_http_on_the_same_port() {
+        use std::io::{Read, Write};
+        let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../public-html");
+        let site = Site::load(&root, "Version 0.0.0.25").unwrap();
+        let config = ServerConfig { admin_token: Some("let-me-in".to_string()), ..ServerConfig::default() };
+        let server = Server::bind("127.0.0.1:0", config).unwrap().with_site(site).spawn().unwrap();
+        let get = |path: &str| -> String {
+            let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+            let mut response = Vec::new();
+            stream.read_to_end(&mut response).unwrap();
+            // the font is the only thing that isn't text
+            String::from_utf8_lossy(&response).to_string()
+        };
+
+        let response = get("/api/games");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains(r#""game_id":0"#));
+        // the game started when the server did, and the history says so
+        let response = get("/api/games/0/events");
+        assert!(response.contains(r#""type":"game_started""#));
+        let response = get("/api/games/0/snakes/12");
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(response.ends_with(r#"{"error":"No snake 12"}"#));
+
+        // changing games takes the admin token
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        write!(stream, "POST /api/games HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 401 Unauthorized\r\n"));
+        assert!(response.contains("WWW-Authenticate: Bearer\r\n"));
+
+        // a game nobody could play isn't started, and the server carries on
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        let body = r#"{"size":0}"#;
+        write!(stream, "POST /api/games HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer let-me-in\r\nContent-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        assert!(response.contains(r#"{"error":"#));
+        let response = get("/api/games");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+
+        // and the website, from the same origin as the game
+        let response = get("/");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("Version 0.0.0.25<"));
+        let response = get("/Trattatello.ttf");
+        assert!(response.contains("Content-Type: font/ttf\r\n"));
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        write!(stream, "HEAD /snakegame.css HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("Connection: close\r\n\r\n"));
+    }
+
+    #[test]
+    fn t_two_players() {
+        use crate::eventqueue::eventqueue::EventKind;
+        use crate::game::game::SPAWN_CLEARANCE;
+        // slow enough that both players are in and steering before the first
+        // tick, and seeing all of the game however far apart they're born
+        let mut config = ServerConfig { tick_length: Duration::from_millis(250), ..ServerConfig::default() };
+        config.lobby.interest = None;
+        let mut server = Server::bind("127.0.0.1:0", config).unwrap().spawn().unwrap();
+
+        let mut player1 = _connect(server.local_addr());
+        let (game_id, snake1_id) = match _receive(&mut player1) {
+            ServerMessage::Welcome { game_id, snake_id, snapshot, .. } => {
+                assert!(snapshot.snakes.iter().any(|snake| snake.snake_id == snake_id));
+                (game_id, snake_id)
+            }
+            other => panic!("Expected a welcome, got {:?}", other),
+        };
+
+        // the second player is seated in the same game, and finds the first
+        // already there, well away from where they're born...
+        let mut player2 = _connect(server.local_addr());
+        let snake2_id = match _receive(&mut player2) {
+            ServerMessage::Welcome { game_id: game2_id, snake_id, snapshot, .. } => {
+                assert_eq!(game_id, game2_id);
+                let snake_ids: Vec<usize> = snapshot.snakes.iter().map(|snake| snake.snake_id).collect();
+                assert_eq!(vec![snake1_id, snake_id], snake_ids);
+                let (loc1, loc2) = (snapshot.snakes[0].location, snapshot.snakes[1].location);
+                assert!((loc1.x - loc2.x).hypot(loc1.y - loc2.y) >= SPAWN_CLEARANCE);
+                snake_id
+            }
+            other => panic!("Expected a welcome, got {:?}", other),
+        };
+
+        // ...and the first hears the second arrive
+        let mut born = Vec::new();
+        while born.is_empty() {
+            if let ServerMessage::Events { events, .. } = _receive(&mut player1) {
+                for event in events {
+                    if let EventKind::SnakeBorn { snake_id, .. } = event.kind {
+                        born.push(snake_id);
+                    }
+                }
+            }
+        }
+        assert_eq!(vec![snake2_id], born);
+
+        // the server checks the second player's clock, which is 100s fast...
+        let server_time = loop {
+            if let ServerMessage::Ping { server_time } = _receive(&mut player2) {
+                break server_time;
+            }
+        };
+        let pong = ClientMessage::Pong { server_time, client_time: server_time + 100.0 };
+        player2.send(Message::Text(serde_json::to_string(&pong).unwrap())).unwrap();
+
+        // ...so a turn stamped by it is made in time, and each player sees the
+        // other steer, and the clock keep going a few ticks with both snakes
+        // still alive
+        let turn = ClientMessage::Turn { rad_relative: 0.5, event_time: None, client_time: Some(server_time + 100.0) };
+        player2.send(Message::Text(serde_json::to_string(&turn).unwrap())).unwrap();
+        let mut saw_turn = false;
+        let mut ticks = 0;
+        while !(saw_turn && ticks >= 3) {
+            if let ServerMessage::Events { events, .. } = _receive(&mut player1) {
+                for event in events {
+                    match event.kind {
+                        EventKind::SnakeTurned { snake_id, .. } if snake_id == snake2_id => saw_turn = true,
+                        EventKind::ClockAdvanced { .. } => ticks += 1,
+                        EventKind::SnakeKilled { snake_id } => panic!("Snake {} died", snake_id),
+                        _ => (),
+                    }
+                }
+            }
+        }
+
+        // nonsense is answered, not fatal
+        player2.send(Message::Text("{}".to_string())).unwrap();
+        loop {
+            match _receive(&mut player2) {
+                ServerMessage::Error { .. } => break,
+                ServerMessage::Events { .. } | ServerMessage::Ping { .. } => (),
+                other => panic!("Expected an error, got {:?}", other),
+            }
+        }
+        server.stop();
+    }
+
+    #[test]
+    fn t_reconnect() {
+        let config = ServerConfig { tick_length: Duration::from_millis(50), ..ServerConfig::default() };
+        let mut server = Server::bind("127.0.0.1:0", config).unwrap().spawn().unwrap();
+        let mut player = _connect(server.local_addr());
+        let (snake_id, session) = match _receive(&mut player) {
+            ServerMessage::Welcome { snake_id, session, missed, .. } => {
+                assert!(missed.is_empty());
+                (snake_id, session)
+            }
+            other => panic!("Expected a welcome, got {:?}", other),
+        };
+        let last_sequence = loop {
+            if let ServerMessage::Events { events, .. } = _receive(&mut player) {
+                break events.last().unwrap().sequence;
+            }
+        };
+
+        // the connection drops, and the game goes on without the player
+        drop(player);
+        thread::sleep(Duration::from_millis(200));
+        let mut stranger = _connect_with(server.local_addr(), "?session=guess");
+        match _receive(&mut stranger) {
+            ServerMessage::Error { message } => assert_eq!("No such session!", message),
+            other => panic!("Expected an error, got {:?}", other),
+        }
+
+        // back with the session, they get their snake and what they missed
+        let mut player = _connect_with(server.local_addr(), &format!("?session={}&after={}", session, last_sequence));
+        match _receive(&mut player) {
+            ServerMessage::Welcome { snake_id: returned_id, session: returned_session, snapshot, missed, .. } => {
+                assert_eq!((snake_id, &session), (returned_id, &returned_session));
+                assert!(snapshot.snakes.iter().any(|snake| snake.snake_id == snake_id));
+                assert_eq!(last_sequence + 1, missed[0].sequence);
+                assert!(missed.iter().any(|event| event.kind.get_name() == "clock_advanced"));
+            }
+            other => panic!("Expected a welcome, got {:?}", other),
+        }
+
+        // and coming back again takes over from that connection
+        let mut again = _connect_with(server.local_addr(), &format!("?session={}", session));
+        match _receive(&mut again) {
+            ServerMessage::Welcome { snake_id: returned_id, .. } => assert_eq!(snake_id, returned_id),
+            other => panic!("Expected a welcome, got {:?}", other),
+        }
+        loop {
+            match _receive(&mut player) {
+                ServerMessage::Error { message } => {
+                    assert_eq!("Reconnected from somewhere else", message);
+                    break;
+                }
+                ServerMessage::Events { .. } | ServerMessage::Ping { .. } => (),
+                other => panic!("Expected an error, got {:?}", other),
+            }
+        }
+        server.stop();
+    }
+}
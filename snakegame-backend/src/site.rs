@@ -0,0 +1,195 @@
+pub mod site {
+    // The static website, served from the game server's port so the pages and
+    // the game's socket share an origin.
+    //
+    // Everything under the site's directory is read in once, at startup; only
+    // what was there then is served, so no request can reach outside it. The
+    // page's footer shows the build: index_template.html is filled in from the
+    // VERSION file and served as index.html. Pages are checked with the server
+    // each time they're loaded, by ETag, and everything else is cached a while.
+    use crate::http::http::{HttpRequest, HttpResponse};
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::io;
+    use std::path::Path;
+
+    const INDEX: &str = "index.html";
+    const TEMPLATE: &str = "index_template.html";
+    const BUILD_PLACEHOLDER: &str = "__BUILD__";
+    // fonts and styles don't change between builds often enough to matter
+    const ASSET_MAX_AGE: u32 = 3600;
+
+    struct Asset {
+        content_type: &'static str,
+        body: Vec<u8>,
+        etag: String,
+    }
+
+    pub struct Site {
+        // by path, e.g. /snakegame.css
+        assets: BTreeMap<String, Asset>,
+    }
+
+    impl Site {
+        // The site in root, for the version given like the VERSION file has it,
+        // e.g. "Version 0.0.0.25"
+        pub fn load(root: &Path, version: &str) -> io::Result<Site> {
+            let mut site = Site { assets: BTreeMap::new() };
+            site.load_dir(root, "")?;
+            let template = root.join(TEMPLATE);
+            if template.is_file() {
+                let page = fs::read_to_string(template)?.replace(BUILD_PLACEHOLDER, build_number(version));
+                site.add(&format!("/{}", INDEX), page.into_bytes());
+            }
+            Ok(site)
+        }
+
+        fn load_dir(&mut self, dir: &Path, prefix: &str) -> io::Result<()> {
+            for entry in fs::read_dir(dir)? {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().to_string();
+                // nothing hidden, and the template only as the page it makes
+                if name.starts_with('.') || (prefix.is_empty() && name == TEMPLATE) {
+                    continue;
+                }
+                let path = format!("{}/{}", prefix, name);
+                if entry.file_type()?.is_dir() {
+                    self.load_dir(&entry.path(), &path)?;
+                } else {
+                    self.add(&path, fs::read(entry.path())?);
+                }
+            }
+            Ok(())
+        }
+
+        fn add(&mut self, path: &str, body: Vec<u8>) {
+            let etag = format!("\"{:08x}\"", crc32fast::hash(&body));
+            self.assets.insert(path.to_string(), Asset { content_type: content_type(path), body, etag });
+        }
+
+        pub fn get_paths(&self) -> Vec<&str> {
+            self.assets.keys().map(String::as_str).collect()
+        }
+
+        pub fn respond(&self, request: &HttpRequest) -> HttpResponse {
+            if request.method != "GET" {
+                return HttpResponse::error(405, "Method not allowed").with_header("Allow", "GET, HEAD");
+            }
+            let path = if request.path.ends_with('/') {
+                format!("{}{}", request.path, INDEX)
+            } else {
+                request.path.clone()
+            };
+            let asset = match self.assets.get(&path) {
+                Some(asset) => asset,
+                None => return HttpResponse::error(404, &format!("Nothing at {}", request.path)),
+            };
+            let cache_control = if asset.content_type.starts_with("text/html") {
+                "no-cache".to_string()
+            } else {
+                format!("public, max-age={}", ASSET_MAX_AGE)
+            };
+            let unchanged = match request.get_header("if-none-match") {
+                Some(tags) => tags.split(',').any(|tag| tag.trim() == asset.etag || tag.trim() == "*"),
+                None => false,
+            };
+            let response = if unchanged {
+                HttpResponse::empty(304)
+            } else {
+                HttpResponse::empty(200)
+                    .with_header("Content-Type", asset.content_type)
+                    .with_body(asset.body.clone())
+            };
+            response.with_header("ETag", &asset.etag).with_header("Cache-Control", &cache_control)
+        }
+    }
+
+    // The build is the last part of the version, e.g. 25 for "Version 0.0.0.25"
+    pub fn build_number(version: &str) -> &str {
+        let version = version.trim();
+        match version.rfind('.') {
+            Some(idx) => &version[idx + 1..],
+            None => version,
+        }
+    }
+
+    pub fn content_type(path: &str) -> &'static str {
+        let extension = match path.rfind('.') {
+            Some(idx) => path[idx + 1..].to_ascii_lowercase(),
+            None => String::new(),
+        };
+        match extension.as_str() {
+            "html" | "htm" => "text/html; charset=utf-8",
+            "css" => "text/css; charset=utf-8",
+            "js" => "text/javascript; charset=utf-8",
+            "json" => "application/json",
+            "txt" => "text/plain; charset=utf-8",
+            "ttf" => "font/ttf",
+            "otf" => "font/otf",
+            "woff" => "font/woff",
+            "woff2" => "font/woff2",
+            "png" => "image/png",
+            "jpg" | "jpeg" => "image/jpeg",
+            "gif" => "image/gif",
+            "svg" => "image/svg+xml",
+            "ico" => "image/x-icon",
+            _ => "application/octet-stream",
+        }
+    }
+
+    fn _get(path: &str, if_none_match: Option<&str>) -> HttpRequest {
+        HttpRequest {
+            method: "GET".to_string(),
+            path: path.to_string(),
+            query: Vec::new(),
+            headers: if_none_match.map(|tags| ("If-None-Match".to_string(), tags.to_string())).into_iter().collect(),
+            body: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn t_public_html() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../public-html");
+        let site = Site::load(&root, "Version 0.0.0.25\n").unwrap();
+        assert_eq!(vec!["/Trattatello.ttf", "/index.html", "/snakegame.css"], site.get_paths());
+
+        // the page is made from the template, for this build
+        let response = site.respond(&_get("/", None));
+        assert_eq!(200, response.status);
+        assert_eq!(Some("text/html; charset=utf-8"), response.get_header("Content-Type"));
+        assert_eq!(Some("no-cache"), response.get_header("Cache-Control"));
+        let page = String::from_utf8(response.body.clone()).unwrap();
+        assert!(page.contains("Version 0.0.0.25<"));
+        assert!(!page.contains(BUILD_PLACEHOLDER));
+        assert_eq!(response, site.respond(&_get("/index.html", None)));
+
+        let response = site.respond(&_get("/Trattatello.ttf", None));
+        assert_eq!(Some("font/ttf"), response.get_header("Content-Type"));
+        assert_eq!(Some("public, max-age=3600"), response.get_header("Cache-Control"));
+        assert_eq!(fs::read(root.join("Trattatello.ttf")).unwrap(), response.body);
+        let response = site.respond(&_get("/snakegame.css", None));
+        assert_eq!(Some("text/css; charset=utf-8"), response.get_header("Content-Type"));
+
+        // a browser that has it already is told so
+        let etag = response.get_header("ETag").unwrap().to_string();
+        let response = site.respond(&_get("/snakegame.css", Some(&format!("\"0\", {}", etag))));
+        assert_eq!(304, response.status);
+        assert!(response.body.is_empty());
+        assert_eq!(200, site.respond(&_get("/snakegame.css", Some("\"0\""))).status);
+
+        for path in ["/index_template.html", "/../VERSION", "/nothing.css"].iter() {
+            assert_eq!(404, site.respond(&_get(path, None)).status, "{}", path);
+        }
+        let mut post = _get("/", None);
+        post.method = "POST".to_string();
+        assert_eq!(405, site.respond(&post).status);
+    }
+
+    #[test]
+    fn t_build_number() {
+        assert_eq!("25", build_number("Version 0.0.0.25\n"));
+        assert_eq!("7", build_number("7"));
+        assert_eq!("application/octet-stream", content_type("/README"));
+        assert_eq!("image/png", content_type("/a.b/snake.PNG"));
+    }
+}
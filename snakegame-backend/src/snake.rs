@@ -1,12 +1,24 @@
 pub mod snake {
     use std::fmt;
     use crate::game::common::{Coordinates,Velocity};
+    use crate::snapshot::snapshot::SnakeSnapshot;
 
     pub struct SweepArea {
         pub start_loc: Coordinates,
         pub end_loc: Coordinates,
         pub width: f64,
     }
+    impl SweepArea {
+        // Simple algorithm - treat the swept area as the rectangle enclosing it, and
+        // anything of the given radius overlapping the rectangle is covered
+        pub fn covers(&self, loc: Coordinates, radius: f64) -> bool {
+            let reach = self.width / 2.0 + radius;
+            loc.x > self.start_loc.x.min(self.end_loc.x) - reach &&
+                loc.x < self.start_loc.x.max(self.end_loc.x) + reach &&
+                loc.y > self.start_loc.y.min(self.end_loc.y) - reach &&
+                loc.y < self.start_loc.y.max(self.end_loc.y) + reach
+        }
+    }
     impl fmt::Display for SweepArea {
         fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
             write!(
@@ -23,6 +35,8 @@ pub mod snake {
         velocity: Velocity,
         last_updated: f64,
         size: f64,
+        score: f64,
+        team: Option<usize>,
     }
     impl fmt::Display for Snake {
         fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -41,12 +55,64 @@ pub mod snake {
                 velocity: Velocity {delta_x:0.0, delta_y:1.0},
                 last_updated: event_time,
                 size: 1.0,
+                score: 0.0,
+                team: None,
+            }
+        }
+
+        pub fn new_on_team(team_id: usize, event_time: f64) -> Snake {
+            let mut new_snake = Snake::new(event_time);
+            new_snake.team = Some(team_id);
+            new_snake
+        }
+
+        pub fn from_snapshot(saved: &SnakeSnapshot) -> Snake {
+            Snake {
+                location: saved.location,
+                velocity: saved.velocity,
+                last_updated: saved.last_updated,
+                size: saved.size,
+                score: saved.score,
+                team: saved.team,
+            }
+        }
+
+        pub fn snapshot(&self, snake_id: usize) -> SnakeSnapshot {
+            SnakeSnapshot {
+                snake_id,
+                location: self.location,
+                velocity: self.velocity,
+                last_updated: self.last_updated,
+                size: self.size,
+                score: self.score,
+                team: self.team,
             }
         }
 
         pub fn turn(&mut self, rad_relative: f64, event_time: f64) -> SweepArea {
             // change the clock for this snake to when the turn happened
             let covered = self.update(event_time);
+            self.rotate(rad_relative);
+            covered
+        }
+
+        pub fn boost(&mut self, speed: f64, event_time: f64) -> SweepArea {
+            // the old speed holds right up to the boost
+            let covered = self.update(event_time);
+            self.set_speed(speed);
+            covered
+        }
+
+        // Keep the heading, change how fast the snake goes along it
+        pub fn set_speed(&mut self, speed: f64) {
+            let old_speed = self.get_speed();
+            if old_speed > 0.0 {
+                self.velocity.delta_x *= speed / old_speed;
+                self.velocity.delta_y *= speed / old_speed;
+            }
+        }
+
+        pub fn rotate(&mut self, rad_relative: f64) {
             // rotate the velocity vector by rad_relative
             // https://matthew-brett.github.io/teaching/rotation_2d.html
             // x2 = x1 * cos(β) − y1 * sin(β)
@@ -55,16 +121,21 @@ pub mod snake {
             let y2 = self.velocity.delta_x  * rad_relative.sin() + self.velocity.delta_y * rad_relative.cos();
             self.velocity.delta_x = x2;
             self.velocity.delta_y = y2;
-            covered
         }
 
         pub fn update(&mut self, game_time: f64) -> SweepArea {
+            let delta_t = game_time - self.last_updated;
+            self.travel(delta_t, game_time)
+        }
+
+        // Move for delta_t seconds' worth of velocity, and call that game_time. Lets
+        // rules that don't move continuously decide how far the snake gets.
+        pub fn travel(&mut self, delta_t: f64, game_time: f64) -> SweepArea {
             let mut covered = SweepArea {
                 start_loc: self.location,
                 end_loc: Coordinates {x:0.0, y:0.0},
                 width: self.size,
             };
-            let delta_t = game_time - self.last_updated;
             self.last_updated = game_time;
             self.location.x += self.velocity.delta_x * delta_t;
             self.location.y += self.velocity.delta_y * delta_t;
@@ -72,8 +143,26 @@ pub mod snake {
             covered
         }
 
+        // Clean up floating point drift for snakes that live on whole numbers
+        pub fn snap_to_grid(&mut self) {
+            self.location.x = self.location.x.round();
+            self.location.y = self.location.y.round();
+            self.velocity.delta_x = self.velocity.delta_x.round();
+            self.velocity.delta_y = self.velocity.delta_y.round();
+        }
+
         pub fn eat(&mut self, nutrition: f64) {
-            self.size += nutrition / 10.0;
+            self.feed(nutrition, nutrition / 10.0);
+        }
+
+        pub fn feed(&mut self, nutrition: f64, growth: f64) {
+            self.size += growth;
+            self.score += nutrition;
+        }
+
+        // Put a new snake where it's born
+        pub fn set_location(&mut self, location: Coordinates) {
+            self.location = location;
         }
 
         pub fn get_location(&self) -> Coordinates {
@@ -84,9 +173,25 @@ pub mod snake {
             self.velocity
         }
 
+        pub fn get_speed(&self) -> f64 {
+            self.velocity.delta_x.hypot(self.velocity.delta_y)
+        }
+
         pub fn get_size(&self) -> f64 {
             self.size
         }
+
+        pub fn get_last_updated(&self) -> f64 {
+            self.last_updated
+        }
+
+        pub fn get_score(&self) -> f64 {
+            self.score
+        }
+
+        pub fn get_team(&self) -> Option<usize> {
+            self.team
+        }
     }
 
     #[test]
@@ -103,6 +208,22 @@ pub mod snake {
         assert_eq!(1.0,vel.delta_y);
 
         assert_eq!(1.0,test_snake.get_size());
+
+        // new snakes haven't scored and play alone
+        assert_eq!(0.0,test_snake.get_score());
+        assert_eq!(None,test_snake.get_team());
+    }
+
+    #[test]
+    fn t_new_on_team() {
+        let test_snake = Snake::new_on_team(3, 0.0);
+        assert_eq!(Some(3),test_snake.get_team());
+
+        // apart from the team, team snakes start out like any other
+        let loc = test_snake.get_location();
+        assert_eq!(0.0,loc.x);
+        assert_eq!(0.0,loc.y);
+        assert_eq!(1.0,test_snake.get_size());
     }
 
     #[test]
@@ -147,7 +268,7 @@ pub mod snake {
 
     #[test]
     fn t_turn() {
-        const PI:f64 = 3.14159;
+        use std::f64::consts::{PI, FRAC_1_SQRT_2};
         use assert_approx_eq::assert_approx_eq;
         let mut test_snake = Snake::new(0.0);
 
@@ -187,22 +308,91 @@ pub mod snake {
     assert_approx_eq!(-1.0,coverage.end_loc.y,1e-5);
     assert_eq!(1.0,coverage.width);
     let vel = test_snake.get_velocity();
-    assert_approx_eq!( 0.7071,vel.delta_x,1e-5);
-    assert_approx_eq!(-0.7071,vel.delta_y,1e-5);
+    assert_approx_eq!( FRAC_1_SQRT_2,vel.delta_x,1e-5);
+    assert_approx_eq!(-FRAC_1_SQRT_2,vel.delta_y,1e-5);
 
         // snake should be at pos (.71,-1.71) after two seconds
         let coverage = test_snake.update(2.0);
         assert_approx_eq!( 0.0,coverage.start_loc.x,1e-5);
         assert_approx_eq!(-1.0,coverage.start_loc.y,1e-5);
-        assert_approx_eq!( 0.7071,coverage.end_loc.x,1e-5);
-        assert_approx_eq!(-1.7071,coverage.end_loc.y,1e-5);
+        assert_approx_eq!( FRAC_1_SQRT_2,coverage.end_loc.x,1e-5);
+        assert_approx_eq!(-1.0 - FRAC_1_SQRT_2,coverage.end_loc.y,1e-5);
         assert_eq!(1.0,coverage.width);
         let loc = test_snake.get_location();
-        assert_approx_eq!( 0.7071,loc.x,1e-5);
-        assert_approx_eq!(-1.7071,loc.y,1e-5);
+        assert_approx_eq!( FRAC_1_SQRT_2,loc.x,1e-5);
+        assert_approx_eq!(-1.0 - FRAC_1_SQRT_2,loc.y,1e-5);
 
     }
 
+    #[test]
+    fn t_boost() {
+        let mut test_snake = Snake::new(0.0);
+        assert_eq!(1.0,test_snake.get_speed());
+
+        // the old speed holds until the boost, then the heading keeps going faster
+        let coverage = test_snake.boost(2.0,1.0);
+        assert_eq!(0.0,coverage.start_loc.y);
+        assert_eq!(1.0,coverage.end_loc.y);
+        assert_eq!(2.0,test_snake.get_speed());
+        let vel = test_snake.get_velocity();
+        assert_eq!(0.0,vel.delta_x);
+        assert_eq!(2.0,vel.delta_y);
+
+        test_snake.update(2.0);
+        assert_eq!(3.0,test_snake.get_location().y);
+    }
+
+    #[test]
+    fn t_covers() {
+        let coverage = SweepArea {
+            start_loc: Coordinates {x:0.0, y:0.0},
+            end_loc: Coordinates {x:0.0, y:2.0},
+            width: 1.0,
+        };
+        assert!(coverage.covers(Coordinates {x:0.0, y:1.0}, 0.0));
+        assert!(coverage.covers(Coordinates {x:0.4, y:2.4}, 0.0));
+        assert!(!coverage.covers(Coordinates {x:0.6, y:1.0}, 0.0));
+        assert!(!coverage.covers(Coordinates {x:0.0, y:-0.6}, 0.0));
+
+        // something with a radius of its own is covered from further away
+        assert!(coverage.covers(Coordinates {x:0.6, y:1.0}, 0.5));
+        assert!(!coverage.covers(Coordinates {x:1.1, y:1.0}, 0.5));
+    }
+
+    #[test]
+    fn t_travel() {
+        let mut test_snake = Snake::new(0.0);
+
+        // snake goes as far as it's told, whatever the clock says
+        let coverage = test_snake.travel(2.0, 1.0);
+        assert_eq!(0.0,coverage.start_loc.y);
+        assert_eq!(2.0,coverage.end_loc.y);
+        assert_eq!(1.0,test_snake.get_last_updated());
+
+        let coverage = test_snake.travel(0.0, 1.5);
+        assert_eq!(2.0,coverage.start_loc.y);
+        assert_eq!(2.0,coverage.end_loc.y);
+        assert_eq!(1.5,test_snake.get_last_updated());
+    }
+
+    #[test]
+    fn t_snap_to_grid() {
+        use std::f64::consts::FRAC_PI_2;
+        let mut test_snake = Snake::new(0.0);
+        test_snake.rotate(FRAC_PI_2);
+        test_snake.update(3.0);
+        // a quarter turn isn't exact in floating point
+        assert_ne!(0.0,test_snake.get_velocity().delta_y);
+
+        test_snake.snap_to_grid();
+        let vel = test_snake.get_velocity();
+        assert_eq!(-1.0,vel.delta_x);
+        assert_eq!(0.0,vel.delta_y);
+        let loc = test_snake.get_location();
+        assert_eq!(-3.0,loc.x);
+        assert_eq!(0.0,loc.y);
+    }
+
     #[test]
     fn t_eat() {
         let mut test_snake = Snake::new(0.0);
@@ -210,6 +400,12 @@ pub mod snake {
 
         test_snake.eat(10.0);
         assert_eq!(2.0,test_snake.get_size());
+        assert_eq!(10.0,test_snake.get_score());
+
+        // feeding lets the caller decide how much the snake grows
+        test_snake.feed(1.0, 1.0);
+        assert_eq!(3.0,test_snake.get_size());
+        assert_eq!(11.0,test_snake.get_score());
     }
 
     #[test]
@@ -0,0 +1,363 @@
+pub mod snapshot {
+    // Everything needed to rebuild a running game: Game::snapshot() takes one,
+    // Game::restore() turns it back into a game that plays on exactly as the
+    // original would have. The event queue isn't part of it - a restored game
+    // starts with nothing logged, though its events carry on the numbering.
+    use crate::game::common::{Coordinates, Velocity};
+    use crate::game::game::GameConfig;
+    use std::fmt;
+    #[cfg(feature = "serde")]
+    use serde::{Deserialize, Serialize};
+
+    // Bump whenever the snapshot format changes, and teach upgrade() to bring
+    // the previous version forward
+    pub const SNAPSHOT_VERSION: u32 = 5;
+
+    #[derive(Clone, PartialEq, Debug)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct GameSnapshot {
+        pub version: u32,
+        pub config: GameConfig,
+        pub seed: u64,
+        pub time: f64,
+        // since version 3
+        pub tick: u64,
+        // sequence number of the next event logged, since version 4
+        pub next_sequence: u64,
+        // since version 2
+        pub last_state_snapshot: f64,
+        pub next_snake_id: usize,
+        pub next_food_id: usize,
+        // in id order
+        pub snakes: Vec<SnakeSnapshot>,
+        pub food: Vec<FoodSnapshot>,
+        pub team_scores: Vec<TeamScore>,
+        // in id order, only for snakes that have been renamed, since version 5
+        pub names: Vec<SnakeName>,
+    }
+
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct SnakeSnapshot {
+        pub snake_id: usize,
+        pub location: Coordinates,
+        pub velocity: Velocity,
+        pub last_updated: f64,
+        pub size: f64,
+        pub score: f64,
+        pub team: Option<usize>,
+    }
+
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct FoodSnapshot {
+        pub food_id: usize,
+        pub location: Coordinates,
+        pub nutrition: f64,
+        // since version 5
+        pub added: f64,
+    }
+
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct TeamScore {
+        pub team_id: usize,
+        pub score: f64,
+    }
+
+    #[derive(Clone, PartialEq, Debug)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct SnakeName {
+        pub snake_id: usize,
+        pub name: String,
+    }
+
+    #[derive(Debug, PartialEq)]
+    pub enum SnapshotError {
+        // from a newer version of the game than this one, or not versioned at all
+        UnsupportedVersion(u32),
+        // couldn't be read as a snapshot
+        Malformed(String),
+        // reads fine, but doesn't describe a game that could exist
+        Inconsistent(String),
+    }
+    impl std::error::Error for SnapshotError {}
+    impl fmt::Display for SnapshotError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                SnapshotError::UnsupportedVersion(version) => write!(f, "Unsupported snapshot version {}!", version),
+                SnapshotError::Malformed(reason) => write!(f, "Malformed snapshot: {}!", reason),
+                SnapshotError::Inconsistent(reason) => write!(f, "Inconsistent snapshot: {}!", reason),
+            }
+        }
+    }
+
+    impl GameSnapshot {
+        // The config has to be playable, and ids have to be unique, in order,
+        // and below the counters that hand out new ones, or the restored game
+        // would reuse them
+        pub fn check(&self) -> Result<(), SnapshotError> {
+            if self.version != SNAPSHOT_VERSION {
+                return Err(SnapshotError::UnsupportedVersion(self.version));
+            }
+            if let Err(bad) = self.config.check() {
+                return Err(SnapshotError::Inconsistent(bad.0.to_string()));
+            }
+            fn in_order(ids: &[usize], next_id: usize) -> bool {
+                ids.windows(2).all(|pair| pair[0] < pair[1]) && ids.last().is_none_or(|last| *last < next_id)
+            }
+            let snake_ids: Vec<usize> = self.snakes.iter().map(|snake| snake.snake_id).collect();
+            if !in_order(&snake_ids, self.next_snake_id) {
+                return Err(SnapshotError::Inconsistent("snake ids out of order or past next_snake_id".to_string()));
+            }
+            let food_ids: Vec<usize> = self.food.iter().map(|food| food.food_id).collect();
+            if !in_order(&food_ids, self.next_food_id) {
+                return Err(SnapshotError::Inconsistent("food ids out of order or past next_food_id".to_string()));
+            }
+            let team_ids: Vec<usize> = self.team_scores.iter().map(|team| team.team_id).collect();
+            if !team_ids.windows(2).all(|pair| pair[0] < pair[1]) {
+                return Err(SnapshotError::Inconsistent("team ids out of order".to_string()));
+            }
+            let name_ids: Vec<usize> = self.names.iter().map(|name| name.snake_id).collect();
+            if !name_ids.windows(2).all(|pair| pair[0] < pair[1]) || !name_ids.iter().all(|id| snake_ids.contains(id)) {
+                return Err(SnapshotError::Inconsistent("names out of order or for missing snakes".to_string()));
+            }
+            Ok(())
+        }
+
+        #[cfg(feature = "serde")]
+        pub fn to_json(&self) -> String {
+            serde_json::to_string(self).expect("Snapshots always serialize")
+        }
+
+        // Reads snapshots from this version of the game or any earlier one
+        #[cfg(feature = "serde")]
+        pub fn from_json(json: &str) -> Result<GameSnapshot, SnapshotError> {
+            let value: serde_json::Value = serde_json::from_str(json).map_err(|err| SnapshotError::Malformed(err.to_string()))?;
+            let value = upgrade(value)?;
+            serde_json::from_value(value).map_err(|err| SnapshotError::Malformed(err.to_string()))
+        }
+    }
+
+    // Bring a serialized snapshot up to SNAPSHOT_VERSION, one version at a time.
+    // Each format change adds a step here from the version before it.
+    #[cfg(feature = "serde")]
+    fn upgrade(mut snapshot: serde_json::Value) -> Result<serde_json::Value, SnapshotError> {
+        use serde_json::Value;
+        loop {
+            let version = match snapshot.get("version").and_then(|version| version.as_u64()) {
+                Some(version) => version as u32,
+                None => return Err(SnapshotError::UnsupportedVersion(0)),
+            };
+            let fields = match snapshot.as_object_mut() {
+                Some(fields) => fields,
+                None => return Err(SnapshotError::Malformed("not an object".to_string())),
+            };
+            match version {
+                SNAPSHOT_VERSION => return Ok(snapshot),
+                1 => {
+                    // version 2 added periodic StateSnapshot events, which older games never sent
+                    if let Some(Value::Object(config)) = fields.get_mut("config") {
+                        config.insert("snapshot_interval".to_string(), Value::Null);
+                    }
+                    let time = fields.get("time").cloned().unwrap_or(Value::Null);
+                    fields.insert("last_state_snapshot".to_string(), time);
+                    fields.insert("version".to_string(), Value::from(2));
+                }
+                2 => {
+                    // version 3 added the tick count, which older games didn't keep
+                    fields.insert("tick".to_string(), Value::from(0));
+                    fields.insert("version".to_string(), Value::from(3));
+                }
+                3 => {
+                    // version 4 numbered events; start again from 0
+                    fields.insert("next_sequence".to_string(), Value::from(0));
+                    fields.insert("version".to_string(), Value::from(4));
+                }
+                4 => {
+                    // version 5 added snake names and expiring food; older food
+                    // counts as put down now, and never expires anyway
+                    if let Some(Value::Object(config)) = fields.get_mut("config") {
+                        config.insert("food_lifetime".to_string(), Value::Null);
+                    }
+                    let time = fields.get("time").cloned().unwrap_or(Value::Null);
+                    if let Some(Value::Array(food)) = fields.get_mut("food") {
+                        for food in food.iter_mut() {
+                            if let Value::Object(food) = food {
+                                food.insert("added".to_string(), time.clone());
+                            }
+                        }
+                    }
+                    fields.insert("names".to_string(), Value::Array(Vec::new()));
+                    fields.insert("version".to_string(), Value::from(5));
+                }
+                _ => return Err(SnapshotError::UnsupportedVersion(version)),
+            }
+        }
+    }
+
+    fn _play_some_game(game: &mut crate::game::game::Game) {
+        use std::f64::consts::PI;
+        let snake1_id = game.create_snake(game.get_time()).unwrap();
+        game.add_food(0.0, 2.0, game.get_time()).unwrap();
+        game.add_food(-3.0, 2.0, game.get_time()).unwrap();
+        for _i in 0..10 {
+            game.advance_clock(0.25);
+        }
+        game.turn_snake(snake1_id, PI / 2.0, game.get_time()).unwrap();
+        let snake2_id = game.create_team_snake(1, game.get_time()).unwrap();
+        game.turn_snake(snake2_id, PI, game.get_time()).unwrap();
+        game.rename_snake(snake2_id, "Kaa", game.get_time()).unwrap();
+        for _i in 0..40 {
+            game.advance_clock(0.1);
+        }
+    }
+
+    fn _drain_events(game: &mut crate::game::game::Game) -> Vec<crate::eventqueue::eventqueue::Event> {
+        let mut events = Vec::new();
+        while let Some(event) = game.get_event_queue_mut().get_event() {
+            events.push(event);
+        }
+        events
+    }
+
+    #[test]
+    fn t_snapshot_restore() {
+        use crate::game::game::Game;
+        let mut original = Game::new_seeded(GameConfig::default(), 42);
+        _play_some_game(&mut original);
+        _drain_events(&mut original);
+
+        let snapshot = original.snapshot();
+        assert_eq!(SNAPSHOT_VERSION, snapshot.version);
+        assert_eq!(42, snapshot.seed);
+        assert_eq!(50, snapshot.tick);
+        assert_eq!(2, snapshot.snakes.len());
+        assert_eq!(Some(1), snapshot.snakes[1].team);
+        let mut restored = Game::restore(&snapshot).expect("Snapshot should restore");
+        assert_eq!(snapshot, restored.snapshot());
+
+        // both games play on the same way from here
+        _play_some_game(&mut original);
+        _play_some_game(&mut restored);
+        assert_eq!(original.snapshot(), restored.snapshot());
+        let original_events = _drain_events(&mut original);
+        assert!(!original_events.is_empty());
+        assert_eq!(original_events, _drain_events(&mut restored));
+    }
+
+    #[test]
+    fn t_snapshot_restore_grid() {
+        use crate::game::game::Game;
+        use crate::rules::rules::GameMode;
+        use crate::team::team::{TeamRules, WinCondition};
+        let config = GameConfig {
+            mode: GameMode::classic_grid(),
+            team_rules: TeamRules { friendly_fire: false, win_condition: WinCondition::ScoreTarget(5.0) },
+            snapshot_interval: Some(0.75),
+            ..GameConfig::default()
+        };
+        let mut original = Game::new_seeded(config, 7);
+        _play_some_game(&mut original);
+        _drain_events(&mut original);
+
+        let mut restored = Game::restore(&original.snapshot()).expect("Snapshot should restore");
+        assert_eq!(config, restored.get_config());
+        _play_some_game(&mut original);
+        _play_some_game(&mut restored);
+        assert_eq!(original.snapshot(), restored.snapshot());
+        // including when the state snapshot events go out
+        assert_eq!(_drain_events(&mut original), _drain_events(&mut restored));
+        assert_eq!(original.get_team_score(1).unwrap(), restored.get_team_score(1).unwrap());
+    }
+
+    #[test]
+    fn t_restore_inconsistent() {
+        use crate::game::game::Game;
+        let mut game = Game::new_seeded(GameConfig::default(), 42);
+        _play_some_game(&mut game);
+        let snapshot = game.snapshot();
+
+        let mut bad = snapshot.clone();
+        bad.next_snake_id = 1;
+        assert!(matches!(Game::restore(&bad).err(), Some(SnapshotError::Inconsistent(_))));
+
+        let mut bad = snapshot.clone();
+        bad.food.reverse();
+        assert!(matches!(Game::restore(&bad).err(), Some(SnapshotError::Inconsistent(_))));
+
+        let mut bad = snapshot.clone();
+        bad.names.push(SnakeName { snake_id: 99, name: "Nobody".to_string() });
+        assert!(matches!(Game::restore(&bad).err(), Some(SnapshotError::Inconsistent(_))));
+
+        let mut bad = snapshot;
+        bad.version = SNAPSHOT_VERSION + 1;
+        assert_eq!(Some(SnapshotError::UnsupportedVersion(SNAPSHOT_VERSION + 1)), Game::restore(&bad).err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn t_snapshot_json() {
+        use crate::game::game::Game;
+        let mut original = Game::new_seeded(GameConfig::default(), 42);
+        _play_some_game(&mut original);
+        let snapshot = original.snapshot();
+
+        let json = snapshot.to_json();
+        assert!(json.starts_with(&format!(r#"{{"version":{},"#, SNAPSHOT_VERSION)));
+        assert!(json.contains(r#""mode":{"type":"continuous"}"#));
+        assert!(json.contains(r#""win_condition":{"type":"no_winner"}"#));
+        assert_eq!(snapshot, GameSnapshot::from_json(&json).unwrap());
+
+        // a restored game picks up where the saved one left off
+        let mut restored = Game::restore(&GameSnapshot::from_json(&json).unwrap()).unwrap();
+        _play_some_game(&mut original);
+        _play_some_game(&mut restored);
+        assert_eq!(original.snapshot(), restored.snapshot());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn t_snapshot_upgrade() {
+        use crate::game::game::Game;
+        let version1 = r#"{"version":1,"config":{"size":100.0,"mode":{"type":"continuous"},
+            "team_rules":{"friendly_fire":true,"win_condition":{"type":"no_winner"}}},
+            "seed":42,"time":2.5,"next_snake_id":1,"next_food_id":1,
+            "snakes":[{"snake_id":0,"location":{"x":0.0,"y":2.5},"velocity":{"delta_x":0.0,"delta_y":1.0},
+                "last_updated":2.5,"size":1.0,"score":0.0,"team":null}],
+            "food":[],"team_scores":[]}"#;
+        let snapshot = GameSnapshot::from_json(version1).expect("Version 1 snapshots should upgrade");
+        assert_eq!(SNAPSHOT_VERSION, snapshot.version);
+        assert_eq!(None, snapshot.config.snapshot_interval);
+        assert_eq!(2.5, snapshot.last_state_snapshot);
+        assert_eq!(0, snapshot.tick);
+        assert_eq!(0, snapshot.next_sequence);
+        assert_eq!(None, snapshot.config.food_lifetime);
+        assert!(snapshot.names.is_empty());
+
+        let mut restored = Game::restore(&snapshot).unwrap();
+        restored.advance_clock(0.5);
+        assert_eq!(3.0, restored.get_snake(0).unwrap().get_location().y);
+
+        // version 4 food counts as put down when the snapshot was taken
+        let version4 = r#"{"version":4,"config":{"size":100.0,"mode":{"type":"continuous"},
+            "team_rules":{"friendly_fire":true,"win_condition":{"type":"no_winner"}},"snapshot_interval":null},
+            "seed":42,"time":2.5,"tick":10,"next_sequence":12,"last_state_snapshot":0.0,"next_snake_id":0,"next_food_id":1,
+            "snakes":[],"food":[{"food_id":0,"location":{"x":1.0,"y":1.0},"nutrition":1.0}],"team_scores":[]}"#;
+        let snapshot = GameSnapshot::from_json(version4).expect("Version 4 snapshots should upgrade");
+        assert_eq!(2.5, snapshot.food[0].added);
+        assert!(Game::restore(&snapshot).is_ok());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn t_snapshot_json_versions() {
+        let unversioned = r#"{"seed":1}"#;
+        assert_eq!(Some(SnapshotError::UnsupportedVersion(0)), GameSnapshot::from_json(unversioned).err());
+        let from_the_future = r#"{"version":99,"seed":1}"#;
+        assert_eq!(Some(SnapshotError::UnsupportedVersion(99)), GameSnapshot::from_json(from_the_future).err());
+        let truncated = format!(r#"{{"version":{},"seed":1}}"#, SNAPSHOT_VERSION);
+        assert!(matches!(GameSnapshot::from_json(&truncated).err(), Some(SnapshotError::Malformed(_))));
+        assert!(matches!(GameSnapshot::from_json("not json").err(), Some(SnapshotError::Malformed(_))));
+    }
+}
@@ -0,0 +1,85 @@
+pub mod team {
+    use std::fmt;
+    #[cfg(feature = "serde")]
+    use serde::{Deserialize, Serialize};
+
+    // How a team game is decided
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    #[cfg_attr(feature = "serde", serde(tag = "type", content = "target", rename_all = "snake_case"))]
+    pub enum WinCondition {
+        // play until the game is stopped from outside
+        NoWinner,
+        // the only team with snakes still alive wins
+        LastTeamStanding,
+        // the first team whose members' scores add up to the target wins
+        ScoreTarget(f64),
+    }
+    impl fmt::Display for WinCondition {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                WinCondition::NoWinner => write!(f, "no winner"),
+                WinCondition::LastTeamStanding => write!(f, "last team standing"),
+                WinCondition::ScoreTarget(target) => write!(f, "first team to {:.1}", target),
+            }
+        }
+    }
+
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct TeamRules {
+        // when false, teammates pass through each other instead of colliding
+        pub friendly_fire: bool,
+        pub win_condition: WinCondition,
+    }
+    impl Default for TeamRules {
+        fn default() -> Self {
+            TeamRules {
+                friendly_fire: true,
+                win_condition: WinCondition::NoWinner,
+            }
+        }
+    }
+
+    impl TeamRules {
+        // Two snakes collide unless they are teammates and friendly fire is off
+        pub fn can_collide(&self, team1: Option<usize>, team2: Option<usize>) -> bool {
+            if self.friendly_fire {
+                return true;
+            }
+            match (team1, team2) {
+                (Some(t1), Some(t2)) => t1 != t2,
+                _ => true,
+            }
+        }
+    }
+
+    #[test]
+    fn t_default() {
+        let rules = TeamRules::default();
+        assert!(rules.friendly_fire);
+        assert_eq!(WinCondition::NoWinner, rules.win_condition);
+    }
+
+    #[test]
+    fn t_can_collide() {
+        let mut rules = TeamRules::default();
+        assert!(rules.can_collide(Some(1), Some(1)));
+        assert!(rules.can_collide(Some(1), Some(2)));
+        assert!(rules.can_collide(None, None));
+
+        rules.friendly_fire = false;
+        assert!(!rules.can_collide(Some(1), Some(1)));
+        assert!(rules.can_collide(Some(1), Some(2)));
+        assert!(rules.can_collide(Some(1), None));
+        // snakes without a team are nobody's teammate
+        assert!(rules.can_collide(None, None));
+    }
+
+    #[test]
+    fn t_win_condition_fmt() {
+        assert_eq!("no winner", format!("{}", WinCondition::NoWinner));
+        assert_eq!("last team standing", format!("{}", WinCondition::LastTeamStanding));
+        assert_eq!("first team to 10.0", format!("{}", WinCondition::ScoreTarget(10.0)));
+    }
+}
@@ -0,0 +1,420 @@
+pub mod wamp {
+    // Just enough of a WAMP v2 router (the Basic Profile, JSON serialization) to
+    // play the game from standard clients like Autobahn. The game is the only
+    // publisher and callee: every event is published on its own topic,
+    //   snakegame.event.<kind name>, e.g. snakegame.event.snake_ate
+    // with the event's JSON as the only argument, and these procedures can be called:
+    //   snakegame.create_snake             -> [snake_id]
+    //   snakegame.turn_snake [snake_id, rad_relative, event_time (optional)]
+    //   snakegame.get_snake [snake_id]     -> [snake, as in a GameSnapshot]
+    //   snakegame.get_snapshot             -> [GameSnapshot]
+    //   snakegame.get_time                 -> [time, tick]
+    // Sessions can only turn the snakes they created, and those snakes leave
+    // the game with them. A session gets one snake at a time, or however many
+    // the CommandGuard's limits allow; past that, create_snake is an error.
+    // Turns go through the game's CommandGuard, and a session it kicks is sent
+    // away. Any realm will do.
+    // https://wamp-proto.org/wamp_bp_latest_ietf.html
+    use crate::command::command::{Command, CommandGuard, Rejection};
+    use crate::eventqueue::eventqueue::{Event, SubscriberId};
+    use crate::game::game::Game;
+    use crate::lobby::lobby::Seat;
+    use crate::server::server::{read_events, timed_out};
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::net::TcpStream;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Mutex;
+    use tungstenite::handshake::server::Request;
+    use tungstenite::{Message, WebSocket};
+
+    pub const SUBPROTOCOL: &str = "wamp.2.json";
+    pub const TOPIC_PREFIX: &str = "snakegame.event.";
+
+    const HELLO: u64 = 1;
+    const WELCOME: u64 = 2;
+    const ABORT: u64 = 3;
+    const GOODBYE: u64 = 6;
+    const ERROR: u64 = 8;
+    const PUBLISH: u64 = 16;
+    const SUBSCRIBE: u64 = 32;
+    const SUBSCRIBED: u64 = 33;
+    const UNSUBSCRIBE: u64 = 34;
+    const UNSUBSCRIBED: u64 = 35;
+    const EVENT: u64 = 36;
+    const CALL: u64 = 48;
+    const RESULT: u64 = 50;
+    const REGISTER: u64 = 64;
+
+    // ids are whole numbers JavaScript can hold exactly
+    const MAX_ID: u64 = 1 << 53;
+
+    // Whether the client would like to speak WAMP
+    pub fn offers_subprotocol(request: &Request) -> bool {
+        request
+            .headers()
+            .get_all(tungstenite::http::header::SEC_WEBSOCKET_PROTOCOL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|protocol| protocol.trim() == SUBPROTOCOL)
+    }
+
+    // A WAMP error to send back, as its URI and a message for people
+    type CallError = (&'static str, String);
+
+    struct Session {
+        // until the client says HELLO, all it may do is say HELLO
+        joined: bool,
+        subscriber_id: Option<SubscriberId>,
+        // subscription id of each topic subscribed to
+        subscriptions: HashMap<String, u64>,
+        next_subscription_id: u64,
+        // who the session is to the game's CommandGuard
+        player_id: usize,
+    }
+
+    // What to do after handling a message
+    enum Next {
+        Carry,
+        // the session is over; say this and close
+        Leave(Value),
+    }
+
+    // A WAMP session on the game, for as long as the game stays open
+    pub(crate) fn serve_session(socket: &mut WebSocket<TcpStream>, seat: &Seat) -> tungstenite::Result<()> {
+        let mut session = Session {
+            joined: false,
+            subscriber_id: None,
+            subscriptions: HashMap::new(),
+            next_subscription_id: 1,
+            player_id: seat.guard.lock().unwrap().add_player(),
+        };
+        let result = session.run(socket, &seat.guard, &seat.game, &seat.open);
+
+        let mut guard = seat.guard.lock().unwrap();
+        let mut game = seat.game.lock().unwrap();
+        if let Some(subscriber_id) = session.subscriber_id {
+            game.get_event_queue_mut().unsubscribe(subscriber_id);
+        }
+        let event_time = game.get_time();
+        for snake_id in guard.remove_player(session.player_id) {
+            // some may already have been killed
+            let _ = game.remove_snake(snake_id, event_time);
+        }
+        result
+    }
+
+    impl Session {
+        fn run(&mut self, socket: &mut WebSocket<TcpStream>, guard: &Mutex<CommandGuard>, game: &Mutex<Game>, open: &AtomicBool) -> tungstenite::Result<()> {
+            while open.load(Ordering::SeqCst) {
+                match socket.read() {
+                    Ok(Message::Text(text)) => {
+                        let (replies, next) = self.handle(guard, game, &text);
+                        for reply in replies {
+                            send(socket, &reply)?;
+                        }
+                        if let Next::Leave(goodbye) = next {
+                            send(socket, &goodbye)?;
+                            socket.close(None)?;
+                            return Ok(());
+                        }
+                    }
+                    Ok(Message::Close(_)) => return Ok(()),
+                    Ok(_) => (),
+                    Err(ref error) if timed_out(error) => (),
+                    Err(error) => return Err(error),
+                }
+
+                self.publish_events(socket, game)?;
+            }
+            // the last word from a game that's ending
+            self.publish_events(socket, game)?;
+            send(socket, &json!([GOODBYE, {}, "wamp.close.system_shutdown"]))?;
+            socket.close(None)?;
+            Ok(())
+        }
+
+        fn publish_events(&mut self, socket: &mut WebSocket<TcpStream>, game: &Mutex<Game>) -> tungstenite::Result<()> {
+            if let Some(subscriber_id) = self.subscriber_id {
+                for event in read_events(game, subscriber_id) {
+                    if let Some(publish) = self.publish(&event) {
+                        send(socket, &publish)?;
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        fn handle(&mut self, guard: &Mutex<CommandGuard>, game: &Mutex<Game>, text: &str) -> (Vec<Value>, Next) {
+            let message: Vec<Value> = match serde_json::from_str(text) {
+                Ok(message) => message,
+                Err(_) => return (Vec::new(), abort("Messages must be JSON arrays")),
+            };
+            let code = message.first().and_then(Value::as_u64);
+            if !self.joined {
+                return match code {
+                    Some(HELLO) => {
+                        self.joined = true;
+                        self.subscriber_id = Some(game.lock().unwrap().get_event_queue_mut().subscribe());
+                        let session_id = new_id();
+                        let welcome = json!([WELCOME, session_id, {
+                            "agent": "snakegame-backend",
+                            "roles": {"broker": {}, "dealer": {}},
+                        }]);
+                        (vec![welcome], Next::Carry)
+                    }
+                    _ => (Vec::new(), abort("Say HELLO first")),
+                };
+            }
+            let request_id = message.get(1).and_then(Value::as_u64);
+            match (code, request_id) {
+                (Some(GOODBYE), _) => (Vec::new(), Next::Leave(json!([GOODBYE, {}, "wamp.close.goodbye_and_out"]))),
+                (Some(SUBSCRIBE), Some(request_id)) => {
+                    let topic = message.get(3).and_then(Value::as_str).unwrap_or("");
+                    if !topic.starts_with(TOPIC_PREFIX) {
+                        return (vec![error(SUBSCRIBE, request_id, ("wamp.error.invalid_uri", format!("No such topic {}", topic)))], Next::Carry);
+                    }
+                    let subscription_id = match self.subscriptions.get(topic) {
+                        Some(subscription_id) => *subscription_id,
+                        None => {
+                            let subscription_id = self.next_subscription_id;
+                            self.next_subscription_id += 1;
+                            self.subscriptions.insert(topic.to_string(), subscription_id);
+                            subscription_id
+                        }
+                    };
+                    (vec![json!([SUBSCRIBED, request_id, subscription_id])], Next::Carry)
+                }
+                (Some(UNSUBSCRIBE), Some(request_id)) => {
+                    let subscription_id = message.get(2).and_then(Value::as_u64);
+                    let before = self.subscriptions.len();
+                    self.subscriptions.retain(|_, id| Some(*id) != subscription_id);
+                    if self.subscriptions.len() < before {
+                        (vec![json!([UNSUBSCRIBED, request_id])], Next::Carry)
+                    } else {
+                        (vec![error(UNSUBSCRIBE, request_id, ("wamp.error.no_such_subscription", "Not subscribed".to_string()))], Next::Carry)
+                    }
+                }
+                (Some(CALL), Some(request_id)) => {
+                    let procedure = message.get(3).and_then(Value::as_str).unwrap_or("");
+                    let args = message.get(4).and_then(Value::as_array).cloned().unwrap_or_default();
+                    let reply = match self.call(guard, game, procedure, &args) {
+                        Ok(results) => json!([RESULT, request_id, {}, results]),
+                        Err(call_error) => error(CALL, request_id, call_error),
+                    };
+                    if guard.lock().unwrap().is_kicked(self.player_id) {
+                        return (vec![reply], Next::Leave(json!([GOODBYE, {"message": "Too many bad commands"}, "wamp.close.killed"])));
+                    }
+                    (vec![reply], Next::Carry)
+                }
+                // the game is the only publisher and callee
+                (Some(code @ PUBLISH), Some(request_id)) | (Some(code @ REGISTER), Some(request_id)) => {
+                    (vec![error(code, request_id, ("wamp.error.not_authorized", "Only the game publishes and registers here".to_string()))], Next::Carry)
+                }
+                _ => (Vec::new(), abort("Unexpected message")),
+            }
+        }
+
+        fn call(&mut self, guard: &Mutex<CommandGuard>, game: &Mutex<Game>, procedure: &str, args: &[Value]) -> Result<Vec<Value>, CallError> {
+            let mut guard = guard.lock().unwrap();
+            let mut game = game.lock().unwrap();
+            match procedure {
+                "snakegame.create_snake" => {
+                    let snake_id = guard.create_snake(&mut game, self.player_id).map_err(rejected)?;
+                    Ok(vec![json!(snake_id)])
+                }
+                "snakegame.turn_snake" => {
+                    let snake_id = snake_id_arg(args)?;
+                    let rad_relative = args.get(1).and_then(Value::as_f64).ok_or_else(|| invalid_argument("rad_relative"))?;
+                    let event_time = match args.get(2) {
+                        Some(event_time) => event_time.as_f64().ok_or_else(|| invalid_argument("event_time"))?,
+                        None => game.get_time(),
+                    };
+                    let command = Command::Turn { snake_id, rad_relative, event_time };
+                    guard.apply(&mut game, self.player_id, command).map_err(rejected)?;
+                    Ok(Vec::new())
+                }
+                "snakegame.get_snake" => {
+                    let snake_id = snake_id_arg(args)?;
+                    let the_snake = game.get_snake(snake_id).map_err(|_| not_found(snake_id))?;
+                    Ok(vec![json!(the_snake.snapshot(snake_id))])
+                }
+                "snakegame.get_snapshot" => Ok(vec![json!(game.snapshot())]),
+                "snakegame.get_time" => Ok(vec![json!(game.get_time()), json!(game.get_tick())]),
+                _ => Err(("wamp.error.no_such_procedure", format!("No procedure {}", procedure))),
+            }
+        }
+
+        // The EVENT for a subscriber to this kind of event, if there is one.
+        // Sequence numbers are unique, so they serve as publication ids.
+        fn publish(&self, event: &Event) -> Option<Value> {
+            let topic = format!("{}{}", TOPIC_PREFIX, event.kind.get_name());
+            let subscription_id = self.subscriptions.get(&topic)?;
+            Some(json!([EVENT, subscription_id, event.sequence % MAX_ID + 1, {}, [event]]))
+        }
+    }
+
+    fn new_id() -> u64 {
+        use rand::Rng;
+        rand::thread_rng().gen_range(1, MAX_ID + 1)
+    }
+
+    fn abort(message: &str) -> Next {
+        Next::Leave(json!([ABORT, {"message": message}, "wamp.error.protocol_violation"]))
+    }
+
+    fn error(request_type: u64, request_id: u64, (uri, message): CallError) -> Value {
+        json!([ERROR, request_type, request_id, {}, uri, [message]])
+    }
+
+    fn snake_id_arg(args: &[Value]) -> Result<usize, CallError> {
+        args.first().and_then(Value::as_u64).map(|snake_id| snake_id as usize).ok_or_else(|| invalid_argument("snake_id"))
+    }
+
+    fn invalid_argument(name: &str) -> CallError {
+        ("wamp.error.invalid_argument", format!("Missing or bad {}", name))
+    }
+
+    fn not_found(snake_id: usize) -> CallError {
+        ("snakegame.error.not_found", format!("No snake {}", snake_id))
+    }
+
+    fn rejected(rejection: Rejection) -> CallError {
+        match rejection {
+            Rejection::NotYourSnake { .. } | Rejection::Kicked => ("wamp.error.not_authorized", rejection.to_string()),
+            Rejection::NoSuchSnake { snake_id } => not_found(snake_id),
+            Rejection::TooManySnakes { .. } => ("snakegame.error.too_many_snakes", rejection.to_string()),
+            _ => ("snakegame.error.rejected", rejection.to_string()),
+        }
+    }
+
+    fn send(socket: &mut WebSocket<TcpStream>, message: &Value) -> tungstenite::Result<()> {
+        socket.send(Message::Text(message.to_string()))
+    }
+
+    #[test]
+    fn t_handle() {
+        use crate::command::command::CommandLimits;
+        let game = Mutex::new(Game::new_blank_game());
+        let guard = Mutex::new(CommandGuard::new(CommandLimits { max_strikes: 3, ..CommandLimits::default() }));
+        let mut session = Session {
+            joined: false,
+            subscriber_id: None,
+            subscriptions: HashMap::new(),
+            next_subscription_id: 1,
+            player_id: guard.lock().unwrap().add_player(),
+        };
+
+        // nothing but HELLO until the session is open
+        let (replies, next) = session.handle(&guard, &game, r#"[32,1,{},"snakegame.event.snake_born"]"#);
+        assert!(replies.is_empty());
+        assert!(matches!(next, Next::Leave(ref abort) if abort[0] == json!(ABORT)));
+        let (replies, _next) = session.handle(&guard, &game, r#"[1,"realm1",{"roles":{"subscriber":{},"caller":{}}}]"#);
+        assert_eq!(json!(WELCOME), replies[0][0]);
+        assert!(replies[0][2]["roles"]["broker"].is_object());
+
+        // subscribing twice to a topic gets the same subscription
+        let (replies, _next) = session.handle(&guard, &game, r#"[32,2,{},"snakegame.event.snake_born"]"#);
+        assert_eq!(json!([SUBSCRIBED, 2, 1]), replies[0]);
+        let (replies, _next) = session.handle(&guard, &game, r#"[32,3,{},"snakegame.event.snake_born"]"#);
+        assert_eq!(json!([SUBSCRIBED, 3, 1]), replies[0]);
+        let (replies, _next) = session.handle(&guard, &game, r#"[32,4,{},"elsewhere.topic"]"#);
+        assert_eq!(json!("wamp.error.invalid_uri"), replies[0][4]);
+
+        // calls
+        let (replies, _next) = session.handle(&guard, &game, r#"[48,5,{},"snakegame.create_snake"]"#);
+        assert_eq!(json!([RESULT, 5, {}, [0]]), replies[0]);
+        // and only the one
+        let (replies, _next) = session.handle(&guard, &game, r#"[48,50,{},"snakegame.create_snake"]"#);
+        assert_eq!(json!([ERROR, CALL, 50, {}, "snakegame.error.too_many_snakes", ["Already as many snakes as a player can have (1)!"]]), replies[0]);
+        assert_eq!(vec![0], guard.lock().unwrap().get_snake_ids(session.player_id));
+        let (replies, _next) = session.handle(&guard, &game, r#"[48,6,{},"snakegame.turn_snake",[0,0.5]]"#);
+        assert_eq!(json!([RESULT, 6, {}, []]), replies[0]);
+        let (replies, _next) = session.handle(&guard, &game, r#"[48,7,{},"snakegame.turn_snake",[0]]"#);
+        assert_eq!(json!("wamp.error.invalid_argument"), replies[0][4]);
+        let (replies, _next) = session.handle(&guard, &game, r#"[48,8,{},"snakegame.get_snake",[3]]"#);
+        assert_eq!(json!("snakegame.error.not_found"), replies[0][4]);
+        let (replies, _next) = session.handle(&guard, &game, r#"[48,9,{},"snakegame.get_time"]"#);
+        assert_eq!(json!([RESULT, 9, {}, [0.0, 0]]), replies[0]);
+        let (replies, _next) = session.handle(&guard, &game, r#"[48,10,{},"snakegame.fly"]"#);
+        assert_eq!(json!([ERROR, CALL, 10, {}, "wamp.error.no_such_procedure", ["No procedure snakegame.fly"]]), replies[0]);
+
+        // someone else's snake can't be turned
+        let other_snake_id = game.lock().unwrap().create_snake(0.0).unwrap();
+        let (replies, _next) = session.handle(&guard, &game, &format!(r#"[48,11,{{}},"snakegame.turn_snake",[{},0.5]]"#, other_snake_id));
+        assert_eq!(json!("wamp.error.not_authorized"), replies[0][4]);
+
+        // only the game publishes
+        let (replies, _next) = session.handle(&guard, &game, r#"[16,12,{"acknowledge":true},"snakegame.event.snake_born",[]]"#);
+        assert_eq!(json!("wamp.error.not_authorized"), replies[0][4]);
+
+        let (replies, _next) = session.handle(&guard, &game, r#"[34,13,1]"#);
+        assert_eq!(json!([UNSUBSCRIBED, 13]), replies[0]);
+        let (replies, _next) = session.handle(&guard, &game, r#"[34,14,1]"#);
+        assert_eq!(json!("wamp.error.no_such_subscription"), replies[0][4]);
+
+        // a turn from the future is turned away, and a repeat offender sent off
+        let (replies, next) = session.handle(&guard, &game, r#"[48,15,{},"snakegame.turn_snake",[0,0.5,100.0]]"#);
+        assert_eq!(json!("snakegame.error.rejected"), replies[0][4]);
+        assert!(matches!(next, Next::Carry));
+        let (_replies, next) = session.handle(&guard, &game, r#"[48,16,{},"snakegame.turn_snake",[0,0.5,100.0]]"#);
+        assert!(matches!(next, Next::Leave(ref goodbye) if goodbye[2] == json!("wamp.close.killed")));
+        assert_eq!(3, guard.lock().unwrap().get_security_log().len());
+
+        let (_replies, next) = session.handle(&guard, &game, r#"[6,{},"wamp.close.close_realm"]"#);
+        assert!(matches!(next, Next::Leave(ref goodbye) if *goodbye == json!([GOODBYE, {}, "wamp.close.goodbye_and_out"])));
+    }
+
+    #[test]
+    fn t_router() {
+        use crate::server::server::{Server, ServerConfig};
+        use std::time::Duration;
+        use tungstenite::client::IntoClientRequest;
+        let config = ServerConfig { tick_length: Duration::from_millis(20), ..ServerConfig::default() };
+        let mut server = Server::bind("127.0.0.1:0", config).unwrap().spawn().unwrap();
+
+        let mut request = format!("ws://{}", server.local_addr()).into_client_request().unwrap();
+        request.headers_mut().insert(
+            tungstenite::http::header::SEC_WEBSOCKET_PROTOCOL,
+            tungstenite::http::HeaderValue::from_static(SUBPROTOCOL),
+        );
+        let (mut client, response) = tungstenite::connect(request).unwrap();
+        assert_eq!(SUBPROTOCOL, response.headers()[tungstenite::http::header::SEC_WEBSOCKET_PROTOCOL]);
+        if let tungstenite::stream::MaybeTlsStream::Plain(stream) = client.get_ref() {
+            stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        }
+        let mut exchange = |message: Value| -> Value {
+            client.send(Message::Text(message.to_string())).unwrap();
+            loop {
+                let reply: Value = serde_json::from_str(client.read().unwrap().to_text().unwrap()).unwrap();
+                // skip publications on the way to the reply
+                if reply[0] != json!(EVENT) {
+                    return reply;
+                }
+            }
+        };
+
+        assert_eq!(json!(WELCOME), exchange(json!([HELLO, "realm1", {"roles": {"subscriber": {}, "caller": {}}}]))[0]);
+        assert_eq!(json!([SUBSCRIBED, 1, 1]), exchange(json!([SUBSCRIBE, 1, {}, "snakegame.event.clock_advanced"])));
+        let created = exchange(json!([CALL, 2, {}, "snakegame.create_snake", []]));
+        let snake_id = created[3][0].as_u64().unwrap();
+        let snake = exchange(json!([CALL, 3, {}, "snakegame.get_snake", [snake_id]]));
+        assert_eq!(json!(snake_id), snake[3][0]["snake_id"]);
+
+        // the clock's ticks get published to the subscriber
+        let event: Value = serde_json::from_str(client.read().unwrap().to_text().unwrap()).unwrap();
+        assert_eq!(json!(EVENT), event[0]);
+        assert_eq!(json!(1), event[1]);
+        assert_eq!(json!("clock_advanced"), event[4][0]["type"]);
+
+        client.send(Message::Text(json!([GOODBYE, {}, "wamp.close.close_realm"]).to_string())).unwrap();
+        loop {
+            let reply: Value = serde_json::from_str(client.read().unwrap().to_text().unwrap()).unwrap();
+            if reply[0] == json!(GOODBYE) {
+                break;
+            }
+        }
+        server.stop();
+    }
+}
@@ -0,0 +1,1146 @@
+pub mod wire {
+    // Compact binary encoding of events and game snapshots, for clients where
+    // JSON costs too much.
+    //
+    // Every message starts with the same header:
+    //   version (1 byte), coord_scale, time_scale, angle_scale
+    // An events message follows it with an event count, then for each event:
+    //   kind tag (1 byte), then the event_time, sequence and tick, each as the
+    //   change from the previous event's, then the kind fields
+    // Ids and counts are LEB128 varints. Coordinates, sizes and nutrition are
+    // multiplied by coord_scale, times by time_scale and angles by angle_scale,
+    // rounded, and written as zigzag varints - so a scale of 100 keeps two decimals.
+    // Durations, like a clock advance's delta_t, count as times. A state snapshot
+    // is a snake count followed by each snake's id, location, velocity and size.
+    // Names are a byte count followed by the UTF-8 bytes, and a food removal's
+    // reason is a byte: 0 consumed, 1 expired.
+    //
+    // A snapshot message follows the header with one GameSnapshot: its version,
+    // the config, seed, time, tick, next_sequence, last_state_snapshot and the
+    // next ids, then a count and each of the snakes, the food, the team scores
+    // and the names, every field in the order GameSnapshot declares them. Times
+    // are absolute here. Enums and flags are a byte: the mode is 0 for continuous
+    // or 1 for grid and its tick_length, the win condition 0 for none, 1 for last
+    // team standing or 2 for a score target and the target, and the snapshot
+    // interval and food lifetime each 0 for none or 1 and the time.
+    use crate::eventqueue::eventqueue::{Event, EventKind, FoodRemovalReason, SnakeState};
+    use crate::game::common::{Coordinates, Velocity};
+    use crate::game::game::GameConfig;
+    use crate::rules::rules::GameMode;
+    use crate::snapshot::snapshot::{FoodSnapshot, GameSnapshot, SnakeName, SnakeSnapshot, TeamScore};
+    use crate::team::team::{TeamRules, WinCondition};
+    use std::fmt;
+
+    // version 2 added sequence numbers and ticks, 3 the kinds from food_removed
+    // on, 4 snake_removed, 5 names, food ages and food lifetimes in snapshots
+    pub const WIRE_VERSION: u8 = 5;
+
+    const TAG_SNAKE_BORN: u8 = 0;
+    const TAG_SNAKE_KILLED: u8 = 1;
+    const TAG_SNAKE_TURNED: u8 = 2;
+    const TAG_SNAKE_ATE: u8 = 3;
+    const TAG_FOOD_ADDED: u8 = 4;
+    const TAG_CLOCK_ADVANCED: u8 = 5;
+    const TAG_STATE_SNAPSHOT: u8 = 6;
+    const TAG_FOOD_REMOVED: u8 = 7;
+    const TAG_SNAKE_GREW: u8 = 8;
+    const TAG_SNAKE_COLLIDED: u8 = 9;
+    const TAG_SNAKE_BOOSTED: u8 = 10;
+    const TAG_SNAKE_RENAMED: u8 = 11;
+    const TAG_SCORE_CHANGED: u8 = 12;
+    const TAG_GAME_STARTED: u8 = 13;
+    const TAG_GAME_ENDED: u8 = 14;
+    const TAG_SNAKE_REMOVED: u8 = 15;
+
+    const REASON_CONSUMED: u8 = 0;
+    const REASON_EXPIRED: u8 = 1;
+
+    const MODE_CONTINUOUS: u8 = 0;
+    const MODE_GRID: u8 = 1;
+
+    const WIN_NO_WINNER: u8 = 0;
+    const WIN_LAST_TEAM_STANDING: u8 = 1;
+    const WIN_SCORE_TARGET: u8 = 2;
+
+    #[derive(Debug, PartialEq)]
+    pub enum WireError {
+        // ran out of bytes partway through a message
+        Truncated,
+        UnsupportedVersion(u8),
+        UnknownKind(u8),
+        // a varint longer than fits in 64 bits
+        BadVarint,
+        // a scale of zero in the header
+        BadScale,
+        // bytes left over after the last event
+        TrailingBytes,
+        // a name that isn't UTF-8
+        BadName,
+        UnknownReason(u8),
+        // in a snapshot, a byte that's none of the values its field can take
+        BadByte(u8),
+    }
+    impl std::error::Error for WireError {}
+    impl fmt::Display for WireError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                WireError::Truncated => write!(f, "Message ends too soon!"),
+                WireError::UnsupportedVersion(version) => write!(f, "Unsupported wire version {}!", version),
+                WireError::UnknownKind(tag) => write!(f, "Unknown event kind {}!", tag),
+                WireError::BadVarint => write!(f, "Malformed varint!"),
+                WireError::BadScale => write!(f, "Scale can't be zero!"),
+                WireError::TrailingBytes => write!(f, "Unexpected bytes after the last event!"),
+                WireError::BadName => write!(f, "Name isn't valid UTF-8!"),
+                WireError::UnknownReason(reason) => write!(f, "Unknown food removal reason {}!", reason),
+                WireError::BadByte(byte) => write!(f, "Unexpected byte {} in a snapshot!", byte),
+            }
+        }
+    }
+
+    // How finely values are quantized - units per 1.0 of game distance, seconds and radians
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    pub struct WireFormat {
+        pub coord_scale: u32,
+        pub time_scale: u32,
+        pub angle_scale: u32,
+    }
+    impl Default for WireFormat {
+        fn default() -> Self {
+            WireFormat {
+                coord_scale: 100,
+                time_scale: 1000,
+                angle_scale: 10000,
+            }
+        }
+    }
+
+    impl WireFormat {
+        pub fn encode_events(&self, events: &[Event]) -> Vec<u8> {
+            let mut writer = Writer { format: *self, bytes: Vec::new() };
+            writer.header();
+            writer.varint(events.len() as u64);
+            let mut previous = Previous::default();
+            for event in events {
+                previous = writer.event(event, previous);
+            }
+            writer.bytes
+        }
+
+        pub fn encode_snapshot(&self, snapshot: &GameSnapshot) -> Vec<u8> {
+            let mut writer = Writer { format: *self, bytes: Vec::new() };
+            writer.header();
+            writer.snapshot(snapshot);
+            writer.bytes
+        }
+
+        // The value a decoder will see after v has been through the wire
+        pub fn quantize_coord(&self, v: f64) -> f64 {
+            quantize(v, self.coord_scale) as f64 / self.coord_scale as f64
+        }
+    }
+
+    pub fn decode_events(bytes: &[u8]) -> Result<Vec<Event>, WireError> {
+        let mut reader = Reader { format: WireFormat::default(), bytes, pos: 0 };
+        reader.header()?;
+        let count = reader.varint()?;
+        // don't trust the count for allocation - every event takes at least two bytes
+        let mut events = Vec::with_capacity((count as usize).min(bytes.len() / 2));
+        let mut previous = Previous::default();
+        for _i in 0..count {
+            let (event, this_one) = reader.event(previous)?;
+            previous = this_one;
+            events.push(event);
+        }
+        reader.finish()?;
+        Ok(events)
+    }
+
+    pub fn decode_snapshot(bytes: &[u8]) -> Result<GameSnapshot, WireError> {
+        let mut reader = Reader { format: WireFormat::default(), bytes, pos: 0 };
+        reader.header()?;
+        let snapshot = reader.snapshot()?;
+        reader.finish()?;
+        Ok(snapshot)
+    }
+
+    fn kind_tag(kind: &EventKind) -> u8 {
+        match kind {
+            EventKind::SnakeBorn { .. } => TAG_SNAKE_BORN,
+            EventKind::SnakeKilled { .. } => TAG_SNAKE_KILLED,
+            EventKind::SnakeTurned { .. } => TAG_SNAKE_TURNED,
+            EventKind::SnakeAte { .. } => TAG_SNAKE_ATE,
+            EventKind::FoodAdded { .. } => TAG_FOOD_ADDED,
+            EventKind::ClockAdvanced { .. } => TAG_CLOCK_ADVANCED,
+            EventKind::StateSnapshot { .. } => TAG_STATE_SNAPSHOT,
+            EventKind::FoodRemoved { .. } => TAG_FOOD_REMOVED,
+            EventKind::SnakeGrew { .. } => TAG_SNAKE_GREW,
+            EventKind::SnakeCollided { .. } => TAG_SNAKE_COLLIDED,
+            EventKind::SnakeBoosted { .. } => TAG_SNAKE_BOOSTED,
+            EventKind::SnakeRenamed { .. } => TAG_SNAKE_RENAMED,
+            EventKind::ScoreChanged { .. } => TAG_SCORE_CHANGED,
+            EventKind::GameStarted => TAG_GAME_STARTED,
+            EventKind::GameEnded { .. } => TAG_GAME_ENDED,
+            EventKind::SnakeRemoved { .. } => TAG_SNAKE_REMOVED,
+        }
+    }
+
+    fn quantize(v: f64, scale: u32) -> i64 {
+        // `as` saturates, and turns NaN into 0
+        (v * scale as f64).round() as i64
+    }
+
+    fn zigzag(v: i64) -> u64 {
+        ((v << 1) ^ (v >> 63)) as u64
+    }
+
+    fn unzigzag(v: u64) -> i64 {
+        ((v >> 1) as i64) ^ -((v & 1) as i64)
+    }
+
+    // What the next event's time, sequence and tick are written relative to
+    #[derive(Copy, Clone, Default)]
+    struct Previous {
+        // quantized
+        time: i64,
+        sequence: u64,
+        tick: u64,
+    }
+
+    struct Writer {
+        format: WireFormat,
+        bytes: Vec<u8>,
+    }
+    impl Writer {
+        fn header(&mut self) {
+            self.bytes.push(WIRE_VERSION);
+            self.varint(self.format.coord_scale as u64);
+            self.varint(self.format.time_scale as u64);
+            self.varint(self.format.angle_scale as u64);
+        }
+
+        fn varint(&mut self, mut v: u64) {
+            while v >= 0x80 {
+                self.bytes.push((v as u8) | 0x80);
+                v >>= 7;
+            }
+            self.bytes.push(v as u8);
+        }
+
+        fn signed(&mut self, v: i64) {
+            self.varint(zigzag(v));
+        }
+
+        fn coord(&mut self, v: f64) {
+            self.signed(quantize(v, self.format.coord_scale));
+        }
+
+        fn angle(&mut self, v: f64) {
+            self.signed(quantize(v, self.format.angle_scale));
+        }
+
+        fn time(&mut self, v: f64) {
+            self.signed(quantize(v, self.format.time_scale));
+        }
+
+        fn coordinates(&mut self, loc: Coordinates) {
+            self.coord(loc.x);
+            self.coord(loc.y);
+        }
+
+        fn velocity(&mut self, vel: Velocity) {
+            self.coord(vel.delta_x);
+            self.coord(vel.delta_y);
+        }
+
+        fn optional_id(&mut self, id: Option<usize>) {
+            // 0 for none, otherwise one more than the id
+            match id {
+                None => self.varint(0),
+                Some(id) => self.varint(id as u64 + 1),
+            }
+        }
+
+        fn name(&mut self, name: &str) {
+            self.varint(name.len() as u64);
+            self.bytes.extend_from_slice(name.as_bytes());
+        }
+
+        // Times, sequences and ticks go as the change from the previous one, so
+        // in a busy tick they cost a byte each. Returns what to delta the next
+        // event against.
+        fn event(&mut self, event: &Event, previous: Previous) -> Previous {
+            let this_one = Previous {
+                time: quantize(event.event_time, self.format.time_scale),
+                sequence: event.sequence,
+                tick: event.tick,
+            };
+            self.bytes.push(kind_tag(&event.kind));
+            self.signed(this_one.time.wrapping_sub(previous.time));
+            self.signed(this_one.sequence.wrapping_sub(previous.sequence) as i64);
+            self.signed(this_one.tick.wrapping_sub(previous.tick) as i64);
+            match &event.kind {
+                &EventKind::SnakeBorn { snake_id, loc, vel, size, team } => {
+                    self.varint(snake_id as u64);
+                    self.coordinates(loc);
+                    self.velocity(vel);
+                    self.coord(size);
+                    self.optional_id(team);
+                }
+                &EventKind::SnakeKilled { snake_id } | &EventKind::SnakeRemoved { snake_id } => {
+                    self.varint(snake_id as u64);
+                }
+                &EventKind::SnakeTurned { snake_id, rad_relative } => {
+                    self.varint(snake_id as u64);
+                    self.angle(rad_relative);
+                }
+                &EventKind::SnakeAte { snake_id, food_id } => {
+                    self.varint(snake_id as u64);
+                    self.varint(food_id as u64);
+                }
+                &EventKind::FoodAdded { food_id, loc, nutrition } => {
+                    self.varint(food_id as u64);
+                    self.coordinates(loc);
+                    self.coord(nutrition);
+                }
+                &EventKind::ClockAdvanced { delta_t } => {
+                    self.signed(quantize(delta_t, self.format.time_scale));
+                }
+                EventKind::StateSnapshot { snakes } => {
+                    self.varint(snakes.len() as u64);
+                    for snake in snakes {
+                        self.varint(snake.snake_id as u64);
+                        self.coordinates(snake.loc);
+                        self.velocity(snake.vel);
+                        self.coord(snake.size);
+                    }
+                }
+                &EventKind::FoodRemoved { food_id, reason } => {
+                    self.varint(food_id as u64);
+                    self.bytes.push(match reason {
+                        FoodRemovalReason::Consumed => REASON_CONSUMED,
+                        FoodRemovalReason::Expired => REASON_EXPIRED,
+                    });
+                }
+                &EventKind::SnakeGrew { snake_id, size } => {
+                    self.varint(snake_id as u64);
+                    self.coord(size);
+                }
+                &EventKind::SnakeCollided { snake_id, other_snake_id, loc } => {
+                    self.varint(snake_id as u64);
+                    self.varint(other_snake_id as u64);
+                    self.coordinates(loc);
+                }
+                &EventKind::SnakeBoosted { snake_id, speed } => {
+                    self.varint(snake_id as u64);
+                    self.coord(speed);
+                }
+                EventKind::SnakeRenamed { snake_id, name } => {
+                    self.varint(*snake_id as u64);
+                    self.name(name);
+                }
+                &EventKind::ScoreChanged { snake_id, score } => {
+                    self.varint(snake_id as u64);
+                    self.coord(score);
+                }
+                EventKind::GameStarted => (),
+                &EventKind::GameEnded { winner } => {
+                    self.optional_id(winner);
+                }
+            }
+            this_one
+        }
+
+        fn snapshot(&mut self, snapshot: &GameSnapshot) {
+            self.varint(snapshot.version as u64);
+            self.config(&snapshot.config);
+            self.varint(snapshot.seed);
+            self.time(snapshot.time);
+            self.varint(snapshot.tick);
+            self.varint(snapshot.next_sequence);
+            self.time(snapshot.last_state_snapshot);
+            self.varint(snapshot.next_snake_id as u64);
+            self.varint(snapshot.next_food_id as u64);
+            self.varint(snapshot.snakes.len() as u64);
+            for snake in snapshot.snakes.iter() {
+                self.varint(snake.snake_id as u64);
+                self.coordinates(snake.location);
+                self.velocity(snake.velocity);
+                self.time(snake.last_updated);
+                self.coord(snake.size);
+                self.coord(snake.score);
+                self.optional_id(snake.team);
+            }
+            self.varint(snapshot.food.len() as u64);
+            for food in snapshot.food.iter() {
+                self.varint(food.food_id as u64);
+                self.coordinates(food.location);
+                self.coord(food.nutrition);
+                self.time(food.added);
+            }
+            self.varint(snapshot.team_scores.len() as u64);
+            for team in snapshot.team_scores.iter() {
+                self.varint(team.team_id as u64);
+                self.coord(team.score);
+            }
+            self.varint(snapshot.names.len() as u64);
+            for name in snapshot.names.iter() {
+                self.varint(name.snake_id as u64);
+                self.name(&name.name);
+            }
+        }
+
+        fn config(&mut self, config: &GameConfig) {
+            self.coord(config.size);
+            match config.mode {
+                GameMode::Continuous => self.bytes.push(MODE_CONTINUOUS),
+                GameMode::Grid { tick_length } => {
+                    self.bytes.push(MODE_GRID);
+                    self.time(tick_length);
+                }
+            }
+            self.bytes.push(config.team_rules.friendly_fire as u8);
+            match config.team_rules.win_condition {
+                WinCondition::NoWinner => self.bytes.push(WIN_NO_WINNER),
+                WinCondition::LastTeamStanding => self.bytes.push(WIN_LAST_TEAM_STANDING),
+                WinCondition::ScoreTarget(target) => {
+                    self.bytes.push(WIN_SCORE_TARGET);
+                    self.coord(target);
+                }
+            }
+            match config.snapshot_interval {
+                None => self.bytes.push(0),
+                Some(interval) => {
+                    self.bytes.push(1);
+                    self.time(interval);
+                }
+            }
+            match config.food_lifetime {
+                None => self.bytes.push(0),
+                Some(lifetime) => {
+                    self.bytes.push(1);
+                    self.time(lifetime);
+                }
+            }
+        }
+    }
+
+    struct Reader<'a> {
+        format: WireFormat,
+        bytes: &'a [u8],
+        pos: usize,
+    }
+    impl<'a> Reader<'a> {
+        fn header(&mut self) -> Result<(), WireError> {
+            let version = self.byte()?;
+            if version != WIRE_VERSION {
+                return Err(WireError::UnsupportedVersion(version));
+            }
+            self.format = WireFormat {
+                coord_scale: self.scale()?,
+                time_scale: self.scale()?,
+                angle_scale: self.scale()?,
+            };
+            Ok(())
+        }
+
+        fn scale(&mut self) -> Result<u32, WireError> {
+            let scale = self.varint()?;
+            if scale == 0 || scale > u32::MAX as u64 {
+                return Err(WireError::BadScale);
+            }
+            Ok(scale as u32)
+        }
+
+        fn finish(&self) -> Result<(), WireError> {
+            if self.pos == self.bytes.len() {
+                Ok(())
+            } else {
+                Err(WireError::TrailingBytes)
+            }
+        }
+
+        fn byte(&mut self) -> Result<u8, WireError> {
+            let byte = *self.bytes.get(self.pos).ok_or(WireError::Truncated)?;
+            self.pos += 1;
+            Ok(byte)
+        }
+
+        fn varint(&mut self) -> Result<u64, WireError> {
+            let mut v: u64 = 0;
+            for shift in (0..64).step_by(7) {
+                let byte = self.byte()?;
+                let bits = (byte & 0x7f) as u64;
+                // the tenth byte only has room for one more bit
+                if shift == 63 && bits > 1 {
+                    return Err(WireError::BadVarint);
+                }
+                v |= bits << shift;
+                if byte & 0x80 == 0 {
+                    return Ok(v);
+                }
+            }
+            Err(WireError::BadVarint)
+        }
+
+        fn id(&mut self) -> Result<usize, WireError> {
+            Ok(self.varint()? as usize)
+        }
+
+        fn signed(&mut self) -> Result<i64, WireError> {
+            Ok(unzigzag(self.varint()?))
+        }
+
+        fn coord(&mut self) -> Result<f64, WireError> {
+            Ok(self.signed()? as f64 / self.format.coord_scale as f64)
+        }
+
+        fn angle(&mut self) -> Result<f64, WireError> {
+            Ok(self.signed()? as f64 / self.format.angle_scale as f64)
+        }
+
+        fn time(&mut self) -> Result<f64, WireError> {
+            Ok(self.signed()? as f64 / self.format.time_scale as f64)
+        }
+
+        fn flag(&mut self) -> Result<bool, WireError> {
+            match self.byte()? {
+                0 => Ok(false),
+                1 => Ok(true),
+                byte => Err(WireError::BadByte(byte)),
+            }
+        }
+
+        // A count of things that each take at least min_len bytes, which
+        // there have to be enough bytes left for
+        fn count(&mut self, min_len: usize) -> Result<usize, WireError> {
+            let count = self.varint()?;
+            if count > ((self.bytes.len() - self.pos) / min_len) as u64 {
+                return Err(WireError::Truncated);
+            }
+            Ok(count as usize)
+        }
+
+        fn coordinates(&mut self) -> Result<Coordinates, WireError> {
+            Ok(Coordinates { x: self.coord()?, y: self.coord()? })
+        }
+
+        fn velocity(&mut self) -> Result<Velocity, WireError> {
+            Ok(Velocity { delta_x: self.coord()?, delta_y: self.coord()? })
+        }
+
+        fn optional_id(&mut self) -> Result<Option<usize>, WireError> {
+            match self.varint()? {
+                0 => Ok(None),
+                id => Ok(Some((id - 1) as usize)),
+            }
+        }
+
+        fn name(&mut self) -> Result<String, WireError> {
+            let len = self.varint()?;
+            // don't trust the length for allocation either
+            if len > (self.bytes.len() - self.pos) as u64 {
+                return Err(WireError::Truncated);
+            }
+            let end = self.pos + len as usize;
+            let name = std::str::from_utf8(&self.bytes[self.pos..end]).map_err(|_| WireError::BadName)?;
+            self.pos = end;
+            Ok(name.to_string())
+        }
+
+        fn reason(&mut self) -> Result<FoodRemovalReason, WireError> {
+            match self.byte()? {
+                REASON_CONSUMED => Ok(FoodRemovalReason::Consumed),
+                REASON_EXPIRED => Ok(FoodRemovalReason::Expired),
+                reason => Err(WireError::UnknownReason(reason)),
+            }
+        }
+
+        fn event(&mut self, previous: Previous) -> Result<(Event, Previous), WireError> {
+            let tag = self.byte()?;
+            let this_one = Previous {
+                time: previous.time.wrapping_add(self.signed()?),
+                sequence: previous.sequence.wrapping_add(self.signed()? as u64),
+                tick: previous.tick.wrapping_add(self.signed()? as u64),
+            };
+            let kind = match tag {
+                TAG_SNAKE_BORN => EventKind::SnakeBorn {
+                    snake_id: self.id()?,
+                    loc: self.coordinates()?,
+                    vel: self.velocity()?,
+                    size: self.coord()?,
+                    team: self.optional_id()?,
+                },
+                TAG_SNAKE_KILLED => EventKind::SnakeKilled { snake_id: self.id()? },
+                TAG_SNAKE_TURNED => EventKind::SnakeTurned {
+                    snake_id: self.id()?,
+                    rad_relative: self.angle()?,
+                },
+                TAG_SNAKE_ATE => EventKind::SnakeAte {
+                    snake_id: self.id()?,
+                    food_id: self.id()?,
+                },
+                TAG_FOOD_ADDED => EventKind::FoodAdded {
+                    food_id: self.id()?,
+                    loc: self.coordinates()?,
+                    nutrition: self.coord()?,
+                },
+                TAG_CLOCK_ADVANCED => EventKind::ClockAdvanced {
+                    delta_t: self.signed()? as f64 / self.format.time_scale as f64,
+                },
+                TAG_STATE_SNAPSHOT => {
+                    let count = self.varint()?;
+                    let mut snakes = Vec::new();
+                    for _i in 0..count {
+                        snakes.push(SnakeState {
+                            snake_id: self.id()?,
+                            loc: self.coordinates()?,
+                            vel: self.velocity()?,
+                            size: self.coord()?,
+                        });
+                    }
+                    EventKind::StateSnapshot { snakes }
+                }
+                TAG_FOOD_REMOVED => EventKind::FoodRemoved {
+                    food_id: self.id()?,
+                    reason: self.reason()?,
+                },
+                TAG_SNAKE_GREW => EventKind::SnakeGrew {
+                    snake_id: self.id()?,
+                    size: self.coord()?,
+                },
+                TAG_SNAKE_COLLIDED => EventKind::SnakeCollided {
+                    snake_id: self.id()?,
+                    other_snake_id: self.id()?,
+                    loc: self.coordinates()?,
+                },
+                TAG_SNAKE_BOOSTED => EventKind::SnakeBoosted {
+                    snake_id: self.id()?,
+                    speed: self.coord()?,
+                },
+                TAG_SNAKE_RENAMED => EventKind::SnakeRenamed {
+                    snake_id: self.id()?,
+                    name: self.name()?,
+                },
+                TAG_SCORE_CHANGED => EventKind::ScoreChanged {
+                    snake_id: self.id()?,
+                    score: self.coord()?,
+                },
+                TAG_GAME_STARTED => EventKind::GameStarted,
+                TAG_GAME_ENDED => EventKind::GameEnded { winner: self.optional_id()? },
+                TAG_SNAKE_REMOVED => EventKind::SnakeRemoved { snake_id: self.id()? },
+                tag => return Err(WireError::UnknownKind(tag)),
+            };
+            let event = Event {
+                kind,
+                event_time: this_one.time as f64 / self.format.time_scale as f64,
+                sequence: this_one.sequence,
+                tick: this_one.tick,
+            };
+            Ok((event, this_one))
+        }
+
+        fn snapshot(&mut self) -> Result<GameSnapshot, WireError> {
+            let version = self.varint()?;
+            if version > u32::MAX as u64 {
+                return Err(WireError::BadVarint);
+            }
+            let config = self.config()?;
+            let seed = self.varint()?;
+            let time = self.time()?;
+            let tick = self.varint()?;
+            let next_sequence = self.varint()?;
+            let last_state_snapshot = self.time()?;
+            let next_snake_id = self.id()?;
+            let next_food_id = self.id()?;
+            // a snake's fields take at least nine bytes, food's five, a team's or a name's two
+            let mut snakes = Vec::new();
+            for _i in 0..self.count(9)? {
+                snakes.push(SnakeSnapshot {
+                    snake_id: self.id()?,
+                    location: self.coordinates()?,
+                    velocity: self.velocity()?,
+                    last_updated: self.time()?,
+                    size: self.coord()?,
+                    score: self.coord()?,
+                    team: self.optional_id()?,
+                });
+            }
+            let mut food = Vec::new();
+            for _i in 0..self.count(5)? {
+                food.push(FoodSnapshot {
+                    food_id: self.id()?,
+                    location: self.coordinates()?,
+                    nutrition: self.coord()?,
+                    added: self.time()?,
+                });
+            }
+            let mut team_scores = Vec::new();
+            for _i in 0..self.count(2)? {
+                team_scores.push(TeamScore {
+                    team_id: self.id()?,
+                    score: self.coord()?,
+                });
+            }
+            let mut names = Vec::new();
+            for _i in 0..self.count(2)? {
+                names.push(SnakeName {
+                    snake_id: self.id()?,
+                    name: self.name()?,
+                });
+            }
+            Ok(GameSnapshot {
+                version: version as u32,
+                config,
+                seed,
+                time,
+                tick,
+                next_sequence,
+                last_state_snapshot,
+                next_snake_id,
+                next_food_id,
+                snakes,
+                food,
+                team_scores,
+                names,
+            })
+        }
+
+        fn config(&mut self) -> Result<GameConfig, WireError> {
+            let size = self.coord()?;
+            let mode = match self.byte()? {
+                MODE_CONTINUOUS => GameMode::Continuous,
+                MODE_GRID => GameMode::Grid { tick_length: self.time()? },
+                byte => return Err(WireError::BadByte(byte)),
+            };
+            let friendly_fire = self.flag()?;
+            let win_condition = match self.byte()? {
+                WIN_NO_WINNER => WinCondition::NoWinner,
+                WIN_LAST_TEAM_STANDING => WinCondition::LastTeamStanding,
+                WIN_SCORE_TARGET => WinCondition::ScoreTarget(self.coord()?),
+                byte => return Err(WireError::BadByte(byte)),
+            };
+            let snapshot_interval = if self.flag()? {
+                Some(self.time()?)
+            } else {
+                None
+            };
+            let food_lifetime = if self.flag()? {
+                Some(self.time()?)
+            } else {
+                None
+            };
+            Ok(GameConfig {
+                size,
+                mode,
+                team_rules: TeamRules { friendly_fire, win_condition },
+                snapshot_interval,
+                food_lifetime,
+            })
+        }
+    }
+
+    fn _sample_events() -> Vec<Event> {
+        // one of each kind, with values that survive the default quantization exactly
+        vec![
+            Event {
+                kind: EventKind::FoodAdded {
+                    food_id: 0,
+                    loc: Coordinates { x: -12.25, y: 40.5 },
+                    nutrition: 1.0,
+                },
+                event_time: 0.0,
+                sequence: 100,
+                tick: 20,
+            },
+            Event {
+                kind: EventKind::SnakeBorn {
+                    snake_id: 300,
+                    loc: Coordinates { x: 0.0, y: 0.0 },
+                    vel: Velocity { delta_x: 0.0, delta_y: 1.0 },
+                    size: 1.0,
+                    team: Some(2),
+                },
+                event_time: 0.0,
+                sequence: 101,
+                tick: 20,
+            },
+            Event {
+                kind: EventKind::SnakeBorn {
+                    snake_id: 301,
+                    loc: Coordinates { x: 0.0, y: 0.0 },
+                    vel: Velocity { delta_x: 0.0, delta_y: 1.0 },
+                    size: 1.0,
+                    team: None,
+                },
+                event_time: 0.25,
+                sequence: 102,
+                tick: 20,
+            },
+            Event {
+                kind: EventKind::SnakeTurned { snake_id: 300, rad_relative: -0.5 },
+                event_time: 1.5,
+                sequence: 103,
+                tick: 21,
+            },
+            Event {
+                kind: EventKind::SnakeAte { snake_id: 300, food_id: 0 },
+                event_time: 2.0,
+                sequence: 104,
+                tick: 21,
+            },
+            Event {
+                kind: EventKind::ClockAdvanced { delta_t: 0.5 },
+                event_time: 2.0,
+                sequence: 105,
+                tick: 21,
+            },
+            Event {
+                kind: EventKind::StateSnapshot {
+                    snakes: vec![
+                        SnakeState {
+                            snake_id: 300,
+                            loc: Coordinates { x: -1.5, y: 2.0 },
+                            vel: Velocity { delta_x: -1.0, delta_y: 0.0 },
+                            size: 1.1,
+                        },
+                        SnakeState {
+                            snake_id: 301,
+                            loc: Coordinates { x: 0.0, y: 1.75 },
+                            vel: Velocity { delta_x: 0.0, delta_y: 1.0 },
+                            size: 1.0,
+                        },
+                    ],
+                },
+                event_time: 2.0,
+                sequence: 106,
+                tick: 22,
+            },
+            // out of time and tick order, to exercise negative deltas
+            Event {
+                kind: EventKind::SnakeKilled { snake_id: 301 },
+                event_time: 1.75,
+                sequence: 107,
+                tick: 19,
+            },
+            Event {
+                kind: EventKind::GameStarted,
+                event_time: 0.0,
+                sequence: 108,
+                tick: 0,
+            },
+            Event {
+                kind: EventKind::FoodRemoved { food_id: 0, reason: FoodRemovalReason::Consumed },
+                event_time: 2.0,
+                sequence: 109,
+                tick: 21,
+            },
+            Event {
+                kind: EventKind::FoodRemoved { food_id: 1, reason: FoodRemovalReason::Expired },
+                event_time: 2.0,
+                sequence: 110,
+                tick: 21,
+            },
+            Event {
+                kind: EventKind::SnakeGrew { snake_id: 300, size: 1.1 },
+                event_time: 2.0,
+                sequence: 111,
+                tick: 21,
+            },
+            Event {
+                kind: EventKind::ScoreChanged { snake_id: 300, score: 1.0 },
+                event_time: 2.0,
+                sequence: 112,
+                tick: 21,
+            },
+            Event {
+                kind: EventKind::SnakeCollided {
+                    snake_id: 300,
+                    other_snake_id: 301,
+                    loc: Coordinates { x: 0.0, y: 1.75 },
+                },
+                event_time: 2.0,
+                sequence: 113,
+                tick: 21,
+            },
+            Event {
+                kind: EventKind::SnakeBoosted { snake_id: 300, speed: 2.5 },
+                event_time: 2.0,
+                sequence: 114,
+                tick: 21,
+            },
+            Event {
+                kind: EventKind::SnakeRenamed { snake_id: 300, name: "Sir Hiss 🐍".to_string() },
+                event_time: 2.0,
+                sequence: 115,
+                tick: 21,
+            },
+            Event {
+                kind: EventKind::GameEnded { winner: Some(2) },
+                event_time: 2.0,
+                sequence: 116,
+                tick: 21,
+            },
+            Event {
+                kind: EventKind::GameEnded { winner: None },
+                event_time: 2.0,
+                sequence: 117,
+                tick: 21,
+            },
+            Event {
+                kind: EventKind::SnakeRemoved { snake_id: 300 },
+                event_time: 2.0,
+                sequence: 118,
+                tick: 21,
+            },
+        ]
+    }
+
+    fn _sample_snapshot() -> GameSnapshot {
+        // a grid game with teams, again with values the default quantization keeps exactly
+        let mut config = GameConfig { mode: GameMode::classic_grid(), snapshot_interval: Some(2.5), food_lifetime: Some(30.0), ..GameConfig::default() };
+        config.team_rules.win_condition = WinCondition::ScoreTarget(20.0);
+        GameSnapshot {
+            version: crate::snapshot::snapshot::SNAPSHOT_VERSION,
+            config,
+            seed: u64::MAX - 7,
+            time: 12.5,
+            tick: 25,
+            next_sequence: 300,
+            last_state_snapshot: 10.0,
+            next_snake_id: 4,
+            next_food_id: 140,
+            snakes: vec![
+                SnakeSnapshot {
+                    snake_id: 1,
+                    location: Coordinates { x: -3.0, y: 17.0 },
+                    velocity: Velocity { delta_x: 0.0, delta_y: -1.0 },
+                    last_updated: 12.5,
+                    size: 1.25,
+                    score: 2.5,
+                    team: Some(0),
+                },
+                SnakeSnapshot {
+                    snake_id: 3,
+                    location: Coordinates { x: 40.0, y: -49.0 },
+                    velocity: Velocity { delta_x: 1.0, delta_y: 0.0 },
+                    last_updated: 12.0,
+                    size: 1.0,
+                    score: 0.0,
+                    team: None,
+                },
+            ],
+            food: vec![
+                FoodSnapshot { food_id: 0, location: Coordinates { x: 5.0, y: 5.0 }, nutrition: 1.0, added: 0.0 },
+                FoodSnapshot { food_id: 139, location: Coordinates { x: -50.0, y: 0.0 }, nutrition: 2.5, added: 11.5 },
+            ],
+            team_scores: vec![TeamScore { team_id: 0, score: 6.5 }, TeamScore { team_id: 7, score: 0.0 }],
+            names: vec![SnakeName { snake_id: 3, name: "Sir Hiss 🐍".to_string() }],
+        }
+    }
+
+    #[test]
+    fn t_varint() {
+        for v in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX].iter() {
+            let mut writer = Writer { format: WireFormat::default(), bytes: Vec::new() };
+            writer.varint(*v);
+            let mut reader = Reader { format: WireFormat::default(), bytes: &writer.bytes, pos: 0 };
+            assert_eq!(*v, reader.varint().unwrap());
+            assert!(reader.finish().is_ok());
+        }
+        for v in [0, -1, 1, -64, 64, i64::MIN, i64::MAX].iter() {
+            assert_eq!(*v, unzigzag(zigzag(*v)));
+        }
+        // small values fit in a byte, sign and all
+        assert!(zigzag(-64) < 0x80);
+    }
+
+    #[test]
+    fn t_round_trip() {
+        let events = _sample_events();
+        let bytes = WireFormat::default().encode_events(&events);
+        assert_eq!(events, decode_events(&bytes).unwrap());
+
+        // empty batches are fine too
+        let bytes = WireFormat::default().encode_events(&[]);
+        assert!(decode_events(&bytes).unwrap().is_empty());
+    }
+
+    #[test]
+    fn t_snapshot_round_trip() {
+        let snapshot = _sample_snapshot();
+        let bytes = WireFormat::default().encode_snapshot(&snapshot);
+        assert_eq!(snapshot, decode_snapshot(&bytes).unwrap());
+
+        // and a game that's been played goes over close enough to carry on with
+        use crate::game::game::Game;
+        let mut game = Game::new_seeded(GameConfig::default(), 29);
+        game.create_team_snake(1, 0.0).unwrap();
+        game.create_snake(0.0).unwrap();
+        for _i in 0..10 {
+            game.advance_clock(0.1);
+        }
+        let decoded = decode_snapshot(&WireFormat::default().encode_snapshot(&game.snapshot())).unwrap();
+        assert_eq!(game.snapshot().seed, decoded.seed);
+        assert_eq!(game.snapshot().tick, decoded.tick);
+        assert!(Game::restore(&decoded).is_ok());
+    }
+
+    #[test]
+    fn t_snapshot_decode_errors() {
+        use rand::{Rng, SeedableRng};
+        let bytes = WireFormat::default().encode_snapshot(&_sample_snapshot());
+        for len in 0..bytes.len() {
+            assert!(decode_snapshot(&bytes[..len]).is_err());
+        }
+        let mut extra = bytes.clone();
+        extra.push(0);
+        assert_eq!(Err(WireError::TrailingBytes), decode_snapshot(&extra));
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[0] = WIRE_VERSION + 1;
+        assert_eq!(Err(WireError::UnsupportedVersion(WIRE_VERSION + 1)), decode_snapshot(&wrong_version));
+
+        // a game mode there's no such thing as: header, snapshot version, size 0, mode
+        assert_eq!(Err(WireError::BadByte(7)), decode_snapshot(&[WIRE_VERSION, 1, 1, 1, 5, 0, 7]));
+
+        // more snakes than there are bytes for
+        let mut too_many = vec![WIRE_VERSION, 1, 1, 1, 5, 0, MODE_CONTINUOUS, 1, WIN_NO_WINNER, 0, 0];
+        too_many.extend_from_slice(&[0; 7]);
+        too_many.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0x0f]);
+        assert_eq!(Err(WireError::Truncated), decode_snapshot(&too_many));
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(29);
+        for _i in 0..2000 {
+            let mut garbled = bytes.clone();
+            let idx = rng.gen_range(0, garbled.len());
+            garbled[idx] = rng.gen();
+            let _ = decode_snapshot(&garbled);
+        }
+    }
+
+    #[test]
+    fn t_compact() {
+        // a turn is a handful of bytes, not a JSON object
+        let events = vec![Event {
+            kind: EventKind::SnakeTurned { snake_id: 3, rad_relative: 0.5 },
+            event_time: 10.0,
+            sequence: 0,
+            tick: 0,
+        }];
+        let header_len = WireFormat::default().encode_events(&[]).len();
+        let bytes = WireFormat::default().encode_events(&events);
+        assert!(bytes.len() - header_len <= 10);
+    }
+
+    #[test]
+    fn t_precision() {
+        use assert_approx_eq::assert_approx_eq;
+        let events = vec![Event {
+            kind: EventKind::FoodAdded {
+                food_id: 0,
+                loc: Coordinates { x: 1.23456, y: -9.87654 },
+                nutrition: 1.0,
+            },
+            event_time: 0.123456,
+            sequence: 0,
+            tick: 0,
+        }];
+
+        let coarse = WireFormat { coord_scale: 10, time_scale: 10, angle_scale: 10 };
+        let decoded = decode_events(&coarse.encode_events(&events)).unwrap();
+        assert_eq!(0.1, decoded[0].event_time);
+        match decoded[0].kind {
+            EventKind::FoodAdded { loc, .. } => {
+                assert_eq!(coarse.quantize_coord(1.23456), loc.x);
+                assert_approx_eq!(1.2, loc.x, 1e-9);
+                assert_approx_eq!(-9.9, loc.y, 1e-9);
+            }
+            _ => panic!("Expected FoodAdded"),
+        }
+
+        let fine = WireFormat { coord_scale: 100000, time_scale: 1000000, angle_scale: 10 };
+        let decoded = decode_events(&fine.encode_events(&events)).unwrap();
+        assert_approx_eq!(0.123456, decoded[0].event_time, 1e-9);
+        match decoded[0].kind {
+            EventKind::FoodAdded { loc, .. } => assert_approx_eq!(1.23456, loc.x, 1e-9),
+            _ => panic!("Expected FoodAdded"),
+        }
+    }
+
+    #[test]
+    fn t_decode_errors() {
+        let bytes = WireFormat::default().encode_events(&_sample_events());
+
+        // every truncation is an error, never a panic
+        for len in 0..bytes.len() {
+            assert!(decode_events(&bytes[..len]).is_err());
+        }
+        assert_eq!(Err(WireError::Truncated), decode_events(&[]));
+
+        let mut extra = bytes.clone();
+        extra.push(0);
+        assert_eq!(Err(WireError::TrailingBytes), decode_events(&extra));
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[0] = WIRE_VERSION + 1;
+        assert_eq!(Err(WireError::UnsupportedVersion(WIRE_VERSION + 1)), decode_events(&wrong_version));
+
+        // header with zero scale
+        assert_eq!(Err(WireError::BadScale), decode_events(&[WIRE_VERSION, 0, 1, 1, 0]));
+
+        // one event of an unknown kind
+        assert_eq!(Err(WireError::UnknownKind(99)), decode_events(&[WIRE_VERSION, 1, 1, 1, 1, 99, 0, 0, 0]));
+
+        // a name that isn't UTF-8, and one longer than the message
+        assert_eq!(Err(WireError::BadName), decode_events(&[WIRE_VERSION, 1, 1, 1, 1, TAG_SNAKE_RENAMED, 0, 0, 0, 0, 1, 0xff]));
+        assert_eq!(Err(WireError::Truncated), decode_events(&[WIRE_VERSION, 1, 1, 1, 1, TAG_SNAKE_RENAMED, 0, 0, 0, 0, 0x7f, b'a']));
+
+        // food removed for a reason there's no such thing as
+        assert_eq!(Err(WireError::UnknownReason(9)), decode_events(&[WIRE_VERSION, 1, 1, 1, 1, TAG_FOOD_REMOVED, 0, 0, 0, 0, 9]));
+
+        // a count claiming far more events than there are bytes
+        assert_eq!(Err(WireError::Truncated), decode_events(&[WIRE_VERSION, 1, 1, 1, 0xff, 0xff, 0xff, 0xff, 0x0f]));
+
+        // a varint that never ends
+        let mut endless = vec![WIRE_VERSION];
+        endless.extend_from_slice(&[0xff; 11]);
+        assert_eq!(Err(WireError::BadVarint), decode_events(&endless));
+    }
+
+    #[test]
+    fn t_decode_garbage() {
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand::rngs::StdRng::seed_from_u64(29);
+        let valid = WireFormat::default().encode_events(&_sample_events());
+        for _i in 0..2000 {
+            // random bytes, and valid messages with random bytes flipped
+            let mut bytes: Vec<u8> = (0..rng.gen_range(0, 64)).map(|_| rng.gen()).collect();
+            let _ = decode_events(&bytes);
+            bytes = valid.clone();
+            let idx = rng.gen_range(0, bytes.len());
+            bytes[idx] = rng.gen();
+            let _ = decode_events(&bytes);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn t_same_data_as_json() {
+        // whichever way an event travels, the client ends up with the same event
+        let events = _sample_events();
+        let from_json: Vec<Event> = serde_json::from_str(&serde_json::to_string(&events).unwrap()).unwrap();
+        let from_wire = decode_events(&WireFormat::default().encode_events(&events)).unwrap();
+        assert_eq!(from_json, from_wire);
+        assert_eq!(events, from_wire);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn t_snapshot_same_data_as_json() {
+        let snapshot = _sample_snapshot();
+        let from_json = GameSnapshot::from_json(&snapshot.to_json()).unwrap();
+        let from_wire = decode_snapshot(&WireFormat::default().encode_snapshot(&snapshot)).unwrap();
+        assert_eq!(from_json, from_wire);
+        assert_eq!(snapshot, from_wire);
+    }
+}